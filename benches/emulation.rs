@@ -0,0 +1,85 @@
+//! Emulation throughput, with and without a tracelog being kept.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::hint::black_box;
+use std::rc::Rc;
+
+use common::{rom_with, START};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nest::{Host, Nes, NullHost, Tracelog};
+
+/// A busy loop touching most addressing modes, so every frame is all CPU work.
+const BUSY_LOOP: &[u8] = &[
+    0xB5, 0x00, // LDA $00,X
+    0x69, 0x01, // ADC #$01
+    0x99, 0x00, 0x02, // STA $0200,Y
+    0x7D, 0x00, 0x03, // ADC $0300,X
+    0x91, 0x20, // STA ($20),Y
+    0xE8, // INX
+    0xC8, // INY
+    0xC6, 0x10, // DEC $10
+    0xD0, 0xEE, // BNE $8000
+    0x4C, 0x00, 0x80, // JMP $8000
+];
+
+/// Keeps a tracelog and throws every row away.
+struct DiscardingHost;
+
+impl Host for DiscardingHost {
+    fn console_log(&self, _msg: &str) {}
+    fn wants_tracelog(&self) -> bool {
+        true
+    }
+    fn add_tracelog(&self, row: &Tracelog) {
+        black_box(row);
+    }
+    fn update_ram(&self, _ram: &[u8]) {}
+    fn update_prg_rom(&self, _rom: &[u8]) {}
+    fn update_vram(&self, _ram: &[u8]) {}
+    fn update_chr_rom(&self, _rom: &[u8]) {}
+    fn force_screen_draw(&self) {}
+}
+
+fn boot(host: Rc<dyn Host>) -> Nes {
+    let mut nes = Nes::with_host(rom_with(&[(0x8000, BUSY_LOOP)], START), host).unwrap();
+    nes.reset();
+    nes
+}
+
+fn run_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_frame");
+    group.throughput(Throughput::Elements(1));
+
+    let mut nes = boot(Rc::new(NullHost));
+    group.bench_function("untraced", |b| b.iter(|| black_box(nes.run_frame())));
+
+    let mut nes = boot(Rc::new(DiscardingHost));
+    group.bench_function("traced", |b| b.iter(|| black_box(nes.run_frame())));
+
+    let mut nes = boot(Rc::new(NullHost));
+    nes.trace_start();
+    group.bench_function("trace_buffer", |b| b.iter(|| black_box(nes.run_frame())));
+
+    group.finish();
+}
+
+fn instructions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(1000));
+
+    let mut nes = boot(Rc::new(NullHost));
+    group.bench_function("untraced", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(nes.clock());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, run_frame, instructions);
+criterion_main!(benches);
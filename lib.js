@@ -47,13 +47,12 @@ const tracelogAsm = document.querySelector("#tracelog-asm");
 const tracelogReg = document.querySelector("#tracelog-reg");
 const tracelogs = [];
 
-export function addTracelog(pg, by, inst, regA, regX, regY, sp, p, f, cy) {
-    while (tracelogs.length >= 50) {
-        tracelogs.shift();
-    }
-
-    tracelogs.push({ pg, by, inst, regA, regX, regY, sp, p, f, cy });
+// nestest.log columns: everything before the registers is the instruction
+const TRACE_REGS_AT = 48;
 
+export function addTraceLines(text) {
+    tracelogs.push(...text.trimEnd().split("\n"));
+    tracelogs.splice(0, Math.max(0, tracelogs.length - 50));
 
     renderTracelog();
 }
@@ -62,25 +61,75 @@ function renderTracelog() {
     tracelogAsm.innerHTML = "";
     tracelogReg.innerHTML = "";
 
-    tracelogs.forEach(l => {
+    tracelogs.forEach(line => {
         const asm = document.createElement("tr");
-        insertToRow(asm, l.pg + ":");
-        insertToRow(asm, l.by);
-        insertToRow(asm, l.inst);
+        insertToRow(asm, line.slice(0, TRACE_REGS_AT).trimEnd());
         tracelogAsm.appendChild(asm);
 
         const reg = document.createElement("tr");
-        insertToRow(reg, "A:" + l.regA);
-        insertToRow(reg, "X:" + l.regX);
-        insertToRow(reg, "Y:" + l.regY)
-        insertToRow(reg, "SP:" + l.sp);
-        insertToRow(reg, "P:" + l.p);
-        insertToRow(reg, l.f);
-        insertToRow(reg, "CY:" + l.cy);
+        insertToRow(reg, line.slice(TRACE_REGS_AT));
         tracelogReg.appendChild(reg);
     })
 }
 
+const debugStatus = document.querySelector("#debug-status");
+
+export function showDebugState({ paused, reason, line, disasm, callStack, location, source, cdl }) {
+    document.querySelector("#cdl-summary").textContent = cdl;
+    debugStatus.textContent = paused ? `${reason}: ${location ? `${location} ` : ""}${line}` : "running";
+    document.querySelector("#debug-source").textContent = paused ? source : "";
+    document.querySelector("#debug-disasm").textContent = paused ? disasm : "";
+    document.querySelector("#debug-call-stack").textContent = paused ? callStack : "";
+}
+
+export function showDebugError(message) {
+    debugStatus.textContent = message;
+}
+
+export function addBreakpoint(id, label, remove) {
+    const item = document.createElement("li");
+    item.textContent = `#${id} ${label} `;
+
+    const button = document.createElement("button");
+    button.textContent = "x";
+    button.addEventListener("click", () => {
+        remove(id);
+        item.remove();
+    });
+
+    item.appendChild(button);
+    document.querySelector("#breakpoints").appendChild(item);
+}
+
+let shownCheats = null;
+
+// `list` has a line per cheat, starting with its id and whether it's on
+export function showCheats(list, toggle, remove) {
+    if (list === shownCheats) {
+        return;
+    }
+    shownCheats = list;
+
+    const items = list.split("\n").filter(line => line).map(line => {
+        const [id, state] = line.split(" ", 2);
+        const item = document.createElement("li");
+
+        const enabled = document.createElement("input");
+        enabled.type = "checkbox";
+        enabled.checked = state === "on";
+        enabled.addEventListener("change", () => toggle(Number(id), enabled.checked));
+
+        const button = document.createElement("button");
+        button.textContent = "x";
+        button.addEventListener("click", () => remove(Number(id)));
+
+        item.append(enabled, ` ${line.slice(id.length + state.length + 2)} `, button);
+        return item;
+    });
+
+    document.querySelector("#cheats").replaceChildren(...items);
+}
+
 export function updateRam(bytes) {
     fill("ram", bytes, 16)
 }
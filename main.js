@@ -1,5 +1,5 @@
 const worker = new Worker("worker.js", { type: "module" });
-import { addTracelog, consoleLog, drawScreen, updateCRom, updatePRom, updateRam, updateVRam } from "./lib.js";
+import { addBreakpoint, addTraceLines, consoleLog, showCheats, showDebugError, showDebugState, drawScreen, updateCRom, updatePRom, updateRam, updateVRam } from "./lib.js";
 
 let canvas = document.querySelector("#screen");
 
@@ -12,8 +12,32 @@ worker.onmessage = (e) => {
         case "consoleLog":
             consoleLog(data.msg);
             break;
-        case "addTracelog":
-            requestAnimationFrame(() => addTracelog(...data.args));
+        case "traceLines":
+            requestAnimationFrame(() => addTraceLines(data.lines));
+            break;
+        case "debugState":
+            requestAnimationFrame(() => {
+                showDebugState(data);
+                showCheats(data.cheats, (id, enabled) => debug("enableCheat", { id, enabled }), (id) => debug("removeCheat", { id }));
+            });
+            break;
+        case "breakpointAdded":
+            addBreakpoint(data.id, data.label, (id) => debug("removeBreakpoint", { id }));
+            break;
+        case "profile":
+            requestAnimationFrame(() => document.querySelector("#profile-report").textContent = data.report);
+            break;
+        case "search":
+            requestAnimationFrame(() => document.querySelector("#search-report").textContent = data.report);
+            break;
+        case "profileExported":
+            download("profile.folded", new TextEncoder().encode(data.text));
+            break;
+        case "cdlSaved":
+            download("game.cdl", data.bytes);
+            break;
+        case "debugError":
+            showDebugError(data.message);
             break;
         case "updateRam":
             requestAnimationFrame(() => updateRam(data.bytes));
@@ -33,6 +57,90 @@ worker.onmessage = (e) => {
     }
 }
 
+document.querySelector("#trace").addEventListener("change", (e) => {
+    worker.postMessage({ type: "trace", payload: { enabled: e.target.checked } });
+});
+
+function debug(command, args = {}) {
+    worker.postMessage({ type: "debug", payload: { command, ...args } });
+}
+
+document.querySelector("#debug-pause").addEventListener("click", () => debug("pause"));
+document.querySelector("#debug-resume").addEventListener("click", () => debug("resume"));
+document.querySelector("#debug-step-into").addEventListener("click", () => debug("stepInto"));
+document.querySelector("#debug-step-over").addEventListener("click", () => debug("stepOver"));
+document.querySelector("#debug-step-out").addEventListener("click", () => debug("stepOut"));
+document.querySelector("#debug-step-line").addEventListener("click", () => debug("stepLine"));
+document.querySelector("#debug-step-line-over").addEventListener("click", () => debug("stepLineOver"));
+document.querySelector("#debug-run-to").addEventListener("click", () => debug("runTo", { addr: document.querySelector("#debug-cursor").value }));
+
+document.querySelector("#bp-add").addEventListener("click", () => {
+    const kind = document.querySelector("#bp-kind");
+    // addresses or labels, the worker has the symbols to resolve them
+    const start = document.querySelector("#bp-start").value.trim();
+    const end = document.querySelector("#bp-end").value.trim();
+    const condition = document.querySelector("#bp-condition").value;
+
+    if (!start) {
+        showDebugError("breakpoint needs a start address");
+        return;
+    }
+
+    const range = end && end !== start ? `${start}-${end}` : start;
+    const label = `${kind.selectedOptions[0].text} ${range}${condition ? ` if ${condition}` : ""}`;
+    debug("addBreakpoint", { kind: Number(kind.value), start, end, condition, label });
+});
+
+document.querySelector("#symbols").addEventListener("change", async (e) => {
+    const file = e.target.files[0];
+    if (file) {
+        debug("loadSymbols", { name: file.name, text: await file.text() });
+    }
+});
+
+document.querySelector("#sources").addEventListener("change", async (e) => {
+    for (const file of e.target.files) {
+        debug("addSource", { name: file.name, text: await file.text() });
+    }
+});
+
+document.querySelector("#profiler").addEventListener("change", (e) => debug(e.target.checked ? "profilerStart" : "profilerStop"));
+document.querySelector("#profile-cumulative").addEventListener("change", (e) => debug("profileCumulative", { cumulative: e.target.checked }));
+document.querySelector("#profile-reset").addEventListener("click", () => debug("profilerReset"));
+document.querySelector("#profile-export").addEventListener("click", () => debug("profileExport"));
+document.querySelector("#cheat-add").addEventListener("click", () => {
+    const input = document.querySelector("#cheat-code");
+    debug("addCheat", { code: input.value });
+    input.value = "";
+});
+document.querySelector("#search-start").addEventListener("click", () => debug("searchStart", {
+    domain: document.querySelector("#search-domain").value,
+    width: document.querySelector("#search-width").value,
+    format: document.querySelector("#search-format").value,
+}));
+document.querySelector("#search-filter").addEventListener("click", () => debug("searchFilter", {
+    compare: document.querySelector("#search-compare").value,
+    value: document.querySelector("#search-value").value,
+}));
+document.querySelector("#search-undo").addEventListener("click", () => debug("searchUndo"));
+document.querySelector("#search-snapshot").addEventListener("click", () => debug("searchSnapshot"));
+document.querySelector("#cdl").addEventListener("change", (e) => debug(e.target.checked ? "cdlStart" : "cdlStop"));
+document.querySelector("#cdl-save").addEventListener("click", () => debug("cdlSave"));
+document.querySelector("#cdl-load").addEventListener("change", async (e) => {
+    const file = e.target.files[0];
+    if (file) {
+        debug("cdlLoad", { bytes: new Uint8Array(await file.arrayBuffer()) });
+    }
+});
+
+function download(name, bytes) {
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(new Blob([bytes]));
+    link.download = name;
+    link.click();
+    URL.revokeObjectURL(link.href);
+}
+
 async function run() {
     const ctx = canvas.getContext("2d");
     ctx.fillStyle = "black";
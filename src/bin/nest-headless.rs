@@ -0,0 +1,185 @@
+//! Runs an iNES ROM without a browser and dumps the machine state afterwards.
+//!
+//! ```text
+//! nest-headless <rom.nes> [--frames N] [--until-halt] [--until-pc ADDR]
+//!               [--png FILE] [--ram FILE] [--regs FILE]
+//!               [--trace FILE] [--compare-log FILE]
+//! ```
+//!
+//! The run always stops early if the CPU halts. With `--until-halt` the frame count
+//! becomes a budget, and running out of it before a halt is reported as a failure.
+//! Without `--regs` the CPU registers are printed to stdout as JSON.
+//!
+//! `--trace` writes a nestest.log style line per instruction. `--compare-log` instead
+//! steps the ROM against a golden log and reports the first line that differs.
+//!
+//! Only built with the `headless` feature: `cargo run --features headless --bin nest-headless -- ...`.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use nest::{Nes, NullHost};
+
+struct Options {
+    rom: String,
+    frames: usize,
+    until_halt: bool,
+    until_pc: Option<u16>,
+    png: Option<String>,
+    ram: Option<String>,
+    regs: Option<String>,
+    trace: Option<String>,
+    compare_log: Option<String>,
+}
+
+const USAGE: &str = "usage: nest-headless <rom.nes> [--frames N] [--until-halt] [--until-pc ADDR] [--png FILE] [--ram FILE] [--regs FILE] [--trace FILE] [--compare-log FILE]";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut rom = None;
+    let mut options = Options {
+        rom: String::new(),
+        frames: 60,
+        until_halt: false,
+        until_pc: None,
+        png: None,
+        ram: None,
+        regs: None,
+        trace: None,
+        compare_log: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+
+        match arg.as_str() {
+            "--frames" => {
+                let frames = value("--frames")?;
+                options.frames = frames.parse().map_err(|_| format!("invalid frame count {frames}"))?;
+            }
+            "--until-halt" => options.until_halt = true,
+            "--until-pc" => {
+                let pc = value("--until-pc")?;
+                let digits = pc.trim_start_matches('$').trim_start_matches("0x");
+                options.until_pc = Some(u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address {pc}"))?);
+            }
+            "--png" => options.png = Some(value("--png")?),
+            "--ram" => options.ram = Some(value("--ram")?),
+            "--regs" => options.regs = Some(value("--regs")?),
+            "--trace" => options.trace = Some(value("--trace")?),
+            "--compare-log" => options.compare_log = Some(value("--compare-log")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {flag}\n{USAGE}")),
+            path => rom = Some(path.to_string()),
+        }
+    }
+
+    options.rom = rom.ok_or(USAGE.to_string())?;
+    Ok(options)
+}
+
+/// Clocks the machine for `options.frames` frames, stopping early on a halt or the requested PC.
+fn run(nes: &mut Nes, options: &Options) {
+    let last_frame = nes.ppu().frame + options.frames;
+
+    while nes.ppu().frame < last_frame {
+        if !nes.is_running() {
+            break;
+        }
+
+        if options.until_pc == Some(nes.cpu().counter) {
+            break;
+        }
+
+        nes.clock();
+    }
+}
+
+fn write_png(path: &str, pixels: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), 256, 240);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+fn registers_json(nes: &Nes) -> String {
+    let cpu = nes.cpu();
+
+    format!(
+        "{{\"a\": {}, \"x\": {}, \"y\": {}, \"pc\": {}, \"sp\": {}, \"p\": {}, \"cycles\": {}, \"frame\": {}, \"running\": {}}}\n",
+        cpu.reg_a,
+        cpu.reg_x,
+        cpu.reg_y,
+        cpu.counter,
+        cpu.stack,
+        cpu.flags.to_byte(),
+        nes.total_cycles(),
+        nes.ppu().frame,
+        cpu.running
+    )
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{msg}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match headless(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("nest-headless: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn headless(options: &Options) -> Result<(), Box<dyn Error>> {
+    let rom_bytes = fs::read(&options.rom).map_err(|err| format!("{}: {err}", options.rom))?;
+    let mut nes = Nes::with_host(rom_bytes, Rc::new(NullHost))?;
+    nes.reset();
+
+    if let Some(path) = &options.compare_log {
+        let golden = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        let matched = nest::compare_log(&mut nes, &golden)?;
+        println!("{matched} lines match {path}");
+        return Ok(());
+    }
+
+    if let Some(path) = &options.trace {
+        nes.trace_to_file(path).map_err(|err| format!("{path}: {err}"))?;
+        nes.tracer_mut().set_capacity(0);
+        nes.tracer_mut().start();
+    }
+
+    run(&mut nes, options);
+    nes.tracer_mut().flush()?;
+
+    if options.until_halt && nes.is_running() {
+        return Err(format!("CPU still running after {} frames", options.frames).into());
+    }
+
+    if let Some(path) = &options.png {
+        write_png(path, &nes.get_screen_buffer())?;
+    }
+
+    if let Some(path) = &options.ram {
+        fs::write(path, format!("{:?}", nes.cpu().bus.ram))?;
+    }
+
+    let regs = registers_json(&nes);
+    match &options.regs {
+        Some(path) => fs::write(path, regs)?,
+        None => print!("{regs}"),
+    }
+
+    Ok(())
+}
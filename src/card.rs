@@ -1,4 +1,5 @@
-use crate::mapper::Mapper;
+use crate::mapper::{Mapper, NROM};
+use crate::rom::INes;
 
 pub struct Card {
     pub mapper: Box<dyn Mapper>
@@ -11,6 +12,11 @@ impl Card {
         }
     }
 
+    /// The board `ines` describes, with its ROM and however much cartridge RAM it has.
+    pub fn from_ines(ines: INes) -> Self {
+        Self::new(Box::new(NROM::new(ines.prg_rom, ines.chr_rom, ines.prg_ram_size)))
+    }
+
     pub fn cpu_read(&mut self, addr: u16) -> Option<u8> {
         self.mapper.cpu_read(addr)
     }
@@ -26,4 +32,17 @@ impl Card {
     pub fn ppu_write(&mut self, addr: u16, val: u8) {
         self.mapper.ppu_write(addr, val);
     }
+
+    /// Where in PRG ROM the CPU address `addr` reads from, if ROM is there.
+    pub fn prg_offset(&self, addr: u16) -> Option<usize> {
+        let rom = addr.checked_sub(0x8000)?;
+        let bank = self.mapper.prg_bank(rom)?;
+        Some(bank as usize * 0x4000 + (rom & 0x3FFF) as usize)
+    }
+
+    /// Where in CHR ROM the PPU address `addr` reads from, if ROM is there.
+    pub fn chr_offset(&self, addr: u16) -> Option<usize> {
+        let bank = self.mapper.chr_bank(addr)?;
+        Some(bank as usize * 0x400 + (addr & 0x3FF) as usize)
+    }
 }
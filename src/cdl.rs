@@ -0,0 +1,139 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// What every byte of PRG and CHR ROM has been used for, as FCEUX's Code/Data
+/// Logger keeps it. Disassemblers take it to tell code from data, and bytes
+/// still unmarked after a playthrough are likely unused.
+///
+/// The `.cdl` file is one flag byte per PRG ROM byte followed by one per CHR ROM
+/// byte, which is also how the log is kept here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDataLog {
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+}
+
+impl CodeDataLog {
+    /// Executed, as an opcode or an operand.
+    pub const CODE: u8 = 0x01;
+    /// Read by an instruction.
+    pub const DATA: u8 = 0x02;
+    /// Which 8 KiB window of `$8000-$FFFF` the byte was last used through.
+    pub const WINDOW: u8 = 0x0C;
+    /// The target of a `JMP ($nnnn)`.
+    pub const INDIRECT_CODE: u8 = 0x10;
+    /// Read through a pointer, by `($nn,X)` or `($nn),Y`.
+    pub const INDIRECT_DATA: u8 = 0x20;
+    /// Fetched by the APU's DMC channel as a sample. There's no APU yet, so only
+    /// logs loaded from elsewhere have it.
+    pub const DMC_SAMPLE: u8 = 0x40;
+    /// The first byte of an instruction. FCEUX leaves this bit unused and doesn't
+    /// tell opcodes from operands, so it's dropped from saved files.
+    pub const OPCODE: u8 = 0x80;
+
+    /// Fetched by the PPU to draw.
+    pub const DRAWN: u8 = 0x01;
+    /// Read by the CPU through `$2007`.
+    pub const READ: u8 = 0x02;
+
+    pub fn new(prg_len: usize, chr_len: usize) -> Self {
+        Self { prg: vec![0; prg_len], chr: vec![0; chr_len] }
+    }
+
+    /// Reads a `.cdl` file, which has to be for a ROM with these sizes of PRG and CHR.
+    pub fn from_fceux(bytes: &[u8], prg_len: usize, chr_len: usize) -> Result<Self, CdlError> {
+        if bytes.len() != prg_len + chr_len {
+            return Err(CdlError { expected: prg_len + chr_len, found: bytes.len() });
+        }
+
+        let (prg, chr) = bytes.split_at(prg_len);
+        Ok(Self { prg: prg.to_vec(), chr: chr.to_vec() })
+    }
+
+    pub fn to_fceux(&self) -> Vec<u8> {
+        let prg = self.prg.iter().map(|flags| flags & !Self::OPCODE);
+        prg.chain(self.chr.iter().copied()).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.prg.fill(0);
+        self.chr.fill(0);
+    }
+
+    /// Marks the PRG ROM byte at `offset`, used through CPU address `addr`.
+    pub fn mark_prg(&mut self, offset: usize, addr: u16, flags: u8) {
+        let window = ((addr >> 11) as u8) & Self::WINDOW;
+
+        if let Some(byte) = self.prg.get_mut(offset) {
+            *byte = (*byte & !Self::WINDOW) | window | flags;
+        }
+    }
+
+    pub fn mark_chr(&mut self, offset: usize, flags: u8) {
+        if let Some(byte) = self.chr.get_mut(offset) {
+            *byte |= flags;
+        }
+    }
+
+    pub fn summary(&self) -> CdlSummary {
+        let count = |bytes: &[u8], mask: u8| bytes.iter().filter(|&&flags| flags & mask != 0).count();
+
+        CdlSummary {
+            prg: self.prg.len(),
+            code: count(&self.prg, Self::CODE),
+            data: count(&self.prg, Self::DATA | Self::DMC_SAMPLE),
+            prg_unused: self.prg.iter().filter(|&&flags| flags & !Self::WINDOW == 0).count(),
+            chr: self.chr.len(),
+            drawn: count(&self.chr, Self::DRAWN),
+            read: count(&self.chr, Self::READ),
+            chr_unused: self.chr.iter().filter(|&&flags| flags == 0).count(),
+        }
+    }
+}
+
+/// How much of the ROM a log has seen used. A byte can be both code and data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CdlSummary {
+    pub prg: usize,
+    pub code: usize,
+    pub data: usize,
+    pub prg_unused: usize,
+    pub chr: usize,
+    pub drawn: usize,
+    pub read: usize,
+    pub chr_unused: usize,
+}
+
+impl fmt::Display for CdlSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PRG: {} code, {} data, {} of {} unused", self.code, self.data, self.prg_unused, self.prg)?;
+
+        if self.chr > 0 {
+            write!(f, "; CHR: {} drawn, {} read, {} of {} unused", self.drawn, self.read, self.chr_unused, self.chr)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A `.cdl` file that doesn't fit the loaded ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CdlError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for CdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CDL file is {} bytes, but this ROM needs {}", self.found, self.expected)
+    }
+}
+
+impl std::error::Error for CdlError {}
+
+impl From<CdlError> for JsValue {
+    fn from(err: CdlError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::Ram;
+use crate::state::{StateError, StateReader, StateWriter};
+
+/// Game Genie letters, in the order of the nibbles they stand for.
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    /// Changes what the CPU reads from ROM, like a Game Genie does.
+    RomPatch,
+    /// Writes a byte of RAM every frame, like a Pro Action Replay does.
+    Freeze,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub id: u32,
+    /// The code as it was entered, tidied up.
+    pub code: String,
+    pub kind: CheatKind,
+    pub addr: u16,
+    pub value: u8,
+    /// Only patch the read when ROM holds this, so the patch stays off other banks.
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Decodes a 6 or 8 letter Game Genie code, an 8 digit Pro Action Replay code
+    /// like `00007509`, or a raw RAM code like `0075:09`. Dashes and spaces are ignored.
+    ///
+    /// Raw codes need their colon, otherwise a hex code such as `AEAEAE` would read
+    /// just as well as Game Genie letters.
+    pub fn decode(code: &str) -> Result<Self, CheatError> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_ascii_uppercase();
+        let error = |reason| CheatError { code: code.clone(), reason };
+
+        let letters: Option<Vec<u8>> =
+            code.bytes().map(|letter| GAME_GENIE_LETTERS.iter().position(|&l| l == letter).map(|n| n as u8)).collect();
+
+        let (kind, addr, value, compare) = match letters {
+            Some(n) if n.len() == 6 || n.len() == 8 => {
+                let addr = 0x8000
+                    | ((n[3] as u16 & 7) << 12)
+                    | ((n[5] as u16 & 7) << 8)
+                    | ((n[4] as u16 & 8) << 8)
+                    | ((n[2] as u16 & 7) << 4)
+                    | ((n[1] as u16 & 8) << 4)
+                    | (n[4] as u16 & 7)
+                    | (n[3] as u16 & 8);
+
+                // the last letter's high bit finishes the value, or the compare byte in a long code
+                let last = if n.len() == 6 { n[5] } else { n[7] };
+                let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (last & 8);
+                let compare = (n.len() == 8).then(|| ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8));
+
+                (CheatKind::RomPatch, addr, value, compare)
+            }
+            Some(_) => return Err(error("Game Genie codes are 6 or 8 letters")),
+            None => {
+                let (addr, value) = match code.split_once(':') {
+                    Some(parts) => parts,
+                    // Pro Action Replay codes are a code type, the address, then the value
+                    None if code.len() == 8 && code.bytes().all(|c| c.is_ascii_hexdigit()) => match code.split_at(2) {
+                        ("00", rest) => rest.split_at(4),
+                        _ => return Err(error("only Pro Action Replay codes starting with 00 freeze RAM")),
+                    },
+                    None => return Err(error("not a Game Genie, Pro Action Replay or RAM code like 0075:09")),
+                };
+
+                let addr = u16::from_str_radix(addr, 16).map_err(|_| error("the address isn't hex"))?;
+                let value = u8::from_str_radix(value, 16).map_err(|_| error("the value isn't a hex byte"))?;
+
+                if addr >= 0x2000 {
+                    return Err(error("RAM codes can only freeze $0000-$1FFF"));
+                }
+
+                (CheatKind::Freeze, addr, value, None)
+            }
+        };
+
+        Ok(Self { id: 0, code, kind, addr, value, compare, enabled: true })
+    }
+}
+
+/// `1 on SXIOPO: $91D9 = $AD`, or `if $C5` before the value for a compare byte.
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.enabled { "on" } else { "off" };
+        write!(f, "{} {state} {}: ", self.id, self.code)?;
+
+        match (self.kind, self.compare) {
+            (CheatKind::Freeze, _) => write!(f, "freeze ${:04X} = ${:02X}", self.addr, self.value),
+            (CheatKind::RomPatch, Some(compare)) => write!(f, "${:04X} = ${:02X} if ${compare:02X}", self.addr, self.value),
+            (CheatKind::RomPatch, None) => write!(f, "${:04X} = ${:02X}", self.addr, self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatError {
+    pub code: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cheat `{}`: {}", self.code, self.reason)
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+impl From<CheatError> for JsValue {
+    fn from(err: CheatError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// The cheats in play. ROM patches are applied as the CPU reads, freezes once a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+    next_id: u32,
+    /// The enabled ROM patches, kept apart so reads without any stay cheap.
+    patches: Vec<(u16, u8, Option<u8>)>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self { cheats: Vec::new(), next_id: 1, patches: Vec::new() }
+    }
+
+    pub fn add(&mut self, code: &str) -> Result<u32, CheatError> {
+        let mut cheat = Cheat::decode(code)?;
+        cheat.id = self.next_id;
+        self.next_id += 1;
+        self.cheats.push(cheat);
+        self.sync();
+        Ok(self.next_id - 1)
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let before = self.cheats.len();
+        self.cheats.retain(|cheat| cheat.id != id);
+        self.sync();
+        self.cheats.len() != before
+    }
+
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.iter_mut().find(|cheat| cheat.id == id) else {
+            return false;
+        };
+
+        cheat.enabled = enabled;
+        self.sync();
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+        self.sync();
+    }
+
+    pub fn list(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// What the CPU reads at `addr` in `$8000-$FFFF` once the ROM there gave `byte`.
+    pub fn patch(&self, addr: u16, byte: u8) -> u8 {
+        for &(patch_addr, value, compare) in &self.patches {
+            if patch_addr == addr && compare.is_none_or(|compare| compare == byte) {
+                return value;
+            }
+        }
+
+        byte
+    }
+
+    /// Writes every enabled freeze into `ram`.
+    pub fn freeze(&self, ram: &mut Ram) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled && cheat.kind == CheatKind::Freeze) {
+            ram.write(cheat.addr & 0x7FF, cheat.value);
+        }
+    }
+
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.u32(self.next_id);
+        w.u16(self.cheats.len() as u16);
+
+        for cheat in &self.cheats {
+            w.u32(cheat.id);
+            w.bool(cheat.kind == CheatKind::Freeze);
+            w.u16(cheat.addr);
+            w.u8(cheat.value);
+            w.bool(cheat.compare.is_some());
+            w.u8(cheat.compare.unwrap_or(0));
+            w.bool(cheat.enabled);
+            w.u16(cheat.code.len() as u16);
+            w.bytes(cheat.code.as_bytes());
+        }
+    }
+
+    /// Reads what `save_state` wrote. The chunk's length varies with the cheats,
+    /// so it's read whole before anything is applied.
+    pub(crate) fn read_state(r: &mut StateReader) -> Result<Self, StateError> {
+        let next_id = r.u32()?;
+        let count = r.u16()?;
+        let mut cheats = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let id = r.u32()?;
+            let kind = if r.bool()? { CheatKind::Freeze } else { CheatKind::RomPatch };
+            let addr = r.u16()?;
+            let value = r.u8()?;
+            let has_compare = r.bool()?;
+            let compare = r.u8()?;
+            let enabled = r.bool()?;
+            let len = r.u16()? as usize;
+            let code = String::from_utf8_lossy(r.bytes(len)?).into_owned();
+
+            cheats.push(Cheat { id, code, kind, addr, value, compare: has_compare.then_some(compare), enabled });
+        }
+
+        let mut loaded = Self { cheats, next_id, patches: Vec::new() };
+        loaded.sync();
+        Ok(loaded)
+    }
+
+    fn sync(&mut self) {
+        self.patches = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled && cheat.kind == CheatKind::RomPatch)
+            .map(|cheat| (cheat.addr, cheat.value, cheat.compare))
+            .collect();
+    }
+}
+
+impl Default for Cheats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,48 @@
+/// A standard controller behind `$4016`/`$4017`.
+///
+/// `buttons` holds A, B, Select, Start, Up, Down, Left, Right from bit 0 up, the
+/// same order the shift register hands them to the CPU.
+#[derive(Default, Clone)]
+pub struct Controller {
+    pub buttons: u8,
+    shift: u8,
+    strobe: bool,
+}
+
+impl Controller {
+    pub fn write(&mut self, byte: u8) {
+        self.strobe = byte & 1 != 0;
+
+        if self.strobe {
+            self.shift = self.buttons;
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons & 1;
+        }
+
+        let bit = self.shift & 1;
+        // official controllers report 1 once all eight buttons have been read
+        self.shift = (self.shift >> 1) | 0x80;
+        bit
+    }
+
+    /// The bit the next read would return, without shifting.
+    pub fn peek(&self) -> u8 {
+        if self.strobe { self.buttons & 1 } else { self.shift & 1 }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [self.buttons, self.shift, self.strobe as u8]
+    }
+
+    pub fn from_bytes(bytes: [u8; 3]) -> Self {
+        Self {
+            buttons: bytes[0],
+            shift: bytes[1],
+            strobe: bytes[2] != 0,
+        }
+    }
+}
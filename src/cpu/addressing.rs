@@ -1,46 +1,106 @@
-use crate::cpu::Cpu;
+use std::fmt;
+
+use crate::cpu::{Cpu, InstructionBytes};
+
+/// An instruction's operand as it was written, kept around only so the tracelog
+/// can show it. Turning it into text waits until someone actually reads the trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Implied,
+    Immediate(u8),
+    ZeroPage(u8),
+    ZeroPageX(u8),
+    ZeroPageY(u8),
+    Absolute(u16),
+    AbsoluteX(u16),
+    AbsoluteY(u16),
+    Indirect(u16),
+    IndirectX(u8),
+    IndirectY(u8),
+    Relative(u8),
+}
+
+impl Operand {
+    /// The operand bytes following the opcode.
+    pub fn bytes(&self) -> InstructionBytes {
+        match *self {
+            Operand::Implied => InstructionBytes::new([0; 3], 0),
+            Operand::Immediate(byte)
+            | Operand::ZeroPage(byte)
+            | Operand::ZeroPageX(byte)
+            | Operand::ZeroPageY(byte)
+            | Operand::IndirectX(byte)
+            | Operand::IndirectY(byte)
+            | Operand::Relative(byte) => InstructionBytes::new([byte, 0, 0], 1),
+            Operand::Absolute(word) | Operand::AbsoluteX(word) | Operand::AbsoluteY(word) | Operand::Indirect(word) => {
+                let [low, high] = word.to_le_bytes();
+                InstructionBytes::new([low, high, 0], 2)
+            }
+        }
+    }
+}
+
+/// Indexed modes that may cross a page are marked with a trailing `*`.
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Implied => Ok(()),
+            Operand::Immediate(byte) => write!(f, "#${byte:02X}"),
+            Operand::ZeroPage(byte) => write!(f, "${byte:02X}"),
+            Operand::ZeroPageX(byte) => write!(f, "${byte:02X},X"),
+            Operand::ZeroPageY(byte) => write!(f, "${byte:02X},Y"),
+            Operand::Absolute(word) => write!(f, "${word:04X}"),
+            Operand::AbsoluteX(word) => write!(f, "${word:04X},X *"),
+            Operand::AbsoluteY(word) => write!(f, "${word:04X},Y *"),
+            Operand::Indirect(word) => write!(f, "(${word:04X})"),
+            Operand::IndirectX(byte) => write!(f, "(${byte:02X},X)"),
+            Operand::IndirectY(byte) => write!(f, "(${byte:02X}),Y *"),
+            Operand::Relative(byte) => write!(f, "${byte:02X}"),
+        }
+    }
+}
 
 impl Cpu {
-    pub fn immediate(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn immediate(&mut self) -> (Operand, u8) {
         let operand = self.read_next();
-        (vec![operand], operand, format!("#${operand:02X}"))
+        (Operand::Immediate(operand), operand)
     }
 
-    pub fn zeropage(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn zeropage(&mut self) -> (Operand, u8) {
         let addr = self.read_next();
         let operand = self.read(addr as u16);
 
-        (vec![addr], operand, format!("${operand:02X}"))
+        (Operand::ZeroPage(addr), operand)
     }
 
-    pub fn zeropage_x(&mut self) -> (Vec<u8>, u8, String) {
-        let operand = self.read_next();
-        let addr = operand.wrapping_add(self.reg_x) as u16;
+    pub fn zeropage_x(&mut self) -> (Operand, u8) {
+        let zp = self.read_next();
+        let addr = zp.wrapping_add(self.reg_x) as u16;
         self.cycle();
         let operand = self.read(addr);
 
-        (vec![operand], operand, format!("${operand:02X}"))
+        (Operand::ZeroPageX(zp), operand)
     }
 
-    pub fn zeropage_y(&mut self) -> (Vec<u8>, u8, String) {
-        let operand = self.read_next();
-        let addr = operand.wrapping_add(self.reg_y) as u16;
+    pub fn zeropage_y(&mut self) -> (Operand, u8) {
+        let zp = self.read_next();
+        let addr = zp.wrapping_add(self.reg_y) as u16;
         self.cycle();
         let operand = self.read(addr);
 
-        (vec![operand], operand, format!("${operand:02X}"))
+        (Operand::ZeroPageY(zp), operand)
     }
 
-    pub fn absolute(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn absolute(&mut self) -> (Operand, u8) {
         let low = self.read_next();
         let high = self.read_next();
         let addr = (high as u16) << 8 | low as u16;
         let operand = self.read(addr);
 
-        (vec![low, high], operand, format!("${addr:04X}"))
+        (Operand::Absolute(addr), operand)
     }
 
-    pub fn absolute_x(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn absolute_x(&mut self) -> (Operand, u8) {
         let low = self.read_next();
         let high = self.read_next();
         let base_addr = (high as u16) << 8 | low as u16;
@@ -51,10 +111,10 @@ impl Cpu {
         }
 
         let operand = self.read(addr);
-        (vec![low, high], operand, format!("${base_addr:04X},X *"))
+        (Operand::AbsoluteX(base_addr), operand)
     }
 
-    pub fn absolute_y(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn absolute_y(&mut self) -> (Operand, u8) {
         let low = self.read_next();
         let high = self.read_next();
         let base_addr = (high as u16) << 8 | low as u16;
@@ -65,20 +125,20 @@ impl Cpu {
         }
 
         let operand = self.read(addr);
-        (vec![low, high], operand, format!("${base_addr:04X},Y *"))
+        (Operand::AbsoluteY(base_addr), operand)
     }
 
-    pub fn indirect_x(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn indirect_x(&mut self) -> (Operand, u8) {
         let zp = self.read_next();
         let ptr = zp.wrapping_add(self.reg_x);
         self.cycle();
         let low = self.read(ptr as u16) as u16;
         let high = self.read(ptr.wrapping_add(1) as u16) as u16;
-        let operand = self.read((high as u16) << 8 | low as u16);
-        (vec![zp], operand, format!("(${zp:02X},X)"))
+        let operand = self.read_indirect((high << 8) | low);
+        (Operand::IndirectX(zp), operand)
     }
 
-    pub fn indirect_y(&mut self) -> (Vec<u8>, u8, String) {
+    pub fn indirect_y(&mut self) -> (Operand, u8) {
         let zp = self.read_next();
         let low = self.read(zp as u16) as u16;
         let high = self.read(zp.wrapping_add(1) as u16) as u16;
@@ -89,83 +149,80 @@ impl Cpu {
             self.cycle();
         }
 
-        let operand = self.read(addr);
-        (vec![zp], operand, format!("(${zp:02X}),Y *"))
+        let operand = self.read_indirect(addr);
+        (Operand::IndirectY(zp), operand)
     }
 
-    pub fn zeropage_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn zeropage_addr(&mut self) -> (Operand, u16) {
         let addr = self.read_next();
-        (vec![addr], addr as u16, format!("${addr:02X}"))
+        (Operand::ZeroPage(addr), addr as u16)
     }
 
-    pub fn zeropage_x_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn zeropage_x_addr(&mut self) -> (Operand, u16) {
         let operand = self.read_next();
         let addr = operand.wrapping_add(self.reg_x) as u16;
         self.cycle();
-        (vec![operand], addr, format!("${operand:02X},X"))
+        (Operand::ZeroPageX(operand), addr)
     }
 
-    pub fn zeropage_y_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn zeropage_y_addr(&mut self) -> (Operand, u16) {
         let operand = self.read_next();
         let addr = operand.wrapping_add(self.reg_y) as u16;
         self.cycle();
-        (vec![operand], addr, format!("${operand:02X},Y"))
+        (Operand::ZeroPageY(operand), addr)
     }
 
-    pub fn absolute_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn absolute_addr(&mut self) -> (Operand, u16) {
         let low = self.read_next();
         let high = self.read_next();
         let addr = (high as u16) << 8 | low as u16;
-        (vec![low, high], addr, format!("${addr:04X}"))
+        (Operand::Absolute(addr), addr)
     }
 
-    pub fn absolute_x_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn absolute_x_addr(&mut self) -> (Operand, u16) {
         let low = self.read_next();
         let high = self.read_next();
         let base_addr = (high as u16) << 8 | low as u16;
         let addr = base_addr.wrapping_add(self.reg_x as u16);
 
-        if base_addr & 0xFF00 != addr & 0xFF00 {
-            self.cycle();
-        }
+        // writes can't skip fixing up the high byte, so they always pay the extra cycle
+        self.cycle();
 
-        (vec![low, high], addr, format!("${base_addr:04X},X *"))
+        (Operand::AbsoluteX(base_addr), addr)
     }
 
-    pub fn absolute_y_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn absolute_y_addr(&mut self) -> (Operand, u16) {
         let low = self.read_next();
         let high = self.read_next();
         let base_addr = (high as u16) << 8 | low as u16;
         let addr = base_addr.wrapping_add(self.reg_y as u16);
 
-        if base_addr & 0xFF00 != addr & 0xFF00 {
-            self.cycle();
-        }
+        // writes can't skip fixing up the high byte, so they always pay the extra cycle
+        self.cycle();
 
-        (vec![low, high], addr, format!("${base_addr:04X},Y *"))
+        (Operand::AbsoluteY(base_addr), addr)
     }
 
-    pub fn indirect_x_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn indirect_x_addr(&mut self) -> (Operand, u16) {
         let zp = self.read_next();
         let ptr = zp.wrapping_add(self.reg_x);
         self.cycle();
         let low = self.read(ptr as u16) as u16;
         let high = self.read(ptr.wrapping_add(1) as u16) as u16;
         let addr = (high << 8) | low;
-        (vec![zp], addr, format!("(${zp:02X},X)"))
+        (Operand::IndirectX(zp), addr)
     }
 
-    pub fn indirect_y_addr(&mut self) -> (Vec<u8>, u16, String) {
+    pub fn indirect_y_addr(&mut self) -> (Operand, u16) {
         let zp = self.read_next();
         let low = self.read(zp as u16) as u16;
         let high = self.read(zp.wrapping_add(1) as u16) as u16;
         let base_addr = (high << 8) | low;
         let addr = base_addr.wrapping_add(self.reg_y as u16);
 
-        if base_addr & 0xFF00 != addr & 0xFF00 {
-            self.cycle();
-        }
+        // writes can't skip fixing up the high byte, so they always pay the extra cycle
+        self.cycle();
 
-        (vec![zp], addr, format!("(${zp:02X}),Y *"))
+        (Operand::IndirectY(zp), addr)
     }
 }
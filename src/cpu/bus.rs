@@ -1,65 +1,132 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{card::Card, cpu::Ram, js::consoleLog, ppu::{Ppu, PpuCtrl, PpuMask}};
+use crate::{card::Card, cdl::CodeDataLog, cheats::Cheats, controller::Controller, cpu::{IrqLine, Ram}, debugger::Access, host::HostRef, ppu::Ppu};
 
 pub struct Bus {
     pub address: u16,
+    /// What's on the data bus. Reads nothing answers leave it alone, so they see the
+    /// last byte moved, usually the high byte of the address: open bus.
     pub data: u8,
     pub ram: Ram,
     pub card: Rc<RefCell<Card>>,
-    ppu: Rc<RefCell<Ppu>>
+    pub controllers: [Controller; 2],
+    /// IRQ sources living on the CPU side; the cartridge drives its own through the mapper.
+    pub irq: IrqLine,
+    /// Every access since the debugger last looked, kept only while a watchpoint needs them.
+    pub accesses: Option<Vec<Access>>,
+    /// The Code/Data Logger, while it's running.
+    pub cdl: Option<Rc<RefCell<CodeDataLog>>>,
+    /// Game Genie patches to ROM reads and RAM freezes.
+    pub cheats: Cheats,
+    pub(crate) ppu: Rc<RefCell<Ppu>>
 }
 
 impl Bus {
-    pub fn new(card: Rc<RefCell<Card>>, ppu: Rc<RefCell<Ppu>>) -> Self {
+    pub fn new(card: Rc<RefCell<Card>>, ppu: Rc<RefCell<Ppu>>, host: HostRef) -> Self {
         Self {
             address: 0,
             data: 0,
-            ram: Ram::default(),
+            ram: Ram::new(host),
             card,
+            controllers: Default::default(),
+            irq: IrqLine::default(),
+            accesses: None,
+            cdl: None,
+            cheats: Cheats::new(),
             ppu
         }
     }
 
+    /// Advances everything clocked off the CPU by one CPU cycle, three PPU dots on NTSC.
+    pub fn tick(&mut self) {
+        let mut ppu = self.ppu.borrow_mut();
+
+        for _ in 0..3 {
+            ppu.clock();
+        }
+    }
+
+    /// The PPU's /NMI output, high while it wants an interrupt.
+    pub fn nmi_line(&self) -> bool {
+        self.ppu.borrow().nmi_output()
+    }
+
+    /// The shared /IRQ line, asserted while any source holds it.
+    pub fn irq_line(&self) -> bool {
+        self.irq.is_asserted() || self.card.borrow().mapper.irq()
+    }
+
     pub fn read(&mut self) {
         match self.address {
+            // 2 KiB of RAM, mirrored every 2 KiB
             0x0000..=0x1FFF => {
-                if let Some(byte) = self.ram.read(self.address) {
-                    self.data = byte;
-                }
+                self.data = self.ram.read(self.address);
             }
 
-            0x2000 => {
-                let ppu = self.ppu.borrow();
-                self.data = ppu.ctrl_flags.to_byte();
+            // the eight PPU registers, mirrored every 8 bytes, read through the PPU's own latch
+            0x2000..=0x3FFF => {
+                self.data = self.ppu.borrow_mut().read_register(self.address);
             }
 
-            0x2001 => {
-                let ppu = self.ppu.borrow();
-                self.data = ppu.mask_flags.to_byte();
+            // controllers only drive the low bits, the top three are still open bus
+            0x4016 | 0x4017 => {
+                let bits = self.controllers[self.address as usize - 0x4016].read();
+                self.data = (self.data & 0xE0) | (bits & 0x1F);
             }
 
-            0x2002 => {
-                let mut ppu = self.ppu.borrow_mut();
-                ppu.status_flags.v_blank = true;
-                self.data = ppu.status_flags.read_and_clear_vblank();
+            // the APU isn't emulated, and `$4018-$401F` are test registers retail consoles have
+            // turned off, so all of these read open bus
+            0x4000..=0x401F => {}
+
+            0x4020..=0x5FFF => {
+                if let Some(byte) = self.card.borrow_mut().mapper.expansion_read(self.address) {
+                    self.data = byte;
+                }
             }
 
-            0x2007 => {
-                let mut ppu = self.ppu.borrow_mut();
-                self.data = ppu.ppu_data_read();
+            0x6000..=0x7FFF => {
+                if let Some(byte) = self.card.borrow().mapper.prg_ram_read(self.address - 0x6000) {
+                    self.data = byte;
+                }
             }
 
             0x8000..=0xFFFF => {
                 let mut card = self.card.borrow_mut();
 
                 if let Some(byte) = card.cpu_read(self.address - 0x8000) {
-                    self.data = byte;
+                    self.data = self.cheats.patch(self.address, byte);
                 }
             }
+        };
 
-            _ => {}
+        self.log_access(false);
+    }
+
+    /// Reads `addr` without any of the side effects a real read would have: status
+    /// flags stay set, the VRAM address and controllers don't move, and the debugger
+    /// doesn't see it.
+    pub fn peek(&self, addr: u16) -> u8 {
+        let byte = match addr {
+            0x0000..=0x1FFF => Some(self.ram.read(addr)),
+            0x2000..=0x3FFF => Some(self.ppu.borrow().peek_register(addr)),
+            0x4016 | 0x4017 => Some((self.data & 0xE0) | (self.controllers[addr as usize - 0x4016].peek() & 0x1F)),
+            0x6000..=0x7FFF => self.card.borrow().mapper.prg_ram_read(addr - 0x6000),
+            0x8000..=0xFFFF => self.card.borrow().mapper.cpu_peek(addr - 0x8000).map(|byte| self.cheats.patch(addr, byte)),
+            // expansion hardware may have side effects on read
+            _ => None,
         };
+
+        byte.unwrap_or(self.data)
+    }
+
+    /// Writes RAM or patches the cartridge at `addr`. Registers are left alone, since writing them has side effects.
+    pub fn poke(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram.write(addr, byte),
+            0x6000..=0x7FFF => self.card.borrow_mut().mapper.prg_ram_write(addr - 0x6000, byte),
+            0x8000..=0xFFFF => self.card.borrow_mut().mapper.cpu_poke(addr - 0x8000, byte),
+            _ => {}
+        }
     }
 
     pub fn write(&mut self) {
@@ -68,32 +135,51 @@ impl Bus {
                 self.ram.write(self.address, self.data);
             }
 
-            0x2000 => {
-                let mut ppu = self.ppu.borrow_mut();
-                ppu.ctrl_flags = PpuCtrl::from_byte(self.data);
+            0x2000..=0x3FFF => {
+                self.ppu.borrow_mut().write_register(self.address, self.data);
             }
 
-            0x2001 => {
-                let mut ppu = self.ppu.borrow_mut();
-                ppu.mask_flags = PpuMask::from_byte(self.data);
+            0x4016 => {
+                // both ports share the strobe line
+                for controller in self.controllers.iter_mut() {
+                    controller.write(self.data);
+                }
             }
 
-            0x2006 => {
-                let mut ppu = self.ppu.borrow_mut();
-                ppu.ppu_addr(self.data);
+            // the APU and OAM DMA aren't emulated, nor are the test registers
+            0x4000..=0x401F => {}
+
+            0x4020..=0x5FFF => {
+                self.card.borrow_mut().mapper.expansion_write(self.address, self.data);
             }
 
-            0x2007 => {
-                let mut ppu = self.ppu.borrow_mut();
-                ppu.ppu_data_write(self.data);
+            0x6000..=0x7FFF => {
+                self.card.borrow_mut().mapper.prg_ram_write(self.address - 0x6000, self.data);
             }
 
             0x8000..=0xFFFF => {
                 let mut card = self.card.borrow_mut();
                 card.cpu_write(self.address, self.data);
             }
+        }
 
-            _ => {}
+        self.log_access(true);
+    }
+
+    /// Marks the PRG ROM byte the CPU just used at `addr` with `flags`, if the Code/Data Logger is running.
+    pub fn log_prg(&self, addr: u16, flags: u8) {
+        let Some(cdl) = self.cdl.as_ref() else {
+            return;
+        };
+
+        if let Some(offset) = self.card.borrow().prg_offset(addr) {
+            cdl.borrow_mut().mark_prg(offset, addr, flags);
+        }
+    }
+
+    fn log_access(&mut self, write: bool) {
+        if let Some(accesses) = self.accesses.as_mut() {
+            accesses.push(Access { addr: self.address, value: self.data, write });
         }
     }
 }
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::ops::Deref;
+
+use crate::cpu::{AddrMode, Opcode, OPCODES};
+use crate::symbols::Symbols;
+
+/// One decoded instruction, described only by its bytes and where they sit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: u16,
+    pub opcode: u8,
+    /// Operand bytes as a little-endian word, `0` for implied instructions.
+    pub operand: u16,
+    /// Bytes actually decoded, which is short of `info().size()` when the input ran out.
+    pub len: u8,
+}
+
+impl Instruction {
+    pub fn info(&self) -> &'static Opcode {
+        &OPCODES[self.opcode as usize]
+    }
+
+    /// Whether the whole instruction fitted in the bytes it was decoded from.
+    pub fn is_complete(&self) -> bool {
+        self.len as usize == self.info().size()
+    }
+
+    pub fn bytes(&self) -> InstructionBytes {
+        InstructionBytes { bytes: [self.opcode, self.operand as u8, (self.operand >> 8) as u8], len: self.len }
+    }
+
+    /// Where a branch or jump goes, if it can be known without running it.
+    pub fn target(&self) -> Option<u16> {
+        match self.info().mode {
+            AddrMode::Relative => Some(self.addr.wrapping_add(2).wrapping_add(self.operand as u8 as i8 as u16)),
+            AddrMode::Absolute if matches!(self.info().mnemonic, "JMP" | "JSR") => Some(self.operand),
+            _ => None,
+        }
+    }
+
+    /// Displays like the instruction itself, but with addresses that have labels named by them.
+    pub fn labelled<'a>(&'a self, symbols: &'a Symbols) -> impl fmt::Display + 'a {
+        Labelled { instruction: self, symbols }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, symbols: &Symbols) -> fmt::Result {
+        if !self.is_complete() {
+            let bytes: Vec<String> = self.bytes().iter().map(|b| format!("${b:02X}")).collect();
+            return write!(f, ".byte {}", bytes.join(", "));
+        }
+
+        let mnemonic = self.info().mnemonic;
+        let byte = self.operand as u8;
+        let zp = symbols.name_zp(byte);
+        let word = symbols.name(self.operand);
+
+        match self.info().mode {
+            AddrMode::Implied => write!(f, "{mnemonic}"),
+            AddrMode::Accumulator => write!(f, "{mnemonic} A"),
+            AddrMode::Immediate => write!(f, "{mnemonic} #${byte:02X}"),
+            AddrMode::ZeroPage => write!(f, "{mnemonic} {zp}"),
+            AddrMode::ZeroPageX => write!(f, "{mnemonic} {zp},X"),
+            AddrMode::ZeroPageY => write!(f, "{mnemonic} {zp},Y"),
+            AddrMode::Absolute => write!(f, "{mnemonic} {word}"),
+            AddrMode::AbsoluteX => write!(f, "{mnemonic} {word},X"),
+            AddrMode::AbsoluteY => write!(f, "{mnemonic} {word},Y"),
+            AddrMode::Indirect => write!(f, "{mnemonic} ({word})"),
+            AddrMode::IndirectX => write!(f, "{mnemonic} ({zp},X)"),
+            AddrMode::IndirectY => write!(f, "{mnemonic} ({zp}),Y"),
+            AddrMode::Relative => write!(f, "{mnemonic} {}", symbols.name(self.target().unwrap())),
+        }
+    }
+}
+
+/// Up to three bytes of machine code, kept inline so tracing never allocates for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionBytes {
+    bytes: [u8; 3],
+    len: u8,
+}
+
+impl InstructionBytes {
+    pub(crate) fn new(bytes: [u8; 3], len: usize) -> Self {
+        Self { bytes, len: len as u8 }
+    }
+}
+
+impl Deref for InstructionBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+/// Hex bytes separated by spaces, `4C F5 C5`. Honours width and alignment like a `str`.
+impl fmt::Display for InstructionBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const HEX: &[u8; 16] = b"0123456789ABCDEF";
+        let mut text = [b' '; 8];
+
+        for (i, byte) in self.iter().enumerate() {
+            text[i * 3] = HEX[(byte >> 4) as usize];
+            text[i * 3 + 1] = HEX[(byte & 0xF) as usize];
+        }
+
+        let len = (self.len as usize * 3).saturating_sub(1);
+        f.pad(std::str::from_utf8(&text[..len]).unwrap())
+    }
+}
+
+/// Plain assembler syntax, with branch targets resolved to absolute addresses.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &Symbols::new())
+    }
+}
+
+struct Labelled<'a> {
+    instruction: &'a Instruction,
+    symbols: &'a Symbols,
+}
+
+impl fmt::Display for Labelled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.instruction.write(f, self.symbols)
+    }
+}
+
+/// Decodes the instruction starting at `bytes[0]`, which lives at `addr`.
+pub fn decode(bytes: &[u8], addr: u16) -> Option<Instruction> {
+    let opcode = *bytes.first()?;
+    let len = OPCODES[opcode as usize].size().min(bytes.len());
+    let operand = bytes[1..len].iter().rev().fold(0u16, |word, &b| (word << 8) | b as u16);
+
+    Some(Instruction { addr, opcode, operand, len: len as u8 })
+}
+
+/// Decodes `bytes` front to back as if they were mapped at `origin`, without
+/// executing anything. A last instruction cut short comes out incomplete.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Instruction> {
+    let mut out = vec![];
+    let mut pos = 0;
+
+    while let Some(instruction) = decode(&bytes[pos..], origin.wrapping_add(pos as u16)) {
+        pos += instruction.len as usize;
+        out.push(instruction);
+    }
+
+    out
+}
@@ -1,8 +1,10 @@
-use crate::{cpu::{Cpu, status_flags::CpuFlags}, js::consoleLog};
+use crate::cdl::CodeDataLog;
+use crate::cpu::{Cpu, Operand, status_flags::CpuFlags};
 
 impl Cpu {
-    pub fn adc(&mut self, addressing: (Vec<u8>, u8, String)) {
-        let old_a = self.reg_a.clone();
+    #[allow(clippy::overly_complex_bool_expr)]
+    pub fn adc(&mut self, addressing: (Operand, u8)) {
+        let old_a = self.reg_a;
 
         if self.flags.decimal && false { // disable the decimal flag... implemented before I realised that the NES doesn't support this...
             let mut low = (self.reg_a & 0x0F) + (addressing.1 & 0x0F) + (self.flags.carry as u8);
@@ -24,22 +26,19 @@ impl Cpu {
 
             self.flags.overflow = ((!(old_a ^ addressing.1) & (old_a ^ self.reg_a)) & 0x80) != 0;
         } else {
-            let res = self.reg_a as u16 + addressing.1 as u16 + self.flags.carry as u16;
-            self.reg_a = res as u8;
-            self.flags.carry = res > 0xFF;
-            self.flags.overflow = ((!(old_a ^ addressing.1) & (old_a ^ self.reg_a)) & 0x80) != 0;
+            self.add_with_carry(addressing.1);
         }
 
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = self.reg_a > 127;
-        self.add_tracelog(&addressing.0, format!("ADC {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("ADC {}", addressing.0));
     }
 
-    pub fn and(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn and(&mut self, addressing: (Operand, u8)) {
         self.reg_a &= addressing.1;
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = self.reg_a > 127;
-        self.add_tracelog(&addressing.0, format!("AND {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("AND {}", addressing.0));
     }
 
     pub fn asl_accumulator(&mut self) {
@@ -48,25 +47,26 @@ impl Cpu {
         self.cycle();
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = self.reg_a > 127;
-        self.add_tracelog(&[], format!("ASL"));
+        self.add_tracelog(Operand::Implied, format_args!("ASL"));
     }
 
-    pub fn asl(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn asl(&mut self, addressing: (Operand, u16)) {
         let target = self.read(addressing.1);
         self.flags.carry = (target & 0x80) != 0;
         self.cycle();
-        self.write(addressing.1, target << 1);
-        self.flags.zero = target == 0;
-        self.flags.negative = target > 127;
-        self.add_tracelog(&addressing.0, format!("ASL {}", addressing.2));
+        let result = target << 1;
+        self.write(addressing.1, result);
+        self.flags.zero = result == 0;
+        self.flags.negative = result > 127;
+        self.add_tracelog(addressing.0, format_args!("ASL {}", addressing.0));
     }
 
-    pub fn bit(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn bit(&mut self, addressing: (Operand, u8)) {
         let op = addressing.1;
         self.flags.zero = self.reg_a & op == 0;
         self.flags.negative = (op & 0x80) != 0;
         self.flags.overflow = (op & 0x40) != 0;
-        self.add_tracelog(&addressing.0, format!("BIT {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("BIT {}", addressing.0));
     }
 
     // this + set/clear flag used for BPL, BMI, BVC, BVS, BCC, BCS, BNE and BEQ
@@ -75,7 +75,9 @@ impl Cpu {
         let target = self.counter.wrapping_add(byte as i8 as i16 as u16);
 
         if flag {
-            let old = self.counter.clone();
+            self.branch_delays_irq();
+
+            let old = self.counter;
             self.counter = target;
             self.cycle();
 
@@ -83,67 +85,69 @@ impl Cpu {
                 self.cycle();
             }
 
-            self.add_tracelog(&[byte], format!("{name} ${target:04X} -> ${target:04X}"));
+            self.add_tracelog(Operand::Relative(byte), format_args!("{name} ${target:04X} -> ${target:04X}"));
         } else {
-            self.add_tracelog(&[byte], format!("{name} ${target:04X} -> ${:04X}", self.counter.wrapping_add(1)));
+            self.add_tracelog(Operand::Relative(byte), format_args!("{name} ${target:04X} -> ${:04X}", self.counter.wrapping_add(1)));
         }
     }
 
     pub fn brk(&mut self) {
-        let next = self.counter.wrapping_add(1);
+        // BRK skips a padding byte, so it returns two bytes past the opcode
+        self.read_next();
+        let next = self.counter;
         self.push_stack((next >> 8) as u8);
         self.push_stack((next & 0xFF) as u8);
 
-        let mut status = self.flags.clone();
-        status.brk = true;
-        self.push_stack(status.to_byte());
-        self.flags.interrupt_disable = true;
+        let kind = self.push_status_and_vector(true);
 
-        let low = self.read(0xFFFE) as u16;
-        let high = self.read(0xFFFF) as u16;
-        self.counter = (high << 8) | low;
-        self.cycle();
-        self.add_tracelog(&[], String::from("BRK"));
+        // the first instruction of the handler always runs before another NMI
+        self.prev_nmi_pending = false;
+
+        if kind == "NMI" {
+            self.add_tracelog(Operand::Implied, format_args!("BRK (hijacked by NMI)"));
+        } else {
+            self.add_tracelog(Operand::Implied, format_args!("BRK"));
+        }
     }
 
-    pub fn cmp(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn cmp(&mut self, addressing: (Operand, u8)) {
         let res = self.reg_a.wrapping_sub(addressing.1);
 
         self.flags.carry = self.reg_a >= addressing.1;
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
 
-        self.add_tracelog(&addressing.0, format!("CMP {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("CMP {}", addressing.0));
     }
 
-    pub fn cpx(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn cpx(&mut self, addressing: (Operand, u8)) {
         let res = self.reg_x.wrapping_sub(addressing.1);
 
         self.flags.carry = self.reg_x >= addressing.1;
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
 
-        self.add_tracelog(&addressing.0, format!("CPX {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("CPX {}", addressing.0));
     }
 
-    pub fn cpy(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn cpy(&mut self, addressing: (Operand, u8)) {
         let res = self.reg_y.wrapping_sub(addressing.1);
 
         self.flags.carry = self.reg_y >= addressing.1;
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
 
-        self.add_tracelog(&addressing.0, format!("CPY {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("CPY {}", addressing.0));
     }
 
-    pub fn dec(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn dec(&mut self, addressing: (Operand, u16)) {
         let prev = self.read(addressing.1);
         let res = prev.wrapping_sub(1);
         self.cycle();
         self.write(addressing.1, res);
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&addressing.0, format!("DEC {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("DEC {}", addressing.0));
     }
 
     pub fn dex(&mut self) {
@@ -152,7 +156,7 @@ impl Cpu {
         self.cycle();
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&[], String::from("DEX"));
+        self.add_tracelog(Operand::Implied, format_args!("DEX"));
     }
 
     pub fn dey(&mut self) {
@@ -161,25 +165,25 @@ impl Cpu {
         self.cycle();
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&[], String::from("DEY"));
+        self.add_tracelog(Operand::Implied, format_args!("DEY"));
     }
 
-    pub fn eor(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn eor(&mut self, addressing: (Operand, u8)) {
         let res = self.reg_a ^ addressing.1;
         self.reg_a = res;
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&addressing.0, format!("{}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("EOR {}", addressing.0));
     }
 
-    pub fn inc(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn inc(&mut self, addressing: (Operand, u16)) {
         let prev = self.read(addressing.1);
         let res = prev.wrapping_add(1);
         self.cycle();
         self.write(addressing.1, res);
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&addressing.0, format!("INC {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("INC {}", addressing.0));
     }
 
     pub fn inx(&mut self) {
@@ -188,7 +192,7 @@ impl Cpu {
         self.cycle();
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&[], String::from("INX"));
+        self.add_tracelog(Operand::Implied, format_args!("INX"));
     }
 
     pub fn iny(&mut self) {
@@ -197,7 +201,7 @@ impl Cpu {
         self.cycle();
         self.flags.zero = res == 0;
         self.flags.negative = res > 127;
-        self.add_tracelog(&[], String::from("INX"));
+        self.add_tracelog(Operand::Implied, format_args!("INY"));
     }
 
     pub fn jmp_absolute(&mut self) {
@@ -205,7 +209,7 @@ impl Cpu {
         let high = self.read_next();
         let addr = (high as u16) << 8 | low as u16;
         self.counter = addr;
-        self.add_tracelog(&[low, high], format!("JMP ${addr:04X}"));
+        self.add_tracelog(Operand::Absolute(addr), format_args!("JMP ${addr:04X}"));
     }
 
     pub fn jmp_indirect(&mut self) {
@@ -222,7 +226,8 @@ impl Cpu {
 
         let addr = (target_high as u16) << 8 | target_low as u16;
         self.counter = addr;
-        self.add_tracelog(&[low, high], format!("JMP (${ptr:04X})"));
+        self.bus.log_prg(addr, CodeDataLog::INDIRECT_CODE);
+        self.add_tracelog(Operand::Indirect(ptr), format_args!("JMP (${ptr:04X})"));
     }
 
     pub fn jsr_absolute(&mut self) {
@@ -241,28 +246,28 @@ impl Cpu {
         self.push_stack(prev_low);
         self.counter = (high as u16) << 8 | low as u16;
         self.cycle();
-        self.add_tracelog(&[low, high], format!("JSR ${addr:04X}"));
+        self.add_tracelog(Operand::Absolute(addr), format_args!("JSR ${addr:04X}"));
     }
 
-    pub fn lda(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn lda(&mut self, addressing: (Operand, u8)) {
         self.reg_a = addressing.1;
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = self.reg_a > 127;
-        self.add_tracelog(&addressing.0, format!("LDA {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("LDA {}", addressing.0));
     }
 
-    pub fn ldx(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn ldx(&mut self, addressing: (Operand, u8)) {
         self.reg_x = addressing.1;
         self.flags.zero = self.reg_x == 0;
         self.flags.negative = self.reg_x > 127;
-        self.add_tracelog(&addressing.0, format!("LDA {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("LDX {}", addressing.0));
     }
 
-    pub fn ldy(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn ldy(&mut self, addressing: (Operand, u8)) {
         self.reg_y = addressing.1;
         self.flags.zero = self.reg_y == 0;
         self.flags.negative = self.reg_y > 127;
-        self.add_tracelog(&addressing.0, format!("LDA {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("LDY {}", addressing.0));
     }
 
     pub fn lsr_accumulator(&mut self) {
@@ -271,10 +276,10 @@ impl Cpu {
         self.cycle();
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = false;
-        self.add_tracelog(&[], format!("LSR"));
+        self.add_tracelog(Operand::Implied, format_args!("LSR"));
     }
 
-    pub fn lsr(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn lsr(&mut self, addressing: (Operand, u16)) {
         let value = self.read(addressing.1);
         self.flags.carry = (value & 0x01) != 0;
         self.cycle();
@@ -282,27 +287,26 @@ impl Cpu {
         self.flags.zero = result == 0;
         self.flags.negative = false;
         self.write(addressing.1, result);
-        self.add_tracelog(&addressing.0, format!("LSR {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("LSR {}", addressing.0));
     }
 
     pub fn nop(&mut self) {
         self.cycle();
-        self.cycle();
-        self.add_tracelog(&[], String::from("NOP"));
+        self.add_tracelog(Operand::Implied, format_args!("NOP"));
     }
 
-    pub fn ora(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn ora(&mut self, addressing: (Operand, u8)) {
         let res = self.reg_a | addressing.1;
         self.reg_a = res;
         self.flags.zero = res == 0;
-        self.flags.negative = false;
-        self.add_tracelog(&addressing.0, format!("ORA {}", addressing.2));
+        self.flags.negative = res > 127;
+        self.add_tracelog(addressing.0, format_args!("ORA {}", addressing.0));
     }
 
     pub fn pha(&mut self) {
         self.cycle();
         self.push_stack(self.reg_a);
-        self.add_tracelog(&[], String::from("PHA"));
+        self.add_tracelog(Operand::Implied, format_args!("PHA"));
     }
 
     pub fn php(&mut self) {
@@ -310,7 +314,7 @@ impl Cpu {
         let mut status = self.flags.clone();
         status.brk = true;
         self.push_stack(status.to_byte());
-        self.add_tracelog(&[], String::from("PHP"));
+        self.add_tracelog(Operand::Implied, format_args!("PHP"));
     }
 
     pub fn pla(&mut self) {
@@ -318,15 +322,14 @@ impl Cpu {
         self.reg_a = self.pull_stack();
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = self.reg_a > 127;
-        self.add_tracelog(&[], String::from("PLA"));
+        self.add_tracelog(Operand::Implied, format_args!("PLA"));
     }
 
     pub fn plp(&mut self) {
         self.cycle();
         let value = self.pull_stack();
         self.flags = CpuFlags::from_byte(value);
-        self.cycle();
-        self.add_tracelog(&[value], String::from("PLP"));
+        self.add_tracelog(Operand::Implied, format_args!("PLP ${value:02X}"));
     }
 
     pub fn rol_accumulator(&mut self) {
@@ -336,18 +339,19 @@ impl Cpu {
         self.reg_a = (self.reg_a << 1) | (old_carry as u8);
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = (self.reg_a & 0x80) != 0;
-        self.add_tracelog(&[], String::from("ROL"));
+        self.add_tracelog(Operand::Implied, format_args!("ROL"));
     }
 
-    pub fn rol(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn rol(&mut self, addressing: (Operand, u16)) {
         let mut value = self.read(addressing.1);
+        self.cycle();
         let old_carry = self.flags.carry;
         self.flags.carry = (value & 0x80) != 0;
         value = (value << 1) | (old_carry as u8);
         self.write(addressing.1, value);
         self.flags.zero = value == 0;
         self.flags.negative = (value & 0x80) != 0;
-        self.add_tracelog(&addressing.0, format!("ROL {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("ROL {}", addressing.0));
     }
 
     pub fn ror_accumulator(&mut self) {
@@ -357,40 +361,43 @@ impl Cpu {
         self.reg_a = (self.reg_a >> 1) | ((old_carry as u8) << 7);
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = (self.reg_a & 0x80) != 0;
-        self.add_tracelog(&[], String::from("ROL"));
+        self.add_tracelog(Operand::Implied, format_args!("ROR"));
     }
 
-    pub fn ror(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn ror(&mut self, addressing: (Operand, u16)) {
         let mut value = self.read(addressing.1);
+        self.cycle();
         let old_carry = self.flags.carry;
         self.flags.carry = (value & 0x01) != 0;
         value = (value >> 1) | ((old_carry as u8) << 7);
         self.write(addressing.1, value);
         self.flags.zero = value == 0;
         self.flags.negative = (value & 0x80) != 0;
-        self.add_tracelog(&addressing.0, format!("ROL {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("ROR {}", addressing.0));
     }
 
     pub fn rti(&mut self) {
+        self.cycle();
         let status = self.pull_stack();
         self.flags = CpuFlags::from_byte(status);
-        let low = self.pull_stack();
-        let high = self.pull_stack();
+        let low = self.pull_stack_next();
+        let high = self.pull_stack_next();
         let addr = (high as u16) << 8 | low as u16;
         self.counter = addr;
-        self.add_tracelog(&[], format!("RTI ${status:02X} -> ${addr:04X}"));
+        self.add_tracelog(Operand::Implied, format_args!("RTI ${status:02X} -> ${addr:04X}"));
     }
 
     pub fn rts(&mut self) {
+        self.cycle();
         let low = self.pull_stack();
-        let high = self.pull_stack();
+        let high = self.pull_stack_next();
         let addr = (high as u16) << 8 | low as u16;
         self.counter = addr.wrapping_add(1);
         self.cycle();
-        self.add_tracelog(&[], format!("RTS -> ${addr:04X}"));
+        self.add_tracelog(Operand::Implied, format_args!("RTS -> ${addr:04X}"));
     }
 
-    pub fn sbc(&mut self, addressing: (Vec<u8>, u8, String)) {
+    pub fn sbc(&mut self, addressing: (Operand, u8)) {
         let operand = addressing.1;
         let old_a = self.reg_a;
         let carry = if self.flags.carry { 0 } else { 1 };
@@ -400,63 +407,73 @@ impl Cpu {
         self.flags.zero = self.reg_a == 0;
         self.flags.negative = (self.reg_a & 0x80) != 0;
         self.flags.overflow = ((old_a ^ operand) & 0x80 != 0) && ((old_a ^ self.reg_a) & 0x80 != 0);
-        self.add_tracelog(&addressing.0, format!("SBC {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("SBC {}", addressing.0));
     }
 
-    pub fn sta(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn sta(&mut self, addressing: (Operand, u16)) {
         self.write(addressing.1, self.reg_a);
-        self.add_tracelog(&addressing.0, format!("STA {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("STA {}", addressing.0));
     }
 
-    pub fn stx(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn stx(&mut self, addressing: (Operand, u16)) {
         self.write(addressing.1, self.reg_x);
-        self.add_tracelog(&addressing.0, format!("STX {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("STX {}", addressing.0));
     }
 
-    pub fn sty(&mut self, addressing: (Vec<u8>, u16, String)) {
+    pub fn sty(&mut self, addressing: (Operand, u16)) {
         self.write(addressing.1, self.reg_y);
-        self.add_tracelog(&addressing.0, format!("STY {}", addressing.2));
+        self.add_tracelog(addressing.0, format_args!("STY {}", addressing.0));
     }
 
     pub fn tax(&mut self) {
         self.reg_x = self.reg_a;
         self.cycle();
-        self.add_tracelog(&[], String::from("TAX"));
+        self.flags.zero = self.reg_x == 0;
+        self.flags.negative = self.reg_x > 127;
+        self.add_tracelog(Operand::Implied, format_args!("TAX"));
     }
 
     pub fn tay(&mut self) {
         self.reg_y = self.reg_a;
         self.cycle();
-        self.add_tracelog(&[], String::from("TAY"));
+        self.flags.zero = self.reg_y == 0;
+        self.flags.negative = self.reg_y > 127;
+        self.add_tracelog(Operand::Implied, format_args!("TAY"));
     }
 
     pub fn tsx(&mut self) {
         self.reg_x = self.stack;
         self.cycle();
-        self.add_tracelog(&[], String::from("TSX"));
+        self.flags.zero = self.reg_x == 0;
+        self.flags.negative = self.reg_x > 127;
+        self.add_tracelog(Operand::Implied, format_args!("TSX"));
     }
 
     pub fn txa(&mut self) {
         self.reg_a = self.reg_x;
         self.cycle();
-        self.add_tracelog(&[], String::from("TXA"));
+        self.flags.zero = self.reg_a == 0;
+        self.flags.negative = self.reg_a > 127;
+        self.add_tracelog(Operand::Implied, format_args!("TXA"));
     }
 
     pub fn txs(&mut self) {
         self.stack = self.reg_x;
         self.cycle();
-        self.add_tracelog(&[], String::from("TXS"));
+        self.add_tracelog(Operand::Implied, format_args!("TXS"));
     }
 
     pub fn tya(&mut self) {
         self.reg_a = self.reg_y;
         self.cycle();
-        self.add_tracelog(&[], String::from("TYA"));
+        self.flags.zero = self.reg_a == 0;
+        self.flags.negative = self.reg_a > 127;
+        self.add_tracelog(Operand::Implied, format_args!("TYA"));
     }
 
     pub fn hlt(&mut self) {
         self.bus.data = 0xFF;
         self.running = false;
-        self.add_tracelog(&[], String::from("HLT"));
+        self.add_tracelog(Operand::Implied, format_args!("HLT"));
     }
 }
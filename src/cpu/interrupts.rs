@@ -0,0 +1,116 @@
+use crate::cpu::{Cpu, Operand};
+
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
+/// Something that can pull the shared /IRQ line low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqSource {
+    Mapper = 0x01,
+    FrameCounter = 0x02,
+    Dmc = 0x04,
+    /// Anything outside the console, like the expansion port or a test harness.
+    External = 0x08,
+}
+
+/// The wired-OR /IRQ line, asserted while any source holds it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IrqLine {
+    sources: u8,
+}
+
+impl IrqLine {
+    pub fn assert(&mut self, source: IrqSource) {
+        self.sources |= source as u8;
+    }
+
+    pub fn release(&mut self, source: IrqSource) {
+        self.sources &= !(source as u8);
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.sources != 0
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        self.sources
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        Self { sources: byte }
+    }
+}
+
+impl Cpu {
+    /// Samples the interrupt lines at the end of a cycle.
+    ///
+    /// The CPU decides whether to take an interrupt from what it saw at the end of an
+    /// instruction's second to last cycle, so the previous sample is kept too. That
+    /// is what delays an IRQ by one instruction after `CLI`, `SEI` and `PLP`.
+    pub(crate) fn poll_interrupts(&mut self) {
+        self.prev_nmi_pending = self.nmi_pending;
+
+        // NMI is edge triggered, only a low to high change latches it
+        let line = self.bus.nmi_line();
+        if line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = line;
+
+        self.prev_irq_run = self.irq_run;
+        self.irq_run = self.bus.irq_line() && !self.flags.interrupt_disable;
+    }
+
+    /// Whether an interrupt takes the place of the next instruction.
+    pub fn interrupt_due(&self) -> bool {
+        self.prev_nmi_pending || self.prev_irq_run
+    }
+
+    /// A taken branch that stays on its page doesn't poll on its last cycle, so an
+    /// IRQ that only showed up during the branch waits for one more instruction.
+    pub(crate) fn branch_delays_irq(&mut self) {
+        if self.irq_run && !self.prev_irq_run {
+            self.irq_run = false;
+        }
+    }
+
+    /// Runs the 7 cycle interrupt sequence in place of the next instruction.
+    ///
+    /// An NMI that shows up before the vector is picked hijacks an IRQ, which then
+    /// goes through `$FFFA` instead.
+    pub fn interrupt(&mut self) {
+        let pc = self.counter;
+
+        // the opcode fetch and the operand fetch after it are thrown away
+        self.access(pc);
+        self.access(pc);
+
+        self.push_stack((pc >> 8) as u8);
+        self.push_stack((pc & 0xFF) as u8);
+
+        let kind = self.push_status_and_vector(false);
+        self.add_tracelog(Operand::Implied, format_args!("{kind} -> ${:04X}", self.counter));
+    }
+
+    /// The tail shared by interrupts and `BRK`: pushes the status, sets I and jumps
+    /// through whichever vector a pending NMI leaves, returning `"NMI"` or `"IRQ"`.
+    pub(crate) fn push_status_and_vector(&mut self, brk: bool) -> &'static str {
+        let (kind, vector) = if self.nmi_pending {
+            self.nmi_pending = false;
+            ("NMI", NMI_VECTOR)
+        } else {
+            ("IRQ", IRQ_VECTOR)
+        };
+
+        let mut status = self.flags.clone();
+        status.brk = brk;
+        self.push_stack(status.to_byte());
+        self.flags.interrupt_disable = true;
+
+        let low = self.read(vector) as u16;
+        let high = self.read(vector + 1) as u16;
+        self.counter = (high << 8) | low;
+
+        kind
+    }
+}
@@ -1,19 +1,32 @@
-use crate::js::consoleLog;
-use status_flags::CpuFlags;
+use std::fmt;
+
+use crate::cdl::CodeDataLog;
+use crate::host::{HostRef, Tracelog};
 
-use crate::js::{addTracelog};
 
 mod status_flags;
 mod addressing;
+mod opcodes;
+mod disasm;
+mod trace;
+mod state;
+mod interrupts;
 mod instructions;
+mod unofficial;
 mod bus;
 mod ram;
 
 pub use bus::Bus;
 pub use ram::Ram;
+pub use status_flags::CpuFlags;
+pub use addressing::Operand;
+pub use opcodes::{AddrMode, Opcode, OPCODES};
+pub use disasm::{decode, disassemble, Instruction, InstructionBytes};
+pub use interrupts::{IrqLine, IrqSource};
 
 pub struct Cpu {
     pub cycles: usize,
+    pub total_cycles: u64,
     pub counter: u16,
     pub reg_a: u8,
     pub reg_x: u8,
@@ -22,14 +35,26 @@ pub struct Cpu {
     pub flags: CpuFlags,
     pub bus: Bus,
     pub running: bool,
+    /// Level of the NMI line at the end of the last cycle.
+    pub nmi_line: bool,
+    /// An NMI edge was seen and the interrupt hasn't been taken yet.
+    pub nmi_pending: bool,
+    /// `nmi_pending` as of the end of the cycle before the last one.
+    pub prev_nmi_pending: bool,
+    /// The IRQ line was asserted with I clear at the end of the last cycle.
+    pub irq_run: bool,
+    /// `irq_run` as of the end of the cycle before the last one.
+    pub prev_irq_run: bool,
+    host: HostRef,
     last_read_instruction: u8,
     last_location: u16,
 }
 
 impl Cpu {
-    pub fn new(bus: Bus) -> Self {
+    pub fn new(bus: Bus, host: HostRef) -> Self {
         Self {
             cycles: 0,
+            total_cycles: 0,
             counter: 0,
             reg_a: 0,
             reg_x: 0,
@@ -38,23 +63,31 @@ impl Cpu {
             bus,
             flags: CpuFlags::default(),
             running: false,
+            nmi_line: false,
+            nmi_pending: false,
+            prev_nmi_pending: false,
+            irq_run: false,
+            prev_irq_run: false,
+            host,
             last_read_instruction: 0,
             last_location: 0
         }
     }
 
-    pub async fn reset(&mut self) {
+    /// Runs the 7 cycle reset sequence and jumps through the reset vector.
+    pub fn reset(&mut self) {
+        self.cycles = 0;
         self.flags.interrupt_disable = true;
         self.running = true;
 
-        let jmp_addr = {
-            self.bus.address = 0xFFFC;
-            self.bus.read();
-            let second = self.bus.data;
+        // two dummy opcode fetches and three suppressed stack pushes
+        for _ in 0..5 {
+            self.cycle();
+        }
 
-            self.bus.address = 0xFFFD;
-            self.bus.read();
-            let first = self.bus.data;
+        let jmp_addr = {
+            let second = self.read(0xFFFC);
+            let first = self.read(0xFFFD);
 
             first as u16 * 0x100 + second as u16
         };
@@ -63,11 +96,24 @@ impl Cpu {
         self.stack = 0xFD;
     }
 
+    /// Runs one instruction, or takes a pending interrupt, and returns the cycles it took.
+    ///
+    /// Dispatch stays a match rather than going through `OPCODES` on purpose. Each
+    /// handler spends its cycles bus access by bus access, dummy reads and page
+    /// fixups included, which is where the exact timing comes from. `OPCODES` only
+    /// describes the outcome, and `tests/opcode_table.rs` keeps the two in step.
     pub fn clock(&mut self) -> usize {
         self.cycles = 0;
         self.last_location = self.counter;
+
+        if self.interrupt_due() {
+            self.interrupt();
+            return self.cycles;
+        }
+
         let byte = self.read_next();
-        self.last_read_instruction = byte.clone();
+        self.last_read_instruction = byte;
+        self.bus.log_prg(self.last_location, CodeDataLog::OPCODE);
 
         match byte {
             0x69 => self.run_op(Self::adc, Self::immediate),
@@ -108,19 +154,23 @@ impl Cpu {
 
             0x18 => {
                 self.flags.carry = false;
-                self.add_tracelog(&[], String::from("CLC"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("CLC"));
             },
             0xD8 => {
                 self.flags.decimal = false;
-                self.add_tracelog(&[], String::from("CLD"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("CLD"));
             },
             0x58 => {
                 self.flags.interrupt_disable = false;
-                self.add_tracelog(&[], String::from("CLI"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("CLI"));
             },
             0xB8 => {
                 self.flags.overflow = false;
-                self.add_tracelog(&[], String::from("CLV"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("CLV"));
             },
 
             0x00 => self.brk(),
@@ -241,15 +291,18 @@ impl Cpu {
 
             0x38 => {
                 self.flags.carry = true;
-                self.add_tracelog(&[], String::from("SEC"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("SEC"));
             },
             0xF8 => {
                 self.flags.decimal = true;
-                self.add_tracelog(&[], String::from("SED"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("SED"));
             },
             0x78 => {
                 self.flags.interrupt_disable = true;
-                self.add_tracelog(&[], String::from("SEI"));
+                self.cycle();
+                self.add_tracelog(Operand::Implied, format_args!("SEI"));
             },
 
             0x85 => self.run_op_addr(Self::sta, Self::zeropage_addr),
@@ -275,10 +328,83 @@ impl Cpu {
             0x9A => self.txs(),
             0x98 => self.tya(),
 
+            0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => self.nop(),
+            0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => self.run_op(Self::nop_read, Self::immediate),
+            0x04 | 0x44 | 0x64 => self.run_op(Self::nop_read, Self::zeropage),
+            0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => self.run_op(Self::nop_read, Self::zeropage_x),
+            0x0C => self.run_op(Self::nop_read, Self::absolute),
+            0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => self.run_op(Self::nop_read, Self::absolute_x),
+
+            0xA7 => self.run_op(Self::lax, Self::zeropage),
+            0xB7 => self.run_op(Self::lax, Self::zeropage_y),
+            0xAF => self.run_op(Self::lax, Self::absolute),
+            0xBF => self.run_op(Self::lax, Self::absolute_y),
+            0xA3 => self.run_op(Self::lax, Self::indirect_x),
+            0xB3 => self.run_op(Self::lax, Self::indirect_y),
+
+            0x87 => self.run_op_addr(Self::sax, Self::zeropage_addr),
+            0x97 => self.run_op_addr(Self::sax, Self::zeropage_y_addr),
+            0x8F => self.run_op_addr(Self::sax, Self::absolute_addr),
+            0x83 => self.run_op_addr(Self::sax, Self::indirect_x_addr),
+
+            0xC7 => self.run_op_addr(Self::dcp, Self::zeropage_addr),
+            0xD7 => self.run_op_addr(Self::dcp, Self::zeropage_x_addr),
+            0xCF => self.run_op_addr(Self::dcp, Self::absolute_addr),
+            0xDF => self.run_op_addr(Self::dcp, Self::absolute_x_addr),
+            0xDB => self.run_op_addr(Self::dcp, Self::absolute_y_addr),
+            0xC3 => self.run_op_addr(Self::dcp, Self::indirect_x_addr),
+            0xD3 => self.run_op_addr(Self::dcp, Self::indirect_y_addr),
+
+            0xE7 => self.run_op_addr(Self::isb, Self::zeropage_addr),
+            0xF7 => self.run_op_addr(Self::isb, Self::zeropage_x_addr),
+            0xEF => self.run_op_addr(Self::isb, Self::absolute_addr),
+            0xFF => self.run_op_addr(Self::isb, Self::absolute_x_addr),
+            0xFB => self.run_op_addr(Self::isb, Self::absolute_y_addr),
+            0xE3 => self.run_op_addr(Self::isb, Self::indirect_x_addr),
+            0xF3 => self.run_op_addr(Self::isb, Self::indirect_y_addr),
+
+            0x07 => self.run_op_addr(Self::slo, Self::zeropage_addr),
+            0x17 => self.run_op_addr(Self::slo, Self::zeropage_x_addr),
+            0x0F => self.run_op_addr(Self::slo, Self::absolute_addr),
+            0x1F => self.run_op_addr(Self::slo, Self::absolute_x_addr),
+            0x1B => self.run_op_addr(Self::slo, Self::absolute_y_addr),
+            0x03 => self.run_op_addr(Self::slo, Self::indirect_x_addr),
+            0x13 => self.run_op_addr(Self::slo, Self::indirect_y_addr),
+
+            0x27 => self.run_op_addr(Self::rla, Self::zeropage_addr),
+            0x37 => self.run_op_addr(Self::rla, Self::zeropage_x_addr),
+            0x2F => self.run_op_addr(Self::rla, Self::absolute_addr),
+            0x3F => self.run_op_addr(Self::rla, Self::absolute_x_addr),
+            0x3B => self.run_op_addr(Self::rla, Self::absolute_y_addr),
+            0x23 => self.run_op_addr(Self::rla, Self::indirect_x_addr),
+            0x33 => self.run_op_addr(Self::rla, Self::indirect_y_addr),
+
+            0x47 => self.run_op_addr(Self::sre, Self::zeropage_addr),
+            0x57 => self.run_op_addr(Self::sre, Self::zeropage_x_addr),
+            0x4F => self.run_op_addr(Self::sre, Self::absolute_addr),
+            0x5F => self.run_op_addr(Self::sre, Self::absolute_x_addr),
+            0x5B => self.run_op_addr(Self::sre, Self::absolute_y_addr),
+            0x43 => self.run_op_addr(Self::sre, Self::indirect_x_addr),
+            0x53 => self.run_op_addr(Self::sre, Self::indirect_y_addr),
+
+            0x67 => self.run_op_addr(Self::rra, Self::zeropage_addr),
+            0x77 => self.run_op_addr(Self::rra, Self::zeropage_x_addr),
+            0x6F => self.run_op_addr(Self::rra, Self::absolute_addr),
+            0x7F => self.run_op_addr(Self::rra, Self::absolute_x_addr),
+            0x7B => self.run_op_addr(Self::rra, Self::absolute_y_addr),
+            0x63 => self.run_op_addr(Self::rra, Self::indirect_x_addr),
+            0x73 => self.run_op_addr(Self::rra, Self::indirect_y_addr),
+
+            0x0B | 0x2B => self.run_op(Self::anc, Self::immediate),
+            0x4B => self.run_op(Self::alr, Self::immediate),
+            0x6B => self.run_op(Self::arr, Self::immediate),
+            0xCB => self.run_op(Self::axs, Self::immediate),
+            0xEB => self.run_op(Self::sbc, Self::immediate),
+
             0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => self.hlt(),
 
             x => {
-                consoleLog(format!("Unimplemented instruction! {x:#x}").as_str());
+                self.host.console_log(format!("Unimplemented instruction! {x:#x}").as_str());
                 return self.cycles
             }
         };
@@ -286,30 +412,63 @@ impl Cpu {
         self.cycles
     }
 
+    /// The opcode of the last instruction fetched.
+    pub fn last_opcode(&self) -> u8 {
+        self.last_read_instruction
+    }
+
+    /// Fetches the next byte of the instruction stream.
     pub fn read_next(&mut self) -> u8 {
-        self.bus.address = self.counter;
-        self.bus.read();
+        let addr = self.counter;
         self.counter = self.counter.wrapping_add(1);
-        self.cycle();
-        self.bus.data
+        let byte = self.access(addr);
+        self.bus.log_prg(addr, CodeDataLog::CODE);
+        byte
     }
 
+    /// Reads data, from where an instruction or an interrupt says.
     pub fn read(&mut self, addr: u16) -> u8 {
+        let byte = self.access(addr);
+        self.bus.log_prg(addr, CodeDataLog::DATA);
+        byte
+    }
+
+    /// Reads through a pointer.
+    pub fn read_indirect(&mut self, addr: u16) -> u8 {
+        let byte = self.access(addr);
+        self.bus.log_prg(addr, CodeDataLog::DATA | CodeDataLog::INDIRECT_DATA);
+        byte
+    }
+
+    /// A read cycle whose byte goes unused, so it isn't logged as code or data.
+    fn access(&mut self, addr: u16) -> u8 {
+        self.begin_cycle();
         self.bus.address = addr;
         self.bus.read();
-        self.cycle();
+        self.poll_interrupts();
         self.bus.data
     }
 
     pub fn write(&mut self, addr: u16, val: u8) {
+        self.begin_cycle();
         self.bus.address = addr;
         self.bus.data = val;
         self.bus.write();
-        self.cycle();
+        self.poll_interrupts();
     }
 
+    /// Spends one CPU cycle without touching the bus.
     pub fn cycle(&mut self) {
+        self.begin_cycle();
+        self.poll_interrupts();
+    }
+
+    /// Lets the rest of the machine catch up before the cycle's bus access lands.
+    /// Interrupt lines are sampled once it has, at the end of the cycle.
+    fn begin_cycle(&mut self) {
         self.cycles = self.cycles.wrapping_add(1);
+        self.total_cycles = self.total_cycles.wrapping_add(1);
+        self.bus.tick();
     }
 
     pub fn push_stack(&mut self, val: u8) {
@@ -323,7 +482,18 @@ impl Cpu {
         self.read(0x100 + self.stack as u16)
     }
 
-    pub fn add_tracelog(&self, by: &[u8], inst: String) {
+    /// Pulls a byte straight after another pull, where the stack pointer increment doesn't take a cycle of its own.
+    pub fn pull_stack_next(&mut self) -> u8 {
+        self.stack = self.stack.wrapping_add(1);
+        self.read(0x100 + self.stack as u16)
+    }
+
+    /// Reports the instruction that just ran. Nothing gets formatted unless the host keeps a tracelog.
+    pub fn add_tracelog(&self, operand: Operand, inst: fmt::Arguments) {
+        if !self.host.wants_tracelog() {
+            return;
+        }
+
         let f = format!(
             "{}{}--{}{}{}{}",
             if self.flags.negative { "N" } else { "n" },
@@ -334,24 +504,24 @@ impl Cpu {
             if self.flags.carry { "C" } else { "c" },
         );
 
-        addTracelog(
-            format!("{:04X}", self.last_location).as_str(),
-            format!("{:02X} {}", self.last_read_instruction, by.into_iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(" ").as_str()).as_str(),
-            inst.as_str(),
-            format!("{:02X}", self.reg_a).as_str(),
-            format!("{:02X}", self.reg_x).as_str(),
-            format!("{:02X}", self.reg_y).as_str(),
-            format!("{:02X}", self.stack).as_str(),
-            format!("{:02X}", self.flags.to_byte()).as_str(),
-            f.as_str(),
-            self.cycles.to_string().as_str()
-        );
+        self.host.add_tracelog(&Tracelog {
+            pg: format!("{:04X}", self.last_location),
+            by: format!("{:02X} {}", self.last_read_instruction, operand.bytes()),
+            inst: inst.to_string(),
+            reg_a: format!("{:02X}", self.reg_a),
+            reg_x: format!("{:02X}", self.reg_x),
+            reg_y: format!("{:02X}", self.reg_y),
+            sp: format!("{:02X}", self.stack),
+            p: format!("{:02X}", self.flags.to_byte()),
+            f,
+            cy: self.cycles.to_string(),
+        });
     }
 
     fn run_op(
         &mut self,
-        instr: fn(&mut Self, (Vec<u8>, u8, String)),
-        addr_mode: fn(&mut Self) -> (Vec<u8>, u8, String)
+        instr: fn(&mut Self, (Operand, u8)),
+        addr_mode: fn(&mut Self) -> (Operand, u8)
     ) {
         let operand = addr_mode(self);
         instr(self, operand);
@@ -359,8 +529,8 @@ impl Cpu {
 
     fn run_op_addr(
         &mut self,
-        instr: fn(&mut Self, (Vec<u8>, u16, String)),
-        addr_mode: fn(&mut Self) -> (Vec<u8>, u16, String)
+        instr: fn(&mut Self, (Operand, u16)),
+        addr_mode: fn(&mut Self) -> (Operand, u16)
     ) {
         let operand = addr_mode(self);
         instr(self, operand);
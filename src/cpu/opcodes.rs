@@ -0,0 +1,126 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddrMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddrMode {
+    /// Number of operand bytes following the opcode.
+    pub fn operand_len(&self) -> usize {
+        match self {
+            AddrMode::Implied | AddrMode::Accumulator => 0,
+            AddrMode::Absolute | AddrMode::AbsoluteX | AddrMode::AbsoluteY | AddrMode::Indirect => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub mode: AddrMode,
+    /// Cycles taken without a page cross or a taken branch. `HLT` only gets its opcode fetch in.
+    pub cycles: u8,
+    /// Whether crossing a page costs one more cycle. Branches pay it on top of the cycle for being taken.
+    pub page_cross: bool,
+    pub official: bool,
+}
+
+impl Opcode {
+    /// Length of the whole instruction in bytes, opcode included.
+    pub fn size(&self) -> usize {
+        1 + self.mode.operand_len()
+    }
+}
+
+/// Indexed reads can skip fixing up the high byte when no page is crossed, stores
+/// and read-modify-writes can't. The ones that can are exactly those listed with
+/// the shorter cycle count for their mode.
+const fn page_cross(mode: AddrMode, cycles: u8) -> bool {
+    match mode {
+        AddrMode::AbsoluteX | AddrMode::AbsoluteY => cycles == 4,
+        AddrMode::IndirectY => cycles == 5,
+        AddrMode::Relative => true,
+        _ => false,
+    }
+}
+
+const fn op(mnemonic: &'static str, mode: AddrMode, cycles: u8) -> Opcode {
+    Opcode { mnemonic, mode, cycles, page_cross: page_cross(mode, cycles), official: true }
+}
+
+const fn un(mnemonic: &'static str, mode: AddrMode, cycles: u8) -> Opcode {
+    Opcode { mnemonic, mode, cycles, page_cross: page_cross(mode, cycles), official: false }
+}
+
+use AddrMode::{
+    Absolute as Abs, AbsoluteX as Abx, AbsoluteY as Aby, Accumulator as Acc, Immediate as Imm, Implied as Imp,
+    Indirect as Ind, IndirectX as Izx, IndirectY as Izy, Relative as Rel, ZeroPage as Zp, ZeroPageX as Zpx,
+    ZeroPageY as Zpy,
+};
+
+/// Every opcode byte, with the unofficial ones named the way nestest.log names them.
+///
+/// The disassembler and tracer decode from this table. Execution doesn't, see `Cpu::clock`.
+#[rustfmt::skip]
+pub static OPCODES: [Opcode; 256] = [
+    // 0x00
+    op("BRK", Imp, 7),  op("ORA", Izx, 6),  un("HLT", Imp, 1),  un("SLO", Izx, 8),  un("NOP", Zp, 3),   op("ORA", Zp, 3),   op("ASL", Zp, 5),   un("SLO", Zp, 5),
+    op("PHP", Imp, 3),  op("ORA", Imm, 2),  op("ASL", Acc, 2),  un("ANC", Imm, 2),  un("NOP", Abs, 4),  op("ORA", Abs, 4),  op("ASL", Abs, 6),  un("SLO", Abs, 6),
+    // 0x10
+    op("BPL", Rel, 2),  op("ORA", Izy, 5),  un("HLT", Imp, 1),  un("SLO", Izy, 8),  un("NOP", Zpx, 4),  op("ORA", Zpx, 4),  op("ASL", Zpx, 6),  un("SLO", Zpx, 6),
+    op("CLC", Imp, 2),  op("ORA", Aby, 4),  un("NOP", Imp, 2),  un("SLO", Aby, 7),  un("NOP", Abx, 4),  op("ORA", Abx, 4),  op("ASL", Abx, 7),  un("SLO", Abx, 7),
+    // 0x20
+    op("JSR", Abs, 6),  op("AND", Izx, 6),  un("HLT", Imp, 1),  un("RLA", Izx, 8),  op("BIT", Zp, 3),   op("AND", Zp, 3),   op("ROL", Zp, 5),   un("RLA", Zp, 5),
+    op("PLP", Imp, 4),  op("AND", Imm, 2),  op("ROL", Acc, 2),  un("ANC", Imm, 2),  op("BIT", Abs, 4),  op("AND", Abs, 4),  op("ROL", Abs, 6),  un("RLA", Abs, 6),
+    // 0x30
+    op("BMI", Rel, 2),  op("AND", Izy, 5),  un("HLT", Imp, 1),  un("RLA", Izy, 8),  un("NOP", Zpx, 4),  op("AND", Zpx, 4),  op("ROL", Zpx, 6),  un("RLA", Zpx, 6),
+    op("SEC", Imp, 2),  op("AND", Aby, 4),  un("NOP", Imp, 2),  un("RLA", Aby, 7),  un("NOP", Abx, 4),  op("AND", Abx, 4),  op("ROL", Abx, 7),  un("RLA", Abx, 7),
+    // 0x40
+    op("RTI", Imp, 6),  op("EOR", Izx, 6),  un("HLT", Imp, 1),  un("SRE", Izx, 8),  un("NOP", Zp, 3),   op("EOR", Zp, 3),   op("LSR", Zp, 5),   un("SRE", Zp, 5),
+    op("PHA", Imp, 3),  op("EOR", Imm, 2),  op("LSR", Acc, 2),  un("ALR", Imm, 2),  op("JMP", Abs, 3),  op("EOR", Abs, 4),  op("LSR", Abs, 6),  un("SRE", Abs, 6),
+    // 0x50
+    op("BVC", Rel, 2),  op("EOR", Izy, 5),  un("HLT", Imp, 1),  un("SRE", Izy, 8),  un("NOP", Zpx, 4),  op("EOR", Zpx, 4),  op("LSR", Zpx, 6),  un("SRE", Zpx, 6),
+    op("CLI", Imp, 2),  op("EOR", Aby, 4),  un("NOP", Imp, 2),  un("SRE", Aby, 7),  un("NOP", Abx, 4),  op("EOR", Abx, 4),  op("LSR", Abx, 7),  un("SRE", Abx, 7),
+    // 0x60
+    op("RTS", Imp, 6),  op("ADC", Izx, 6),  un("HLT", Imp, 1),  un("RRA", Izx, 8),  un("NOP", Zp, 3),   op("ADC", Zp, 3),   op("ROR", Zp, 5),   un("RRA", Zp, 5),
+    op("PLA", Imp, 4),  op("ADC", Imm, 2),  op("ROR", Acc, 2),  un("ARR", Imm, 2),  op("JMP", Ind, 5),  op("ADC", Abs, 4),  op("ROR", Abs, 6),  un("RRA", Abs, 6),
+    // 0x70
+    op("BVS", Rel, 2),  op("ADC", Izy, 5),  un("HLT", Imp, 1),  un("RRA", Izy, 8),  un("NOP", Zpx, 4),  op("ADC", Zpx, 4),  op("ROR", Zpx, 6),  un("RRA", Zpx, 6),
+    op("SEI", Imp, 2),  op("ADC", Aby, 4),  un("NOP", Imp, 2),  un("RRA", Aby, 7),  un("NOP", Abx, 4),  op("ADC", Abx, 4),  op("ROR", Abx, 7),  un("RRA", Abx, 7),
+    // 0x80
+    un("NOP", Imm, 2),  op("STA", Izx, 6),  un("NOP", Imm, 2),  un("SAX", Izx, 6),  op("STY", Zp, 3),   op("STA", Zp, 3),   op("STX", Zp, 3),   un("SAX", Zp, 3),
+    op("DEY", Imp, 2),  un("NOP", Imm, 2),  op("TXA", Imp, 2),  un("XAA", Imm, 2),  op("STY", Abs, 4),  op("STA", Abs, 4),  op("STX", Abs, 4),  un("SAX", Abs, 4),
+    // 0x90
+    op("BCC", Rel, 2),  op("STA", Izy, 6),  un("HLT", Imp, 1),  un("AHX", Izy, 6),  op("STY", Zpx, 4),  op("STA", Zpx, 4),  op("STX", Zpy, 4),  un("SAX", Zpy, 4),
+    op("TYA", Imp, 2),  op("STA", Aby, 5),  op("TXS", Imp, 2),  un("TAS", Aby, 5),  un("SHY", Abx, 5),  op("STA", Abx, 5),  un("SHX", Aby, 5),  un("AHX", Aby, 5),
+    // 0xA0
+    op("LDY", Imm, 2),  op("LDA", Izx, 6),  op("LDX", Imm, 2),  un("LAX", Izx, 6),  op("LDY", Zp, 3),   op("LDA", Zp, 3),   op("LDX", Zp, 3),   un("LAX", Zp, 3),
+    op("TAY", Imp, 2),  op("LDA", Imm, 2),  op("TAX", Imp, 2),  un("LAX", Imm, 2),  op("LDY", Abs, 4),  op("LDA", Abs, 4),  op("LDX", Abs, 4),  un("LAX", Abs, 4),
+    // 0xB0
+    op("BCS", Rel, 2),  op("LDA", Izy, 5),  un("HLT", Imp, 1),  un("LAX", Izy, 5),  op("LDY", Zpx, 4),  op("LDA", Zpx, 4),  op("LDX", Zpy, 4),  un("LAX", Zpy, 4),
+    op("CLV", Imp, 2),  op("LDA", Aby, 4),  op("TSX", Imp, 2),  un("LAS", Aby, 4),  op("LDY", Abx, 4),  op("LDA", Abx, 4),  op("LDX", Aby, 4),  un("LAX", Aby, 4),
+    // 0xC0
+    op("CPY", Imm, 2),  op("CMP", Izx, 6),  un("NOP", Imm, 2),  un("DCP", Izx, 8),  op("CPY", Zp, 3),   op("CMP", Zp, 3),   op("DEC", Zp, 5),   un("DCP", Zp, 5),
+    op("INY", Imp, 2),  op("CMP", Imm, 2),  op("DEX", Imp, 2),  un("AXS", Imm, 2),  op("CPY", Abs, 4),  op("CMP", Abs, 4),  op("DEC", Abs, 6),  un("DCP", Abs, 6),
+    // 0xD0
+    op("BNE", Rel, 2),  op("CMP", Izy, 5),  un("HLT", Imp, 1),  un("DCP", Izy, 8),  un("NOP", Zpx, 4),  op("CMP", Zpx, 4),  op("DEC", Zpx, 6),  un("DCP", Zpx, 6),
+    op("CLD", Imp, 2),  op("CMP", Aby, 4),  un("NOP", Imp, 2),  un("DCP", Aby, 7),  un("NOP", Abx, 4),  op("CMP", Abx, 4),  op("DEC", Abx, 7),  un("DCP", Abx, 7),
+    // 0xE0
+    op("CPX", Imm, 2),  op("SBC", Izx, 6),  un("NOP", Imm, 2),  un("ISB", Izx, 8),  op("CPX", Zp, 3),   op("SBC", Zp, 3),   op("INC", Zp, 5),   un("ISB", Zp, 5),
+    op("INX", Imp, 2),  op("SBC", Imm, 2),  op("NOP", Imp, 2),  un("SBC", Imm, 2),  op("CPX", Abs, 4),  op("SBC", Abs, 4),  op("INC", Abs, 6),  un("ISB", Abs, 6),
+    // 0xF0
+    op("BEQ", Rel, 2),  op("SBC", Izy, 5),  un("HLT", Imp, 1),  un("ISB", Izy, 8),  un("NOP", Zpx, 4),  op("SBC", Zpx, 4),  op("INC", Zpx, 6),  un("ISB", Zpx, 6),
+    op("SED", Imp, 2),  op("SBC", Aby, 4),  un("NOP", Imp, 2),  un("ISB", Aby, 7),  un("NOP", Abx, 4),  op("SBC", Abx, 4),  op("INC", Abx, 7),  un("ISB", Abx, 7),
+];
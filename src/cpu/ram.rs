@@ -1,34 +1,41 @@
 use std::fmt;
 
-use crate::js::updateRam;
+use crate::host::HostRef;
 
 pub struct Ram {
     contents: [u8; 0x800],
+    host: HostRef,
 }
 
 impl Ram {
-    pub fn read(&self, addr: u16) -> Option<u8> {
-        if let Some(byte) = self.contents.get(addr as usize) {
-            Some(byte.clone())
-        } else {
-            None
+    pub fn new(host: HostRef) -> Self {
+        let contents = [0u8; 0x800];
+        host.update_ram(&contents);
+
+        Self {
+            contents,
+            host,
         }
     }
 
+    /// Reads `addr`, which mirrors the 2 KiB every 2 KiB.
+    pub fn read(&self, addr: u16) -> u8 {
+        self.contents[addr as usize & 0x7FF]
+    }
+
     pub fn write(&mut self, addr: u16, byte: u8) {
-        self.contents[addr as usize] = byte;
-        updateRam(&self.contents);
+        self.contents[addr as usize & 0x7FF] = byte;
+        self.host.update_ram(&self.contents);
     }
-}
 
-impl Default for Ram {
-    fn default() -> Self {
-        let contents = [0u8; 0x800];
-        updateRam(&contents);
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
 
-        Self {
-            contents,
-        }
+    /// Replaces the whole of RAM, `contents` must be exactly 2 KiB.
+    pub fn set_contents(&mut self, contents: &[u8]) {
+        self.contents.copy_from_slice(contents);
+        self.host.update_ram(&self.contents);
     }
 }
 
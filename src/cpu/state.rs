@@ -0,0 +1,74 @@
+use crate::controller::Controller;
+use crate::cpu::{Bus, Cpu, CpuFlags, IrqLine};
+use crate::state::{StateError, StateReader, StateWriter};
+
+impl Cpu {
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.counter);
+        w.u8(self.reg_a);
+        w.u8(self.reg_x);
+        w.u8(self.reg_y);
+        w.u8(self.stack);
+        w.u8(self.flags.to_byte());
+        w.bool(self.flags.brk);
+        w.bool(self.running);
+        w.u32(self.cycles as u32);
+        w.u64(self.total_cycles);
+        w.u8(self.last_read_instruction);
+        w.u16(self.last_location);
+        w.bool(self.nmi_line);
+        w.bool(self.nmi_pending);
+        w.bool(self.prev_nmi_pending);
+        w.bool(self.irq_run);
+        w.bool(self.prev_irq_run);
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.counter = r.u16()?;
+        self.reg_a = r.u8()?;
+        self.reg_x = r.u8()?;
+        self.reg_y = r.u8()?;
+        self.stack = r.u8()?;
+        self.flags = CpuFlags::from_byte(r.u8()?);
+        self.flags.brk = r.bool()?;
+        self.running = r.bool()?;
+        self.cycles = r.u32()? as usize;
+        self.total_cycles = r.u64()?;
+        self.last_read_instruction = r.u8()?;
+        self.last_location = r.u16()?;
+        self.nmi_line = r.bool()?;
+        self.nmi_pending = r.bool()?;
+        self.prev_nmi_pending = r.bool()?;
+        self.irq_run = r.bool()?;
+        self.prev_irq_run = r.bool()?;
+        Ok(())
+    }
+}
+
+impl Bus {
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.u16(self.address);
+        w.u8(self.data);
+        w.u8(self.irq.to_byte());
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.address = r.u16()?;
+        self.data = r.u8()?;
+        self.irq = IrqLine::from_byte(r.u8()?);
+        Ok(())
+    }
+
+    pub(crate) fn save_controllers(&self, w: &mut StateWriter) {
+        for controller in &self.controllers {
+            w.bytes(&controller.to_bytes());
+        }
+    }
+
+    pub(crate) fn load_controllers(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        for controller in self.controllers.iter_mut() {
+            *controller = Controller::from_bytes(r.array()?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::cpu::disasm::{decode, Instruction};
+use crate::cpu::{AddrMode, Cpu};
+use crate::symbols::Symbols;
+use crate::trace::{OperandPeek, TraceEntry};
+
+impl Cpu {
+    /// Describes the instruction at the program counter without executing it.
+    ///
+    /// The disassembly follows nestest.log, so memory operands are annotated with
+    /// the effective address and the value currently stored there.
+    pub fn trace_entry(&self) -> TraceEntry {
+        self.labelled_trace_entry(&Symbols::new())
+    }
+
+    /// A `trace_entry` with operand addresses named by their labels, where they have one.
+    pub fn labelled_trace_entry(&self, symbols: &Symbols) -> TraceEntry {
+        let pc = self.counter;
+        let window: [u8; 3] = std::array::from_fn(|i| self.bus.peek(pc.wrapping_add(i as u16)));
+        let instruction = decode(&window, pc).unwrap();
+
+        TraceEntry {
+            instruction,
+            operand: self.peek_operands(&instruction),
+            label: symbols.shared_label(Self::labelled_addr(&instruction)),
+            a: self.reg_a,
+            x: self.reg_x,
+            y: self.reg_y,
+            p: self.flags.to_byte(),
+            sp: self.stack,
+            scanline: 0,
+            dot: 0,
+            cycle: self.total_cycles,
+        }
+    }
+
+    /// The value shown for a memory operand. Like nestest.log, registers at
+    /// `$2000-$5FFF` aren't looked into, the trace shows whatever was last on the
+    /// bus for them. PRG RAM is plain memory and gets read.
+    fn peek_operand(&self, addr: u16) -> u8 {
+        match addr {
+            0x2000..=0x5FFF => self.bus.data,
+            _ => self.bus.peek(addr),
+        }
+    }
+
+    fn peek_word_zp(&self, zp: u8) -> u16 {
+        let low = self.bus.peek(zp as u16) as u16;
+        let high = self.bus.peek(zp.wrapping_add(1) as u16) as u16;
+        (high << 8) | low
+    }
+
+    /// The address whose label stands in for the operand: the zero page byte, the
+    /// absolute word, or a branch's target.
+    fn labelled_addr(instruction: &Instruction) -> u16 {
+        match instruction.info().mode {
+            AddrMode::ZeroPage | AddrMode::ZeroPageX | AddrMode::ZeroPageY | AddrMode::IndirectX | AddrMode::IndirectY => {
+                instruction.operand & 0xFF
+            }
+            AddrMode::Relative => instruction.target().unwrap(),
+            _ => instruction.operand,
+        }
+    }
+
+    fn peek_operands(&self, instruction: &Instruction) -> OperandPeek {
+        let byte = instruction.operand as u8;
+        let word = instruction.operand;
+        let at = |addr: u16| OperandPeek { pointer: 0, addr, value: self.peek_operand(addr) };
+
+        match instruction.info().mode {
+            AddrMode::Implied | AddrMode::Accumulator | AddrMode::Immediate | AddrMode::Relative => OperandPeek::default(),
+            AddrMode::ZeroPage => at(byte as u16),
+            AddrMode::ZeroPageX => at(byte.wrapping_add(self.reg_x) as u16),
+            AddrMode::ZeroPageY => at(byte.wrapping_add(self.reg_y) as u16),
+            AddrMode::Absolute if instruction.target().is_some() => OperandPeek::default(),
+            AddrMode::Absolute => at(word),
+            AddrMode::AbsoluteX => at(word.wrapping_add(self.reg_x as u16)),
+            AddrMode::AbsoluteY => at(word.wrapping_add(self.reg_y as u16)),
+            AddrMode::Indirect => {
+                // the pointer's high byte never leaves the page, just like the real JMP
+                let low = self.bus.peek(word) as u16;
+                let high = self.bus.peek((word & 0xFF00) | (word.wrapping_add(1) & 0x00FF)) as u16;
+                OperandPeek { pointer: 0, addr: (high << 8) | low, value: 0 }
+            }
+            AddrMode::IndirectX => {
+                let pointer = byte.wrapping_add(self.reg_x);
+                OperandPeek { pointer: pointer as u16, ..at(self.peek_word_zp(pointer)) }
+            }
+            AddrMode::IndirectY => {
+                let pointer = self.peek_word_zp(byte);
+                OperandPeek { pointer, ..at(pointer.wrapping_add(self.reg_y as u16)) }
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::cpu::{Cpu, Operand};
+
+// The stable unofficial opcodes. Most of them glue an official read-modify-write
+// onto an ALU operation, which is why they share the RMW addressing modes and
+// always pay the indexing cycle.
+impl Cpu {
+    /// Binary add with carry in, shared by `ADC`, `RRA` and (inverted) `ISB`.
+    pub(crate) fn add_with_carry(&mut self, operand: u8) {
+        let old_a = self.reg_a;
+        let res = self.reg_a as u16 + operand as u16 + self.flags.carry as u16;
+        self.reg_a = res as u8;
+        self.flags.carry = res > 0xFF;
+        self.flags.overflow = ((!(old_a ^ operand) & (old_a ^ self.reg_a)) & 0x80) != 0;
+        self.flags.zero = self.reg_a == 0;
+        self.flags.negative = self.reg_a > 127;
+    }
+
+    fn compare(&mut self, reg: u8, operand: u8) {
+        let res = reg.wrapping_sub(operand);
+        self.flags.carry = reg >= operand;
+        self.flags.zero = res == 0;
+        self.flags.negative = res > 127;
+    }
+
+    fn set_a(&mut self, val: u8) {
+        self.reg_a = val;
+        self.flags.zero = val == 0;
+        self.flags.negative = val > 127;
+    }
+
+    /// Reads, spends the dummy write cycle and writes back what `modify` makes of the value.
+    fn read_modify_write(&mut self, addr: u16, modify: impl FnOnce(&mut Self, u8) -> u8) -> u8 {
+        let value = self.read(addr);
+        self.cycle();
+        let result = modify(self, value);
+        self.write(addr, result);
+        result
+    }
+
+    /// `NOP` variants with an operand still read it, and take the page-cross cycle.
+    pub fn nop_read(&mut self, addressing: (Operand, u8)) {
+        self.add_tracelog(addressing.0, format_args!("NOP {}", addressing.0));
+    }
+
+    pub fn lax(&mut self, addressing: (Operand, u8)) {
+        self.set_a(addressing.1);
+        self.reg_x = addressing.1;
+        self.add_tracelog(addressing.0, format_args!("LAX {}", addressing.0));
+    }
+
+    pub fn sax(&mut self, addressing: (Operand, u16)) {
+        self.write(addressing.1, self.reg_a & self.reg_x);
+        self.add_tracelog(addressing.0, format_args!("SAX {}", addressing.0));
+    }
+
+    pub fn dcp(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |_, value| value.wrapping_sub(1));
+        self.compare(self.reg_a, result);
+        self.add_tracelog(addressing.0, format_args!("DCP {}", addressing.0));
+    }
+
+    pub fn isb(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |_, value| value.wrapping_add(1));
+        self.add_with_carry(!result);
+        self.add_tracelog(addressing.0, format_args!("ISB {}", addressing.0));
+    }
+
+    pub fn slo(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |cpu, value| {
+            cpu.flags.carry = (value & 0x80) != 0;
+            value << 1
+        });
+        self.set_a(self.reg_a | result);
+        self.add_tracelog(addressing.0, format_args!("SLO {}", addressing.0));
+    }
+
+    pub fn rla(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |cpu, value| {
+            let old_carry = cpu.flags.carry;
+            cpu.flags.carry = (value & 0x80) != 0;
+            (value << 1) | (old_carry as u8)
+        });
+        self.set_a(self.reg_a & result);
+        self.add_tracelog(addressing.0, format_args!("RLA {}", addressing.0));
+    }
+
+    pub fn sre(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |cpu, value| {
+            cpu.flags.carry = (value & 0x01) != 0;
+            value >> 1
+        });
+        self.set_a(self.reg_a ^ result);
+        self.add_tracelog(addressing.0, format_args!("SRE {}", addressing.0));
+    }
+
+    pub fn rra(&mut self, addressing: (Operand, u16)) {
+        let result = self.read_modify_write(addressing.1, |cpu, value| {
+            let old_carry = cpu.flags.carry;
+            cpu.flags.carry = (value & 0x01) != 0;
+            (value >> 1) | ((old_carry as u8) << 7)
+        });
+        self.add_with_carry(result);
+        self.add_tracelog(addressing.0, format_args!("RRA {}", addressing.0));
+    }
+
+    pub fn anc(&mut self, addressing: (Operand, u8)) {
+        self.set_a(self.reg_a & addressing.1);
+        self.flags.carry = self.flags.negative;
+        self.add_tracelog(addressing.0, format_args!("ANC {}", addressing.0));
+    }
+
+    pub fn alr(&mut self, addressing: (Operand, u8)) {
+        let value = self.reg_a & addressing.1;
+        self.flags.carry = (value & 0x01) != 0;
+        self.set_a(value >> 1);
+        self.add_tracelog(addressing.0, format_args!("ALR {}", addressing.0));
+    }
+
+    /// `AND` then `ROR A`, except C and V come out of the adder: C is bit 6, V is bit 6 XOR bit 5.
+    pub fn arr(&mut self, addressing: (Operand, u8)) {
+        let value = self.reg_a & addressing.1;
+        self.set_a((value >> 1) | ((self.flags.carry as u8) << 7));
+        self.flags.carry = (self.reg_a & 0x40) != 0;
+        self.flags.overflow = ((self.reg_a >> 6) ^ (self.reg_a >> 5)) & 0x01 != 0;
+        self.add_tracelog(addressing.0, format_args!("ARR {}", addressing.0));
+    }
+
+    /// X = (A AND X) - operand, with the flags of a compare and no borrow in.
+    pub fn axs(&mut self, addressing: (Operand, u8)) {
+        let and = self.reg_a & self.reg_x;
+        self.compare(and, addressing.1);
+        self.reg_x = and.wrapping_sub(addressing.1);
+        self.add_tracelog(addressing.0, format_args!("AXS {}", addressing.0));
+    }
+}
@@ -0,0 +1,52 @@
+/// Deepest the call stack is kept. Code that calls without ever returning, or
+/// throws away return addresses, would otherwise grow it forever.
+const MAX_DEPTH: usize = 128;
+
+/// One call still waiting for its return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// The `JSR` or `BRK` that made the call, or where an interrupt came in.
+    pub caller: u16,
+    /// Where the call went.
+    pub target: u16,
+    /// The stack pointer before the call pushed anything, which its return brings back.
+    pub sp: u8,
+    /// An NMI, IRQ or `BRK`, which returns with `RTI`.
+    pub interrupt: bool,
+}
+
+/// Calls and interrupts the CPU is inside of, followed as instructions run.
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    /// Innermost call last.
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn call(&mut self, frame: CallFrame) {
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+
+        self.frames.push(frame);
+    }
+
+    /// A return left the stack pointer at `sp`, ending every call made with the stack at or below it.
+    pub fn ret(&mut self, sp: u8) {
+        while self.frames.last().is_some_and(|frame| frame.sp <= sp) {
+            self.frames.pop();
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+use crate::cpu::Cpu;
+use crate::debugger::Access;
+use crate::ppu::Ppu;
+
+/// Something a condition can look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    A,
+    X,
+    Y,
+    Sp,
+    Pc,
+    P,
+    Carry,
+    Zero,
+    Interrupt,
+    Decimal,
+    Overflow,
+    Negative,
+    /// The byte a watchpoint saw being read or written.
+    Value,
+    /// The address a watchpoint saw being accessed.
+    Addr,
+    Cycle,
+    Scanline,
+    Dot,
+    Frame,
+}
+
+impl Var {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "a" => Var::A,
+            "x" => Var::X,
+            "y" => Var::Y,
+            "sp" | "s" => Var::Sp,
+            "pc" => Var::Pc,
+            "p" => Var::P,
+            "c" => Var::Carry,
+            "z" => Var::Zero,
+            "i" => Var::Interrupt,
+            "d" => Var::Decimal,
+            "v" => Var::Overflow,
+            "n" => Var::Negative,
+            "value" => Var::Value,
+            "addr" | "address" => Var::Addr,
+            "cycle" => Var::Cycle,
+            "scanline" => Var::Scanline,
+            "dot" => Var::Dot,
+            "frame" => Var::Frame,
+            _ => return None,
+        })
+    }
+}
+
+/// Machine state a condition is evaluated against.
+pub struct ExprContext<'a> {
+    pub cpu: &'a Cpu,
+    pub scanline: usize,
+    pub dot: usize,
+    pub frame: usize,
+    /// The bus access a watchpoint caught, if that's what is being checked.
+    pub access: Option<Access>,
+}
+
+impl<'a> ExprContext<'a> {
+    pub fn new(cpu: &'a Cpu, ppu: &Ppu, access: Option<Access>) -> Self {
+        Self { cpu, scanline: ppu.scanline, dot: ppu.dot, frame: ppu.frame, access }
+    }
+
+    fn var(&self, var: Var) -> i64 {
+        let cpu = self.cpu;
+        let p = cpu.flags.to_byte();
+        let flag = |bit: u8| (p >> bit & 1) as i64;
+
+        match var {
+            Var::A => cpu.reg_a as i64,
+            Var::X => cpu.reg_x as i64,
+            Var::Y => cpu.reg_y as i64,
+            Var::Sp => cpu.stack as i64,
+            Var::Pc => cpu.counter as i64,
+            Var::P => p as i64,
+            Var::Carry => flag(0),
+            Var::Zero => flag(1),
+            Var::Interrupt => flag(2),
+            Var::Decimal => flag(3),
+            Var::Overflow => flag(6),
+            Var::Negative => flag(7),
+            Var::Value => self.access.map_or(0, |access| access.value as i64),
+            Var::Addr => self.access.map_or(0, |access| access.addr as i64),
+            Var::Cycle => cpu.total_cycles as i64,
+            Var::Scanline => self.scanline as i64,
+            Var::Dot => self.dot as i64,
+            Var::Frame => self.frame as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Not,
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Num(i64),
+    Var(Var),
+    /// A byte of CPU memory.
+    Mem(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, ctx: &ExprContext) -> i64 {
+        match self {
+            Node::Num(num) => *num,
+            Node::Var(var) => ctx.var(*var),
+            Node::Mem(addr) => ctx.cpu.bus.peek(addr.eval(ctx) as u16) as i64,
+            Node::Unary(op, node) => {
+                let val = node.eval(ctx);
+                match op {
+                    UnaryOp::Not => (val == 0) as i64,
+                    UnaryOp::Neg => val.wrapping_neg(),
+                    UnaryOp::BitNot => !val,
+                }
+            }
+            Node::Binary(BinOp::Or, lhs, rhs) => (lhs.eval(ctx) != 0 || rhs.eval(ctx) != 0) as i64,
+            Node::Binary(BinOp::And, lhs, rhs) => (lhs.eval(ctx) != 0 && rhs.eval(ctx) != 0) as i64,
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(ctx), rhs.eval(ctx));
+                match op {
+                    BinOp::BitOr => lhs | rhs,
+                    BinOp::BitXor => lhs ^ rhs,
+                    BinOp::BitAnd => lhs & rhs,
+                    BinOp::Eq => (lhs == rhs) as i64,
+                    BinOp::Ne => (lhs != rhs) as i64,
+                    BinOp::Lt => (lhs < rhs) as i64,
+                    BinOp::Le => (lhs <= rhs) as i64,
+                    BinOp::Gt => (lhs > rhs) as i64,
+                    BinOp::Ge => (lhs >= rhs) as i64,
+                    BinOp::Add => lhs.wrapping_add(rhs),
+                    BinOp::Sub => lhs.wrapping_sub(rhs),
+                    BinOp::Or | BinOp::And => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    /// Byte offset into the condition where parsing gave up.
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "condition error at column {}: {}", self.position + 1, self.reason)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl From<ExprError> for JsValue {
+    fn from(err: ExprError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// A breakpoint condition like `A == $10 && X > 3` or `[$0300] != 0`.
+///
+/// Numbers are decimal, `$` or `0x` hex, or `%` binary. Registers and flags go by
+/// their usual letters, `value` and `addr` are the access a watchpoint caught,
+/// and `[expr]` reads a byte of CPU memory. Anything non-zero is true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    root: Node,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { src: source.as_bytes(), pos: 0 };
+        let root = parser.expr(0)?;
+
+        parser.skip_space();
+        if parser.pos < parser.src.len() {
+            return Err(parser.error("unexpected input"));
+        }
+
+        Ok(Self { source: source.to_string(), root })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn eval(&self, ctx: &ExprContext) -> i64 {
+        self.root.eval(ctx)
+    }
+
+    pub fn is_true(&self, ctx: &ExprContext) -> bool {
+        self.eval(ctx) != 0
+    }
+}
+
+/// Binary operators from loosest to tightest binding.
+const LEVELS: &[&[(&str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("|", BinOp::BitOr)],
+    &[("^", BinOp::BitXor)],
+    &[("&", BinOp::BitAnd)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[("<=", BinOp::Le), (">=", BinOp::Ge), ("<", BinOp::Lt), (">", BinOp::Gt)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+];
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> ExprError {
+        ExprError { position: self.pos, reason: reason.to_string() }
+    }
+
+    fn skip_space(&mut self) {
+        while self.src.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+
+        let matches = self.src[self.pos..].starts_with(token.as_bytes());
+        // `|` and `&` mustn't swallow the first half of `||` and `&&`
+        let doubled = token.len() == 1 && matches!(token, "|" | "&") && self.src.get(self.pos + 1) == Some(&token.as_bytes()[0]);
+
+        if matches && !doubled {
+            self.pos += token.len();
+        }
+
+        matches && !doubled
+    }
+
+    fn expr(&mut self, level: usize) -> Result<Node, ExprError> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+
+        let mut lhs = self.expr(level + 1)?;
+
+        'outer: loop {
+            for &(token, op) in ops.iter() {
+                if self.eat(token) {
+                    let rhs = self.expr(level + 1)?;
+                    lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        let op = if self.eat("!") {
+            UnaryOp::Not
+        } else if self.eat("-") {
+            UnaryOp::Neg
+        } else if self.eat("~") {
+            UnaryOp::BitNot
+        } else {
+            return self.primary();
+        };
+
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, ExprError> {
+        if self.eat("(") {
+            let node = self.expr(0)?;
+            return if self.eat(")") { Ok(node) } else { Err(self.error("expected `)`")) };
+        }
+
+        if self.eat("[") {
+            let node = self.expr(0)?;
+            return if self.eat("]") { Ok(Node::Mem(Box::new(node))) } else { Err(self.error("expected `]`")) };
+        }
+
+        self.skip_space();
+        let start = self.pos;
+
+        let radix = if self.eat("$") || self.eat("0x") {
+            16
+        } else if self.eat("%") {
+            2
+        } else {
+            10
+        };
+
+        let word_start = self.pos;
+        while self.src.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_') {
+            self.pos += 1;
+        }
+
+        let word = std::str::from_utf8(&self.src[word_start..self.pos]).unwrap();
+        if word.is_empty() {
+            self.pos = start;
+            return Err(self.error("expected a number, register or `(`"));
+        }
+
+        if radix != 10 || word.as_bytes()[0].is_ascii_digit() {
+            return i64::from_str_radix(word, radix).map(Node::Num).map_err(|_| ExprError {
+                position: start,
+                reason: format!("invalid number `{}`", std::str::from_utf8(&self.src[start..self.pos]).unwrap()),
+            });
+        }
+
+        Var::parse(word).map(Node::Var).ok_or(ExprError { position: word_start, reason: format!("unknown name `{word}`") })
+    }
+}
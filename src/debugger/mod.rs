@@ -0,0 +1,331 @@
+mod call_stack;
+mod expr;
+
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+pub use call_stack::{CallFrame, CallStack};
+pub use expr::{Condition, ExprContext, ExprError};
+
+use crate::cpu::Cpu;
+
+const BRK: u8 = 0x00;
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+/// What a breakpoint stops on.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakKind {
+    /// The CPU is about to run an instruction in the range.
+    Execute,
+    CpuRead,
+    CpuWrite,
+    /// The PPU bus, so CHR, nametables and palettes, whether through `$2007` or rendering.
+    PpuRead,
+    PpuWrite,
+}
+
+impl BreakKind {
+    pub fn is_ppu(self) -> bool {
+        matches!(self, BreakKind::PpuRead | BreakKind::PpuWrite)
+    }
+}
+
+/// One read or write seen on a bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub addr: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: u32,
+    pub kind: BreakKind,
+    /// First address covered. Watch a single register like `$2007` with `start == end`.
+    pub start: u16,
+    pub end: u16,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    fn covers(&self, kind: BreakKind, addr: u16) -> bool {
+        self.enabled && self.kind == kind && (self.start..=self.end).contains(&addr)
+    }
+
+    fn fires(&self, ctx: &ExprContext) -> bool {
+        self.condition.as_ref().is_none_or(|condition| condition.is_true(ctx))
+    }
+}
+
+/// Why execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// Someone asked for it.
+    Pause,
+    /// A step finished.
+    Step,
+    /// An execute breakpoint, before the instruction at `pc` ran.
+    Breakpoint { id: u32, pc: u16 },
+    /// A watchpoint, after the instruction making the access.
+    Watchpoint { id: u32, kind: BreakKind, access: Access },
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakReason::Pause => write!(f, "paused"),
+            BreakReason::Step => write!(f, "step"),
+            BreakReason::Breakpoint { id, pc } => write!(f, "breakpoint {id} at ${pc:04X}"),
+            BreakReason::Watchpoint { id, kind, access } => {
+                let bus = if kind.is_ppu() { "PPU" } else { "CPU" };
+                let verb = if access.write { "write" } else { "read" };
+                write!(f, "watchpoint {id}: {bus} {verb} ${:04X} = ${:02X}", access.addr, access.value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Stop once the `JSR` at hand returns to `pc` with the stack back at `sp`.
+    Over { pc: u16, sp: u8 },
+    /// Stop after the `RTS` or `RTI` that leaves the routine entered with the stack at `sp`.
+    Out { sp: u8 },
+    /// Stop before running the instruction at the cursor.
+    RunTo(u16),
+    /// Stop once execution leaves `start..=end`, only back at or above `sp` when it's given.
+    Leave { start: u16, end: u16, sp: Option<u8> },
+}
+
+/// Breakpoints, watchpoints and stepping, checked by `Nes` around every instruction.
+///
+/// Execute breakpoints stop before their instruction runs. Watchpoints see bus
+/// accesses as they happen but stop only once the instruction making them is
+/// done, so the CPU is always between instructions while paused.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    next_id: u32,
+    paused: Option<BreakReason>,
+    /// The instruction execution resumed from, whose breakpoints were already reported.
+    skip_pc: Option<u16>,
+    step: Option<Step>,
+    /// The instruction running now finishes a step out.
+    stop_after: bool,
+    call_stack: CallStack,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            next_id: 1,
+            paused: None,
+            skip_pc: None,
+            step: None,
+            stop_after: false,
+            call_stack: CallStack::new(),
+        }
+    }
+
+    /// Adds a breakpoint over `start..=end`, stopping only when `condition` holds if there is one.
+    pub fn add(&mut self, kind: BreakKind, start: u16, end: u16, condition: Option<&str>) -> Result<u32, ExprError> {
+        let condition = condition.map(Condition::parse).transpose()?;
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.breakpoints.push(Breakpoint { id, kind, start: start.min(end), end: start.max(end), condition, enabled: true });
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.breakpoints.len() != len
+    }
+
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.breakpoints.iter_mut().find(|breakpoint| breakpoint.id == id) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Whether any enabled breakpoint of `kind` exists, so accesses of that kind need logging.
+    pub fn watches(&self, kind: BreakKind) -> bool {
+        self.breakpoints.iter().any(|breakpoint| breakpoint.enabled && breakpoint.kind == kind)
+    }
+
+    /// Whether instructions need checking before they run.
+    pub fn is_armed(&self) -> bool {
+        self.step.is_some() || self.watches(BreakKind::Execute)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    pub fn reason(&self) -> Option<BreakReason> {
+        self.paused
+    }
+
+    /// Stops execution, cancelling any step in progress.
+    pub fn pause(&mut self, reason: BreakReason) {
+        self.paused = Some(reason);
+        self.step = None;
+        self.stop_after = false;
+    }
+
+    /// Lets execution go on from `pc` without stopping again on its own breakpoints.
+    pub fn resume(&mut self, pc: u16) {
+        self.paused = None;
+        self.skip_pc = Some(pc);
+    }
+
+    /// Runs until the `JSR` at `pc` returns, returning false for any other `opcode`,
+    /// which is stepped over by stepping into it. Only sets the step up, resuming is
+    /// up to the caller.
+    pub fn step_over(&mut self, pc: u16, sp: u8, opcode: u8) -> bool {
+        if opcode != JSR {
+            return false;
+        }
+
+        self.step = Some(Step::Over { pc: pc.wrapping_add(3), sp });
+        true
+    }
+
+    /// Runs until the routine entered with the stack at `sp` returns.
+    pub fn step_out(&mut self, sp: u8) {
+        self.step = Some(Step::Out { sp });
+    }
+
+    pub fn run_to(&mut self, pc: u16) {
+        self.step = Some(Step::RunTo(pc));
+    }
+
+    /// Runs until execution leaves the addresses `start..=end`, such as the bytes
+    /// of a source line. With `sp`, calls made with the stack below it run
+    /// through instead of stopping inside them.
+    pub fn step_leave(&mut self, start: u16, end: u16, sp: Option<u8>) {
+        self.step = Some(Step::Leave { start, end, sp });
+    }
+
+    /// Checks the instruction `ctx.cpu` is about to run, `opcode`, pausing if it should stop there.
+    pub fn before_instruction(&mut self, ctx: &ExprContext, opcode: u8) -> bool {
+        let pc = ctx.cpu.counter;
+        let sp = ctx.cpu.stack;
+        let resumed_here = self.skip_pc.take() == Some(pc);
+
+        // a pushed byte still on the stack leaves it lower than when the routine returns
+        if let Some(Step::Out { sp: entry_sp }) = self.step
+            && matches!(opcode, RTS | RTI)
+            && sp >= entry_sp
+        {
+            self.stop_after = true;
+        }
+
+        if resumed_here {
+            return false;
+        }
+
+        let step_done = match self.step {
+            // a recursive call returns to the same place with less on the stack
+            Some(Step::Over { pc: target, sp: entry_sp }) => pc == target && sp >= entry_sp,
+            Some(Step::RunTo(target)) => pc == target,
+            Some(Step::Leave { start, end, sp: entry_sp }) => {
+                !(start..=end).contains(&pc) && entry_sp.is_none_or(|entry_sp| sp >= entry_sp)
+            }
+            _ => false,
+        };
+
+        if step_done {
+            self.pause(BreakReason::Step);
+            return true;
+        }
+
+        let hit = self.breakpoints.iter().find(|breakpoint| breakpoint.covers(BreakKind::Execute, pc) && breakpoint.fires(ctx));
+
+        if let Some(breakpoint) = hit {
+            self.pause(BreakReason::Breakpoint { id: breakpoint.id, pc });
+            return true;
+        }
+
+        false
+    }
+
+    /// Pauses if the instruction that just ran finished a step.
+    pub fn after_instruction(&mut self) -> bool {
+        if self.stop_after {
+            self.pause(BreakReason::Step);
+            return true;
+        }
+
+        false
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+
+    /// Forgets every call, for when the CPU starts over somewhere else.
+    pub fn clear_call_stack(&mut self) {
+        self.call_stack.clear();
+    }
+
+    /// Follows calls and returns once `cpu` has run the instruction that was at
+    /// `pc` with the stack at `sp`, or an interrupt if `interrupted`.
+    pub fn track_calls(&mut self, pc: u16, sp: u8, interrupted: bool, cpu: &Cpu) {
+        let frame = |interrupt| CallFrame { caller: pc, target: cpu.counter, sp, interrupt };
+
+        if interrupted {
+            self.call_stack.call(frame(true));
+            return;
+        }
+
+        match cpu.last_opcode() {
+            JSR => self.call_stack.call(frame(false)),
+            BRK => self.call_stack.call(frame(true)),
+            RTS | RTI => self.call_stack.ret(cpu.stack),
+            _ => {}
+        }
+    }
+
+    /// Checks one access the last instruction made, with `ctx.access` holding it.
+    pub fn check_access(&mut self, kind: BreakKind, ctx: &ExprContext) -> bool {
+        let Some(access) = ctx.access else {
+            return false;
+        };
+
+        let hit = self.breakpoints.iter().find(|breakpoint| breakpoint.covers(kind, access.addr) && breakpoint.fires(ctx));
+
+        if let Some(breakpoint) = hit {
+            self.pause(BreakReason::Watchpoint { id: breakpoint.id, kind, access });
+            return true;
+        }
+
+        false
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
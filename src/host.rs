@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A single row of the tracelog, pre-formatted the way the frontend displays it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tracelog {
+    pub pg: String,
+    pub by: String,
+    pub inst: String,
+    pub reg_a: String,
+    pub reg_x: String,
+    pub reg_y: String,
+    pub sp: String,
+    pub p: String,
+    pub f: String,
+    pub cy: String,
+}
+
+/// Everything the emulator core needs from whatever is hosting it.
+///
+/// `Nes` owns one of these and hands it down to the CPU, PPU and memories, so
+/// the core never talks to the browser directly.
+pub trait Host {
+    fn console_log(&self, msg: &str);
+    /// Whether `add_tracelog` is worth calling. The CPU skips building rows for hosts that say no.
+    fn wants_tracelog(&self) -> bool;
+    fn add_tracelog(&self, row: &Tracelog);
+    fn update_ram(&self, ram: &[u8]);
+    fn update_prg_rom(&self, rom: &[u8]);
+    fn update_vram(&self, ram: &[u8]);
+    fn update_chr_rom(&self, rom: &[u8]);
+    fn force_screen_draw(&self);
+}
+
+pub type HostRef = Rc<dyn Host>;
+
+/// Host used when nothing is listening, e.g. native tools and tests.
+#[derive(Default)]
+pub struct NullHost;
+
+impl Host for NullHost {
+    fn console_log(&self, _msg: &str) {}
+    fn wants_tracelog(&self) -> bool {
+        false
+    }
+    fn add_tracelog(&self, _row: &Tracelog) {}
+    fn update_ram(&self, _ram: &[u8]) {}
+    fn update_prg_rom(&self, _rom: &[u8]) {}
+    fn update_vram(&self, _ram: &[u8]) {}
+    fn update_chr_rom(&self, _rom: &[u8]) {}
+    fn force_screen_draw(&self) {}
+}
+
+/// Host that keeps everything it is told, so native callers can inspect it afterwards.
+#[derive(Default)]
+pub struct RecordingHost {
+    pub logs: RefCell<Vec<String>>,
+    pub tracelog: RefCell<Vec<Tracelog>>,
+    pub ram: RefCell<Vec<u8>>,
+    pub prg_rom: RefCell<Vec<u8>>,
+    pub vram: RefCell<Vec<u8>>,
+    pub chr_rom: RefCell<Vec<u8>>,
+    pub screen_draws: RefCell<usize>,
+}
+
+impl Host for RecordingHost {
+    fn console_log(&self, msg: &str) {
+        self.logs.borrow_mut().push(msg.to_string());
+    }
+
+    fn wants_tracelog(&self) -> bool {
+        true
+    }
+
+    fn add_tracelog(&self, row: &Tracelog) {
+        self.tracelog.borrow_mut().push(row.clone());
+    }
+
+    fn update_ram(&self, ram: &[u8]) {
+        *self.ram.borrow_mut() = ram.to_vec();
+    }
+
+    fn update_prg_rom(&self, rom: &[u8]) {
+        *self.prg_rom.borrow_mut() = rom.to_vec();
+    }
+
+    fn update_vram(&self, ram: &[u8]) {
+        *self.vram.borrow_mut() = ram.to_vec();
+    }
+
+    fn update_chr_rom(&self, rom: &[u8]) {
+        *self.chr_rom.borrow_mut() = rom.to_vec();
+    }
+
+    fn force_screen_draw(&self) {
+        *self.screen_draws.borrow_mut() += 1;
+    }
+}
+
+/// The host `Nes::new` uses: the JS worker in the browser, nothing natively.
+pub fn default_host() -> HostRef {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Rc::new(crate::js::WasmHost)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Rc::new(NullHost)
+    }
+}
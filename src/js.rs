@@ -1,12 +1,44 @@
 use wasm_bindgen::prelude::*;
 
+use crate::host::{Host, Tracelog};
+
 #[wasm_bindgen(module = "/src/lib_worker.js")]
 unsafe extern "C" {
     pub fn consoleLog(msg: &str);
-    pub fn addTracelog(pg: &str, by: &str, inst: &str, regA: &str, regX: &str, regY: &str, sp: &str, p: &str, f: &str, cy: &str);
-    pub fn updateRam(ram: &[u8]);
     pub fn updatePRom(ram: &[u8]);
-    pub fn updateVRam(ram: &[u8]);
     pub fn updateCRom(ram: &[u8]);
     pub fn forceScreenDraw();
 }
+
+/// Forwards everything to the worker through the `lib_worker.js` externs.
+pub struct WasmHost;
+
+impl Host for WasmHost {
+    fn console_log(&self, msg: &str) {
+        consoleLog(msg);
+    }
+
+    // the worker pulls trace lines out of the tracer in bulk instead
+    fn wants_tracelog(&self) -> bool {
+        false
+    }
+
+    fn add_tracelog(&self, _row: &Tracelog) {}
+
+    // RAM and VRAM change every frame, so the worker reads them through memory domains once a frame instead
+    fn update_ram(&self, _ram: &[u8]) {}
+
+    fn update_prg_rom(&self, rom: &[u8]) {
+        updatePRom(rom);
+    }
+
+    fn update_vram(&self, _ram: &[u8]) {}
+
+    fn update_chr_rom(&self, rom: &[u8]) {
+        updateCRom(rom);
+    }
+
+    fn force_screen_draw(&self) {
+        forceScreenDraw();
+    }
+}
@@ -2,9 +2,40 @@ mod cpu;
 mod rom;
 mod mapper;
 mod card;
+mod controller;
 mod nes;
+#[cfg(target_arch = "wasm32")]
 mod js;
+mod host;
 mod ppu;
+mod trace;
+mod tracer;
+mod profiler;
+mod ram_search;
+mod debugger;
+mod memory;
+mod cdl;
+mod cheats;
+mod symbols;
+mod source_map;
+mod state;
+mod rewind;
+mod movie;
 
 pub use nes::Nes;
-
+pub use cpu::{decode, disassemble, AddrMode, Cpu, CpuFlags, Instruction, InstructionBytes, IrqLine, IrqSource, Opcode, Operand, Ram, OPCODES};
+pub use ppu::Ppu;
+pub use rom::RomError;
+pub use host::{Host, HostRef, NullHost, RecordingHost, Tracelog};
+pub use trace::{compare_log, parse_log_line, Divergence, FieldDiff, OperandPeek, TraceEntry};
+pub use tracer::{TraceTrigger, Tracer};
+pub use profiler::{Profile, Profiler, RoutineStats};
+pub use debugger::{Access, BreakKind, BreakReason, Breakpoint, CallFrame, CallStack, Condition, Debugger, ExprContext, ExprError};
+pub use memory::MemoryDomain;
+pub use ram_search::{RamSearch, SearchCompare, SearchFormat, SearchResult, SearchWidth};
+pub use cdl::{CdlError, CdlSummary, CodeDataLog};
+pub use cheats::{Cheat, CheatError, CheatKind, Cheats};
+pub use symbols::{SymbolError, SymbolFormat, Symbols};
+pub use source_map::{SourceLine, SourceMap};
+pub use state::{StateError, STATE_MAGIC, STATE_VERSION};
+pub use movie::{Desync, Movie, MovieError, MovieFrame, MovieMode};
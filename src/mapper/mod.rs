@@ -2,12 +2,48 @@ mod nrom;
 pub use nrom::NROM;
 
 use crate::rom::Rom;
+use crate::state::StateError;
 
 pub trait Mapper {
     fn cpu_read(&mut self, addr: u16) -> Option<u8>;
     fn cpu_write(&mut self, addr: u16, val: u8);
+    /// Reads the expansion area, `$4020-$5FFF`. `addr` is the CPU address, since the area
+    /// doesn't start on a boundary. `None` where nothing on the board answers.
+    fn expansion_read(&mut self, addr: u16) -> Option<u8>;
+    fn expansion_write(&mut self, addr: u16, val: u8);
+    /// Reads cartridge RAM at `$6000-$7FFF`, `addr` being relative to `$6000`. Reading RAM
+    /// has no side effects, so debuggers use this too.
+    fn prg_ram_read(&self, addr: u16) -> Option<u8>;
+    fn prg_ram_write(&mut self, addr: u16, val: u8);
     fn ppu_read(&mut self, addr: u16) -> Option<u8>;
     fn ppu_write(&mut self, addr: u16, val: u8);
+    /// `cpu_read` for debuggers, which must not disturb anything a read would.
+    fn cpu_peek(&self, addr: u16) -> Option<u8>;
+    /// Patches whatever memory is mapped at `addr` for the CPU, ROM included, without touching registers.
+    fn cpu_poke(&mut self, addr: u16, val: u8);
+    /// `ppu_read` for debuggers, which must not disturb anything a read would.
+    fn ppu_peek(&self, addr: u16) -> Option<u8>;
+    /// Patches whatever CHR is mapped at `addr` for the PPU, ROM included.
+    fn ppu_poke(&mut self, addr: u16, val: u8);
+    /// The whole of PRG ROM, regardless of banking.
+    fn prg_rom(&self) -> &[u8];
+    fn prg_rom_mut(&mut self) -> &mut [u8];
+    /// The whole of CHR ROM, regardless of banking. Empty for boards with CHR RAM instead.
+    fn chr_rom(&self) -> &[u8];
+    fn chr_rom_mut(&mut self) -> &mut [u8];
+    /// Work or battery RAM on the cartridge, if the board has any.
+    fn prg_ram(&self) -> Option<&[u8]>;
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]>;
     fn swap_prg_rom(&mut self, rom: Rom);
     fn swap_chr_rom(&mut self, rom: Rom);
+    /// The 16 KiB PRG ROM bank mapped at `addr`, which is relative to `$8000` like `cpu_read`.
+    fn prg_bank(&self, addr: u16) -> Option<u16>;
+    /// The 1 KiB CHR ROM bank mapped at PPU address `addr`, `None` where CHR RAM is.
+    fn chr_bank(&self, addr: u16) -> Option<u16>;
+    /// Whether the mapper is holding the CPU's IRQ line.
+    fn irq(&self) -> bool;
+    /// Bank registers and any other state the mapper keeps besides its ROMs.
+    fn save_state(&self) -> Vec<u8>;
+    /// Restores what `save_state` produced, refusing data of any other length.
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError>;
 }
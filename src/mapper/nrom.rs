@@ -1,36 +1,109 @@
+use crate::state::{StateError, CHUNK_MAPPER};
 use crate::{mapper::Mapper, rom::Rom};
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct NROM {
     pub prg_rom: Rom,
     pub chr_rom: Rom,
+    /// Empty unless the header asks for RAM, as Family Basic's does.
+    pub prg_ram: Vec<u8>,
 }
 
 impl NROM {
-    pub fn new(prg_rom: Rom, chr_rom: Rom) -> Self {
+    pub fn new(prg_rom: Rom, chr_rom: Rom, prg_ram_size: usize) -> Self {
         Self {
             prg_rom,
-            chr_rom
+            chr_rom,
+            prg_ram: vec![0; prg_ram_size],
         }
     }
 }
 
 impl Mapper for NROM {
     fn cpu_read(&mut self, addr: u16) -> Option<u8> {
-        return self.prg_rom.read(addr);
+        // NROM-128 only has 16 KiB of PRG, which is mirrored into $C000-$FFFF
+        let len = self.prg_rom.contents.len().max(1);
+        self.prg_rom.read((addr as usize % len) as u16)
     }
 
     fn cpu_write(&mut self, _addr: u16, _val: u8) {
         // do nothing since the NROM is basic and we cant write to ROM
     }
 
+    fn expansion_read(&mut self, _addr: u16) -> Option<u8> {
+        // nothing on an NROM board answers down there
+        None
+    }
+
+    fn expansion_write(&mut self, _addr: u16, _val: u8) {}
+
+    fn prg_ram_read(&self, addr: u16) -> Option<u8> {
+        // RAM smaller than 8 KiB is mirrored through the window
+        let len = self.prg_ram.len().max(1);
+        self.prg_ram.get(addr as usize % len).copied()
+    }
+
+    fn prg_ram_write(&mut self, addr: u16, val: u8) {
+        let len = self.prg_ram.len().max(1);
+        if let Some(byte) = self.prg_ram.get_mut(addr as usize % len) {
+            *byte = val;
+        }
+    }
+
     fn ppu_read(&mut self, addr: u16) -> Option<u8> {
-        return self.chr_rom.read(addr);
+        self.chr_rom.read(addr)
     }
 
     fn ppu_write(&mut self, _addr: u16, _val: u8) {
         // do nothing since the NROM is basic and we cant write to ROM
     }
 
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        let len = self.prg_rom.contents.len().max(1);
+        self.prg_rom.read((addr as usize % len) as u16)
+    }
+
+    fn cpu_poke(&mut self, addr: u16, val: u8) {
+        let len = self.prg_rom.contents.len().max(1);
+        if let Some(byte) = self.prg_rom.contents.get_mut(addr as usize % len) {
+            *byte = val;
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16) -> Option<u8> {
+        self.chr_rom.read(addr)
+    }
+
+    fn ppu_poke(&mut self, addr: u16, val: u8) {
+        if let Some(byte) = self.chr_rom.contents.get_mut(addr as usize) {
+            *byte = val;
+        }
+    }
+
+    fn prg_rom(&self) -> &[u8] {
+        &self.prg_rom.contents
+    }
+
+    fn prg_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.prg_rom.contents
+    }
+
+    fn chr_rom(&self) -> &[u8] {
+        &self.chr_rom.contents
+    }
+
+    fn chr_rom_mut(&mut self) -> &mut [u8] {
+        &mut self.chr_rom.contents
+    }
+
+    fn prg_ram(&self) -> Option<&[u8]> {
+        (!self.prg_ram.is_empty()).then_some(&self.prg_ram[..])
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut [u8]> {
+        (!self.prg_ram.is_empty()).then_some(&mut self.prg_ram[..])
+    }
+
     fn swap_prg_rom(&mut self, rom: Rom) {
         self.prg_rom = rom;
     }
@@ -38,5 +111,34 @@ impl Mapper for NROM {
     fn swap_chr_rom(&mut self, rom: Rom) {
         self.chr_rom = rom
     }
+
+    fn prg_bank(&self, addr: u16) -> Option<u16> {
+        let len = self.prg_rom.contents.len().max(1);
+        Some(((addr as usize % len) / 0x4000) as u16)
+    }
+
+    fn chr_bank(&self, addr: u16) -> Option<u16> {
+        let len = self.chr_rom.contents.len();
+        (len > 0).then(|| ((addr as usize % len) / 0x400) as u16)
+    }
+
+    fn irq(&self) -> bool {
+        // NROM has no IRQ counter
+        false
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        // NROM has no bank registers, so there is nothing besides the ROMs but any RAM
+        self.prg_ram.clone()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() != self.prg_ram.len() {
+            return Err(StateError::BadChunkLength { tag: CHUNK_MAPPER, expected: self.prg_ram.len(), actual: data.len() });
+        }
+
+        self.prg_ram.copy_from_slice(data);
+        Ok(())
+    }
 }
 
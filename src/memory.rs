@@ -0,0 +1,146 @@
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::Bus;
+
+/// A named view of some memory for debuggers, cheats and RAM search.
+///
+/// Peeking never has side effects: reading `$2002` through the CPU bus leaves
+/// vblank set, `$2007` leaves the VRAM address where it was, and the debugger's
+/// watchpoints don't see any of it. Poking writes memory directly, so ROM can be
+/// patched and registers are left alone.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryDomain {
+    /// Everything the CPU addresses, `$0000-$FFFF`.
+    CpuBus,
+    /// The 2 KiB of work RAM.
+    SystemRam,
+    /// Everything the PPU addresses, `$0000-$3FFF`.
+    PpuBus,
+    /// The 2 KiB of nametable RAM in the console, before mirroring.
+    Nametables,
+    /// The 32 palette entries, before mirroring.
+    Palette,
+    /// Sprite memory.
+    Oam,
+    PrgRom,
+    /// CHR ROM, or CHR RAM on boards that have it instead.
+    Chr,
+    /// Work or battery RAM on the cartridge, empty on boards without any.
+    PrgRam,
+}
+
+impl MemoryDomain {
+    pub const ALL: [MemoryDomain; 9] = [
+        MemoryDomain::CpuBus,
+        MemoryDomain::SystemRam,
+        MemoryDomain::PpuBus,
+        MemoryDomain::Nametables,
+        MemoryDomain::Palette,
+        MemoryDomain::Oam,
+        MemoryDomain::PrgRom,
+        MemoryDomain::Chr,
+        MemoryDomain::PrgRam,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MemoryDomain::CpuBus => "CPU Bus",
+            MemoryDomain::SystemRam => "System RAM",
+            MemoryDomain::PpuBus => "PPU Bus",
+            MemoryDomain::Nametables => "Nametables",
+            MemoryDomain::Palette => "Palette RAM",
+            MemoryDomain::Oam => "OAM",
+            MemoryDomain::PrgRom => "PRG ROM",
+            MemoryDomain::Chr => "CHR",
+            MemoryDomain::PrgRam => "PRG RAM",
+        }
+    }
+
+    /// How many bytes the domain holds, addressed from 0.
+    pub fn size(self, bus: &Bus) -> usize {
+        match self {
+            MemoryDomain::CpuBus => 0x10000,
+            MemoryDomain::PpuBus => 0x4000,
+            _ => self.with_contents(bus, <[u8]>::len).unwrap_or_default(),
+        }
+    }
+
+    /// The byte at `addr`, or `None` past the end of the domain.
+    pub fn peek(self, bus: &Bus, addr: usize) -> Option<u8> {
+        match self {
+            MemoryDomain::CpuBus => (addr < 0x10000).then(|| bus.peek(addr as u16)),
+            MemoryDomain::PpuBus => (addr < 0x4000).then(|| bus.ppu.borrow().vbus.peek(addr as u16)),
+            _ => self.with_contents(bus, |contents| contents.get(addr).copied()).flatten(),
+        }
+    }
+
+    /// Up to `len` bytes from `addr`, stopping at the end of the domain.
+    pub fn peek_range(self, bus: &Bus, addr: usize, len: usize) -> Vec<u8> {
+        let end = addr.saturating_add(len).min(self.size(bus));
+
+        match self {
+            MemoryDomain::CpuBus => (addr..end).map(|addr| bus.peek(addr as u16)).collect(),
+            MemoryDomain::PpuBus => {
+                let ppu = bus.ppu.borrow();
+                (addr..end).map(|addr| ppu.vbus.peek(addr as u16)).collect()
+            }
+            _ => self
+                .with_contents(bus, |contents| contents.get(addr..end).unwrap_or_default().to_vec())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Calls `f` with the memory backing the domain, `None` for the buses, which
+    /// have no memory of their own.
+    fn with_contents<R>(self, bus: &Bus, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let ppu = bus.ppu.borrow();
+        let card = bus.card.borrow();
+
+        Some(f(match self {
+            MemoryDomain::CpuBus | MemoryDomain::PpuBus => return None,
+            MemoryDomain::SystemRam => bus.ram.contents(),
+            MemoryDomain::Nametables => ppu.vbus.vram.contents(),
+            MemoryDomain::Palette => &ppu.vbus.palette_ram,
+            MemoryDomain::Oam => &ppu.oam,
+            MemoryDomain::PrgRom => card.mapper.prg_rom(),
+            MemoryDomain::Chr => match ppu.vbus.chr_ram.as_ref() {
+                Some(chr_ram) => chr_ram,
+                None => card.mapper.chr_rom(),
+            },
+            MemoryDomain::PrgRam => card.mapper.prg_ram().unwrap_or_default(),
+        }))
+    }
+
+    /// Writes `byte` at `addr`, returning false past the end of the domain.
+    pub fn poke(self, bus: &mut Bus, addr: usize, byte: u8) -> bool {
+        if addr >= self.size(bus) {
+            return false;
+        }
+
+        let ppu = bus.ppu.clone();
+        let mut ppu = ppu.borrow_mut();
+
+        match self {
+            MemoryDomain::CpuBus => bus.poke(addr as u16, byte),
+            MemoryDomain::SystemRam => bus.ram.write(addr as u16, byte),
+            MemoryDomain::PpuBus => ppu.vbus.poke(addr as u16, byte),
+            MemoryDomain::Nametables => ppu.vbus.vram.write(addr as u16, byte),
+            // only six bits of each entry exist
+            MemoryDomain::Palette => ppu.vbus.palette_ram[addr] = byte & 0x3F,
+            MemoryDomain::Oam => ppu.oam[addr] = byte,
+            MemoryDomain::PrgRom => bus.card.borrow_mut().mapper.prg_rom_mut()[addr] = byte,
+            MemoryDomain::Chr => match ppu.vbus.chr_ram.as_mut() {
+                Some(chr_ram) => chr_ram[addr] = byte,
+                None => bus.card.borrow_mut().mapper.chr_rom_mut()[addr] = byte,
+            },
+            MemoryDomain::PrgRam => {
+                if let Some(ram) = bus.card.borrow_mut().mapper.prg_ram_mut() {
+                    ram[addr] = byte;
+                }
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+use crate::state::StateError;
+
+/// FM2 command bits that can accompany a frame's input.
+pub const COMMAND_SOFT_RESET: u8 = 0x01;
+pub const COMMAND_HARD_RESET: u8 = 0x02;
+
+/// Button letters in FM2 column order, which is bit 7 down to bit 0 of `Controller::buttons`.
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub commands: u8,
+    pub buttons: [u8; 2],
+}
+
+/// Controller input for every frame since power-on or since a save-state anchor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_filename: String,
+    pub rerecords: u32,
+    /// Save state the movie starts from, or `None` to start from power-on.
+    pub anchor: Option<Vec<u8>>,
+    pub frames: Vec<MovieFrame>,
+    /// Hash of the machine state at the start of each frame, once its input is latched.
+    pub hashes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    Recording,
+    Playing { read_only: bool },
+    Finished,
+}
+
+/// A frame where playback no longer matched what was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Desync {
+    pub frame: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "movie desynced at frame {}: state hash {:016X}, recorded {:016X}", self.frame, self.actual, self.expected)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    NotActive,
+    MissingHeader(&'static str),
+    BadLine { line: usize, reason: String },
+    BadAnchor(StateError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::NotActive => write!(f, "no movie is being recorded or played"),
+            MovieError::MissingHeader(key) => write!(f, "FM2 movie has no {key} line"),
+            MovieError::BadLine { line, reason } => write!(f, "FM2 line {line}: {reason}"),
+            MovieError::BadAnchor(err) => write!(f, "movie save-state anchor can't be loaded: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<MovieError> for JsValue {
+    fn from(err: MovieError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+impl Movie {
+    /// Reads an FCEUX `.fm2` text movie.
+    ///
+    /// Frame hashes and save-state anchors only exist in movies nest recorded itself, in
+    /// its `stateHashes` and `nestSavestate` headers, so movies from other emulators play
+    /// back unchecked. FCEUX's own `savestate` anchors are refused, nest can't load them.
+    pub fn from_fm2(text: &str) -> Result<Self, MovieError> {
+        let mut movie = Movie::default();
+        let mut version = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let bad = |reason: &str| MovieError::BadLine { line: line_no, reason: reason.to_string() };
+
+            if line.starts_with('|') {
+                movie.frames.push(parse_input_line(line).ok_or_else(|| bad("malformed input line"))?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line.trim(), ""));
+            match key {
+                "version" => version = true,
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "rerecordCount" => movie.rerecords = value.trim().parse().map_err(|_| bad("invalid rerecordCount"))?,
+                "savestate" => return Err(bad("FCEUX save states can't be loaded, only movies from power-on")),
+                "nestSavestate" => {
+                    let data = value.trim().strip_prefix("base64:").ok_or_else(|| bad("nestSavestate is not base64"))?;
+                    movie.anchor = Some(base64_decode(data).ok_or_else(|| bad("invalid base64 in nestSavestate"))?);
+                }
+                "stateHashes" => {
+                    movie.hashes = value
+                        .split_whitespace()
+                        .map(|hash| u64::from_str_radix(hash, 16))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| bad("invalid stateHashes"))?;
+                }
+                _ => {}
+            }
+        }
+
+        if !version {
+            return Err(MovieError::MissingHeader("version"));
+        }
+
+        Ok(movie)
+    }
+
+    /// Writes the movie as FCEUX `.fm2` text for the ROM with MD5 `rom_md5`.
+    ///
+    /// A save-state anchor goes in a `nestSavestate` header FCEUX doesn't know, so FCEUX
+    /// plays such a movie from power-on.
+    pub fn to_fm2(&self, rom_md5: [u8; 16]) -> String {
+        let mut out = String::new();
+
+        out.push_str("version 3\n");
+        out.push_str("emuVersion 0\n");
+        out.push_str(&format!("rerecordCount {}\n", self.rerecords));
+        out.push_str("palFlag 0\n");
+        out.push_str(&format!("romFilename {}\n", self.rom_filename));
+        out.push_str(&format!("romChecksum base64:{}\n", base64_encode(&rom_md5)));
+        out.push_str("guid 00000000-0000-0000-0000-000000000000\n");
+        out.push_str("fourscore 0\nmicrophone 0\nport0 1\nport1 1\nport2 0\nFDS 0\nNewPPU 0\n");
+
+        if let Some(anchor) = &self.anchor {
+            out.push_str(&format!("nestSavestate base64:{}\n", base64_encode(anchor)));
+        }
+
+        if !self.hashes.is_empty() {
+            let hashes: Vec<String> = self.hashes.iter().map(|hash| format!("{hash:016X}")).collect();
+            out.push_str(&format!("stateHashes {}\n", hashes.join(" ")));
+        }
+
+        for frame in &self.frames {
+            out.push_str(&format!("|{}|{}|{}||\n", frame.commands, buttons_to_fm2(frame.buttons[0]), buttons_to_fm2(frame.buttons[1])));
+        }
+
+        out
+    }
+
+    /// Drops everything from frame `len` on, so recording can carry on from there.
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+        self.hashes.truncate(len);
+    }
+}
+
+/// A movie attached to a running machine.
+pub struct MovieSession {
+    pub movie: Movie,
+    pub mode: MovieMode,
+    /// PPU frame the movie's first frame lines up with.
+    pub start_frame: i64,
+    pub desyncs: Vec<Desync>,
+}
+
+impl MovieSession {
+    pub fn new(movie: Movie, mode: MovieMode, start_frame: usize) -> Self {
+        Self {
+            movie,
+            mode,
+            start_frame: start_frame as i64,
+            desyncs: vec![],
+        }
+    }
+
+    /// The movie frame the PPU's `frame` corresponds to, if the movie covers it.
+    pub fn index(&self, frame: usize) -> Option<usize> {
+        usize::try_from(frame as i64 - self.start_frame).ok()
+    }
+}
+
+fn buttons_to_fm2(buttons: u8) -> String {
+    FM2_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(i, &letter)| if buttons & (0x80 >> i) != 0 { letter as char } else { '.' })
+        .collect()
+}
+
+fn buttons_from_fm2(field: &str) -> Option<u8> {
+    if field.is_empty() {
+        return Some(0);
+    }
+
+    if field.len() != 8 {
+        return None;
+    }
+
+    Some(field.bytes().enumerate().fold(0, |buttons, (i, c)| if c == b'.' || c == b' ' { buttons } else { buttons | (0x80 >> i) }))
+}
+
+fn parse_input_line(line: &str) -> Option<MovieFrame> {
+    let mut fields = line.split('|').skip(1);
+    let commands = fields.next()?.trim().parse().ok()?;
+    let port0 = buttons_from_fm2(fields.next()?)?;
+    let port1 = buttons_from_fm2(fields.next().unwrap_or(""))?;
+
+    Some(MovieFrame { commands, buttons: [port0, port1] })
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        bits = (bits << 6) | value;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+
+    Some(out)
+}
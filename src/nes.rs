@@ -1,17 +1,50 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::rc::Rc;
 
 use wasm_bindgen::prelude::*;
-use crate::cpu::Bus;
-use crate::ppu::{Ppu, VBus};
-use crate::{card::Card, cpu::Cpu, mapper::NROM, rom::Rom};
-use crate::js::{consoleLog, updateCRom, updatePRom};
+use crate::cdl::{CdlError, CodeDataLog};
+use crate::cheats::{CheatError, Cheats};
+use crate::cpu::{decode, Bus};
+use crate::memory::MemoryDomain;
+use crate::debugger::{Access, BreakKind, BreakReason, Debugger, ExprContext, ExprError};
+use crate::host::{default_host, HostRef};
+use crate::movie::{Desync, Movie, MovieError, MovieFrame, MovieMode, MovieSession, COMMAND_HARD_RESET, COMMAND_SOFT_RESET};
+use crate::ppu::{Ppu, VBus, DOTS_PER_SCANLINE, SCANLINES_PER_FRAME, VBLANK_SCANLINE};
+use crate::profiler::Profiler;
+use crate::ram_search::{RamSearch, SearchCompare, SearchFormat, SearchWidth};
+use crate::rewind::Rewind;
+use crate::rom::{INes, RomError};
+use crate::state::{self, SaveState, StateError};
+use crate::source_map::{SourceLine, SourceMap};
+use crate::symbols::{SymbolError, SymbolFormat, Symbols};
+use crate::state::{CHUNK_BUS, CHUNK_CHEATS, CHUNK_CHR_RAM, CHUNK_CONTROLLERS, CHUNK_CPU, CHUNK_MAPPER, CHUNK_OAM, CHUNK_PALETTE, CHUNK_PPU, CHUNK_RAM, CHUNK_VRAM};
+use crate::trace::TraceEntry;
+use crate::tracer::{TraceTrigger, Tracer};
+use crate::{card::Card, cpu::Cpu};
 
 #[wasm_bindgen]
 pub struct Nes {
     cpu: Cpu,
     ppu: Rc<RefCell<Ppu>>,
     card: Rc<RefCell<Card>>,
+    host: HostRef,
+    rom_bytes: Vec<u8>,
+    rom_hash: u64,
+    rom_md5: [u8; 16],
+    /// The ROM's file name without its extension, which is how FM2 movies name it.
+    rom_name: String,
+    last_frame: usize,
+    buttons: [u8; 2],
+    rewind: Rewind,
+    movie: Option<MovieSession>,
+    tracer: Tracer,
+    profiler: Profiler,
+    debugger: Debugger,
+    symbols: Symbols,
+    source: SourceMap,
+    /// What the Code/Data Logger has seen, kept while it's stopped.
+    cdl: Option<Rc<RefCell<CodeDataLog>>>,
+    search: Option<RamSearch>,
 }
 
 
@@ -19,49 +52,26 @@ pub struct Nes {
 impl Nes {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        let mut rom_bytes = include_bytes!("../test_roms/7_Graphics.nes").to_vec();
+        let rom_bytes = include_bytes!("../test_roms/7_Graphics.nes").to_vec();
+        let host = default_host();
 
-        let header = &[0x4E, 0x45, 0x53, 0x1A];
-        if !rom_bytes.starts_with(header) {
-            consoleLog("ROM does not start with an INES header");
-            panic!();
-        }
-
-        let prg_size = rom_bytes[4] as usize * 16 * 1024;
-        let chr_size = rom_bytes[5] as usize * 8 * 1024;
-        let vertical_mirror = rom_bytes[6] & 1 != 0;
-
-        consoleLog(format!("Sizes PRG {prg_size} CHR {chr_size} Allocate CHR_RAM? {:?}", chr_size == 0).as_str());
-
-        rom_bytes.drain(0..16);
-
-        let mut prg_rom = Rom::with_capacity(prg_size);
-        prg_rom.consume_bytes(&mut rom_bytes);
-        let mut chr_rom = Rom::with_capacity(chr_size);
-        chr_rom.consume_bytes(&mut rom_bytes);
-
-        updatePRom(&prg_rom.contents);
-        updateCRom(&chr_rom.contents);
-
-        let mapper = NROM::new(prg_rom, chr_rom);
-        let card = Rc::new(RefCell::new(Card::new(Box::new(mapper))));
-        let vbus = VBus::new(card.clone(), vertical_mirror, chr_size == 0);
-        let ppu = Rc::new(RefCell::new(Ppu::new(vbus)));
-        let bus = Bus::new(card.clone(), ppu.clone());
-        let cpu = Cpu::new(bus);
-
-        Self {
-            cpu,
-            card,
-            ppu
+        match Self::with_host(rom_bytes, host.clone()) {
+            Ok(mut nes) => {
+                nes.rom_name = "7_Graphics".to_string();
+                nes
+            }
+            Err(err) => {
+                host.console_log(err.to_string().as_str());
+                panic!();
+            }
         }
     }
 
     #[wasm_bindgen]
-    pub async fn reset(&mut self) {
-        self.cpu.reset().await;
+    pub fn reset(&mut self) {
+        self.reset_cpu();
 
-        consoleLog(format!(
+        self.host.console_log(format!(
             "CPU Registers: A = {:#x}; X = {:#x}; Y = {:#x}; PC = {:#x}; Stack = {:#x}",
             self.cpu.reg_a,
             self.cpu.reg_x,
@@ -69,21 +79,105 @@ impl Nes {
             self.cpu.counter,
             self.cpu.stack
         ).as_str());
-        consoleLog(format!("RAM:\n{:?}", self.cpu.bus.ram).as_str());
-        consoleLog(format!("CHR_RAM:\n{:?}", self.ppu.borrow().vbus.chr_ram).as_str());
+        self.host.console_log(format!("RAM:\n{:?}", self.cpu.bus.ram).as_str());
+        self.host.console_log(format!("CHR_RAM:\n{:?}", self.ppu.borrow().vbus.chr_ram).as_str());
     }
 
+    /// Runs one instruction, returning its CPU cycles. The PPU runs along inside each cycle.
+    ///
+    /// A halted CPU no longer fetches anything, but the PPU keeps drawing, so each
+    /// call then just advances the PPU by one CPU cycle's worth of dots.
+    ///
+    /// Hitting a breakpoint stops before the instruction runs and returns 0.
     #[wasm_bindgen]
     pub fn clock(&mut self) -> usize {
-        let cpu_cycles = self.cpu.clock();
+        if self.debugger.is_armed() && self.cpu.running && !self.cpu.interrupt_due() && self.break_before_instruction() {
+            return 0;
+        }
+
+        if self.tracer.is_enabled() && self.cpu.running && !self.cpu.interrupt_due() {
+            self.trace_instruction();
+        }
+
+        let cpu_cycles = if self.cpu.running {
+            let (pc, sp, interrupted) = (self.cpu.counter, self.cpu.stack, self.cpu.interrupt_due());
+            let cycles = self.cpu.clock();
 
-        for _ in 0..(cpu_cycles * 3) {
-            self.ppu_clock();
+            // an instruction counts against the routine it ran in, an interrupt against its handler
+            if !interrupted {
+                self.profiler.record(pc, cycles, self.debugger.call_stack().frames());
+            }
+            self.debugger.track_calls(pc, sp, interrupted, &self.cpu);
+            if interrupted {
+                self.profiler.record(self.cpu.counter, cycles, self.debugger.call_stack().frames());
+            }
+
+            cycles
+        } else {
+            self.cpu.bus.tick();
+            1
+        };
+
+        self.debugger.after_instruction();
+        self.check_watchpoints();
+
+        let frame = self.ppu.borrow().frame;
+        if frame != self.last_frame {
+            self.last_frame = frame;
+            self.start_frame(frame);
         }
 
         cpu_cycles
     }
 
+    /// Runs until the PPU finishes scanline 261, returning the CPU cycles that took.
+    ///
+    /// Like the other run methods it stops early when the debugger pauses, and does nothing while paused.
+    #[wasm_bindgen]
+    pub fn run_frame(&mut self) -> u32 {
+        let frame = self.ppu.borrow().frame;
+        let mut cycles = 0;
+
+        while self.ppu.borrow().frame == frame && !self.debugger.is_paused() {
+            cycles += self.clock() as u32;
+        }
+
+        cycles
+    }
+
+    /// Runs whole instructions until at least `cycles` CPU cycles have passed, returning how many did.
+    #[wasm_bindgen]
+    pub fn run_cycles(&mut self, cycles: u32) -> u32 {
+        let mut ran = 0;
+
+        while ran < cycles && !self.debugger.is_paused() {
+            ran += self.clock() as u32;
+        }
+
+        ran
+    }
+
+    /// Runs until the PPU next raises the vblank flag, returning the CPU cycles that took.
+    #[wasm_bindgen]
+    pub fn run_until_vblank(&mut self) -> u32 {
+        const FRAME_DOTS: u64 = (DOTS_PER_SCANLINE * SCANLINES_PER_FRAME) as u64;
+        // the flag goes up while dot 1 runs, so the PPU is past it once it reaches dot 2
+        const VBLANK_DOT: u64 = (VBLANK_SCANLINE * DOTS_PER_SCANLINE + 2) as u64;
+
+        let start = self.ppu.borrow().total_dots();
+        let mut target = start - start % FRAME_DOTS + VBLANK_DOT;
+        if target <= start {
+            target += FRAME_DOTS;
+        }
+
+        let mut cycles = 0;
+        while self.ppu.borrow().total_dots() < target && !self.debugger.is_paused() {
+            cycles += self.clock() as u32;
+        }
+
+        cycles
+    }
+
     #[wasm_bindgen]
     pub fn cpu_clock(&mut self) -> usize {
         self.cpu.clock()
@@ -100,32 +194,686 @@ impl Nes {
     }
 
     #[wasm_bindgen]
-    pub async fn swap_rom(&mut self, mut rom_bytes: Vec<u8>) {
-        let header = &[0x4E, 0x45, 0x53, 0x1A];
-        if !rom_bytes.starts_with(header) {
-            consoleLog("ROM does not start with an INES header");
-            panic!();
+    pub fn swap_rom(&mut self, rom_bytes: Vec<u8>) {
+        let ines = match INes::parse(rom_bytes.clone()) {
+            Ok(ines) => ines,
+            Err(err) => {
+                self.host.console_log(err.to_string().as_str());
+                panic!();
+            }
+        };
+
+        self.host.console_log(format!("Sizes PRG {} CHR {}", ines.prg_rom.contents.len(), ines.chr_rom.contents.len()).as_str());
+        self.host.update_prg_rom(&ines.prg_rom.contents);
+        self.rom_hash = state::hash(&[&ines.prg_rom.contents, &ines.chr_rom.contents]);
+        self.rom_md5 = ines.md5();
+        // the caller names the new ROM with `set_rom_name`
+        self.rom_name.clear();
+        self.rom_bytes = rom_bytes;
+        self.rewind.clear();
+        self.movie = None;
+        // the log is sized for the old ROM
+        self.cdl_stop();
+        self.cdl = None;
+        self.search = None;
+
+        // a new board, not just new ROM: RAM size, mirroring and CHR RAM come with it
+        {
+            let mut ppu = self.ppu.borrow_mut();
+            ppu.vbus.vertical_mirror = ines.vertical_mirror;
+            ppu.vbus.chr_ram = ines.chr_rom.contents.is_empty().then_some([0u8; 0x2000]);
+        }
+        *self.card.borrow_mut() = Card::from_ines(ines);
+
+        self.reset_cpu();
+    }
+
+    /// Serializes the whole machine into a versioned save state.
+    #[wasm_bindgen]
+    pub fn save_state(&self) -> Vec<u8> {
+        let ppu = self.ppu.borrow();
+        let card = self.card.borrow();
+
+        SaveState::write(self.rom_hash, |w| {
+            w.chunk(&CHUNK_CPU, |w| self.cpu.save_state(w));
+            w.chunk(&CHUNK_BUS, |w| self.cpu.bus.save_state(w));
+            w.chunk(&CHUNK_RAM, |w| w.bytes(self.cpu.bus.ram.contents()));
+            w.chunk(&CHUNK_CONTROLLERS, |w| self.cpu.bus.save_controllers(w));
+            w.chunk(&CHUNK_PPU, |w| ppu.save_state(w));
+            w.chunk(&CHUNK_VRAM, |w| w.bytes(ppu.vbus.vram.contents()));
+            w.chunk(&CHUNK_PALETTE, |w| w.bytes(&ppu.vbus.palette_ram));
+            w.chunk(&CHUNK_OAM, |w| w.bytes(&ppu.oam));
+
+            if let Some(chr_ram) = ppu.vbus.chr_ram.as_ref() {
+                w.chunk(&CHUNK_CHR_RAM, |w| w.bytes(chr_ram));
+            }
+
+            w.chunk(&CHUNK_MAPPER, |w| w.bytes(&card.mapper.save_state()));
+            w.chunk(&CHUNK_CHEATS, |w| self.cpu.bus.cheats.save_state(w));
+        })
+    }
+
+    /// Restores a state from `save_state`, leaving the machine untouched if it is rejected.
+    #[wasm_bindgen]
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let state = SaveState::parse(data)?;
+
+        if state.rom_hash != self.rom_hash {
+            return Err(StateError::RomMismatch);
         }
 
-        let prg_size = rom_bytes[4] as usize * 16 * 1024;
-        let chr_size = rom_bytes[5] as usize * 8 * 1024;
+        let current = self.save_state();
+        state.check_layout(&SaveState::parse(&current)?)?;
+        let cheats = Cheats::read_state(&mut state.chunk(&CHUNK_CHEATS)?)?;
 
-        consoleLog(format!("Sizes PRG {prg_size} CHR {chr_size}").as_str());
+        self.cpu.load_state(&mut state.chunk(&CHUNK_CPU)?)?;
+        self.cpu.bus.load_state(&mut state.chunk(&CHUNK_BUS)?)?;
+        self.cpu.bus.ram.set_contents(state.chunk(&CHUNK_RAM)?.bytes(0x800)?);
+        self.cpu.bus.load_controllers(&mut state.chunk(&CHUNK_CONTROLLERS)?)?;
 
-        rom_bytes.drain(0..16);
-        updatePRom(&rom_bytes);
+        {
+            let mut ppu = self.ppu.borrow_mut();
+            ppu.load_state(&mut state.chunk(&CHUNK_PPU)?)?;
+            ppu.vbus.vram.set_contents(state.chunk(&CHUNK_VRAM)?.bytes(0x800)?);
+            ppu.vbus.palette_ram = state.chunk(&CHUNK_PALETTE)?.array()?;
+            ppu.oam = state.chunk(&CHUNK_OAM)?.array()?;
 
-        let mut prg_rom = Rom::with_capacity(prg_size);
-        prg_rom.consume_bytes(&mut rom_bytes);
-        let mut chr_rom = Rom::with_capacity(chr_size);
-        chr_rom.consume_bytes(&mut rom_bytes);
+            if let Some(chr_ram) = ppu.vbus.chr_ram.as_mut() {
+                *chr_ram = state.chunk(&CHUNK_CHR_RAM)?.array()?;
+            }
 
-        let mut card = self.card.borrow_mut();
+            self.last_frame = ppu.frame;
+        }
 
-        card.mapper.swap_prg_rom(prg_rom);
-        card.mapper.swap_chr_rom(chr_rom);
+        self.card.borrow_mut().mapper.load_state(state.chunk(&CHUNK_MAPPER)?.rest())?;
+        self.cpu.bus.cheats = cheats;
+        self.debugger.clear_call_stack();
+        self.movie_state_loaded();
+        Ok(())
+    }
+
+    /// Keeps up to `snapshots` rewind points, 0 turns rewinding off.
+    #[wasm_bindgen]
+    pub fn set_rewind_capacity(&mut self, snapshots: usize) {
+        self.rewind.set_capacity(snapshots);
+    }
 
-        self.cpu.reset().await;
+    /// Caps the memory the rewind buffer may use, dropping the oldest snapshots to stay under it.
+    #[wasm_bindgen]
+    pub fn set_rewind_budget(&mut self, bytes: usize) {
+        self.rewind.set_budget(bytes);
+    }
+
+    /// Takes a rewind snapshot every `frames` frames.
+    #[wasm_bindgen]
+    pub fn set_rewind_interval(&mut self, frames: usize) {
+        self.rewind.set_interval(frames);
+    }
+
+    #[wasm_bindgen]
+    pub fn rewind_len(&self) -> usize {
+        self.rewind.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn rewind_usage(&self) -> usize {
+        self.rewind.usage()
+    }
+
+    /// Goes back to the newest snapshot from an earlier frame, returning false when there is
+    /// none or it couldn't be loaded. A snapshot that fails to load stays in the buffer.
+    #[wasm_bindgen]
+    pub fn rewind_step(&mut self) -> bool {
+        let frame = self.ppu.borrow().frame;
+
+        let Some((at, state)) = self.rewind.pop(frame) else {
+            return false;
+        };
+
+        if self.load_state(&state).is_err() {
+            self.rewind.push(at, state);
+            return false;
+        }
+
+        true
+    }
+
+    /// Sets the buttons held on controller `port`, A in bit 0 through Right in bit 7.
+    ///
+    /// While a movie is recording the change waits for the next frame, so every
+    /// frame sees exactly the input the movie stores for it.
+    #[wasm_bindgen]
+    pub fn set_buttons(&mut self, port: usize, buttons: u8) {
+        if port >= self.buttons.len() {
+            return;
+        }
+
+        self.buttons[port] = buttons;
+
+        if self.movie_mode() != Some(MovieMode::Recording) {
+            self.cpu.bus.controllers[port].buttons = buttons;
+        }
+    }
+
+    /// Starts recording input, from a power cycle or from a save state of the machine as it is now.
+    #[wasm_bindgen]
+    pub fn record_movie(&mut self, from_power_on: bool) {
+        self.movie = None;
+
+        if from_power_on {
+            self.power_on();
+        }
+
+        let movie = Movie {
+            rom_filename: self.rom_name.clone(),
+            anchor: (!from_power_on).then(|| self.save_state()),
+            ..Movie::default()
+        };
+
+        self.begin_movie(movie, MovieMode::Recording);
+    }
+
+    /// Plays back an FM2 movie. Read-write playback turns into recording when a state is loaded.
+    #[wasm_bindgen]
+    pub fn play_movie(&mut self, fm2: &str, read_only: bool) -> Result<(), MovieError> {
+        self.start_movie(Movie::from_fm2(fm2)?, read_only)
+    }
+
+    #[wasm_bindgen]
+    pub fn stop_movie(&mut self) {
+        self.movie = None;
+    }
+
+    #[wasm_bindgen]
+    pub fn export_movie(&self) -> Result<String, MovieError> {
+        let session = self.movie.as_ref().ok_or(MovieError::NotActive)?;
+        Ok(session.movie.to_fm2(self.rom_md5))
+    }
+
+    /// Names the loaded ROM after the file it came from, for the movies recorded with it.
+    #[wasm_bindgen]
+    pub fn set_rom_name(&mut self, file_name: &str) {
+        self.rom_name = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem).to_string();
+    }
+
+    #[wasm_bindgen]
+    pub fn movie_rerecords(&self) -> u32 {
+        self.movie.as_ref().map_or(0, |session| session.movie.rerecords)
+    }
+
+    /// Every frame where playback didn't match the recording, one per line.
+    #[wasm_bindgen]
+    pub fn movie_desync_report(&self) -> String {
+        self.movie_desyncs().iter().map(|desync| format!("{desync}\n")).collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn trace_start(&mut self) {
+        self.tracer.start();
+    }
+
+    #[wasm_bindgen]
+    pub fn trace_stop(&mut self) {
+        self.tracer.stop();
+    }
+
+    /// Keeps at most `entries` trace lines until they are drained.
+    #[wasm_bindgen]
+    pub fn set_trace_capacity(&mut self, entries: usize) {
+        self.tracer.set_capacity(entries);
+    }
+
+    /// Only traces instructions with a PC in `start..=end`.
+    #[wasm_bindgen]
+    pub fn set_trace_pc_range(&mut self, start: u16, end: u16) {
+        self.tracer.set_pc_range(Some((start, end)));
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_trace_pc_range(&mut self) {
+        self.tracer.set_pc_range(None);
+    }
+
+    /// Only traces code running from 16 KiB PRG bank `bank`, or from anywhere when `None`.
+    #[wasm_bindgen]
+    pub fn set_trace_bank(&mut self, bank: Option<u16>) {
+        self.tracer.set_bank(bank);
+    }
+
+    /// Holds tracing off until the CPU has run `cycle` cycles since power-on.
+    #[wasm_bindgen]
+    pub fn set_trace_start_cycle(&mut self, cycle: u64) {
+        self.tracer.set_start_cycle(cycle);
+    }
+
+    /// Traces `count` instructions each time execution reaches `pc`, and nothing else.
+    #[wasm_bindgen]
+    pub fn set_trace_trigger(&mut self, pc: u16, count: u32) {
+        self.tracer.set_trigger(Some(TraceTrigger { pc, count }));
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_trace_trigger(&mut self) {
+        self.tracer.set_trigger(None);
+    }
+
+    /// Takes every buffered trace entry as nestest.log lines, oldest first.
+    #[wasm_bindgen]
+    pub fn drain_trace(&mut self) -> String {
+        self.tracer.drain().iter().map(|entry| format!("{entry}\n")).collect()
+    }
+
+    /// `count` instructions from `addr` in CPU memory, one `ADDR  BYTES  ASM` line
+    /// each, with a `label:` line before any that has one.
+    #[wasm_bindgen]
+    pub fn disassemble(&self, addr: u16, count: usize) -> String {
+        let mut out = String::new();
+        let mut pc = addr;
+
+        for _ in 0..count {
+            let window: [u8; 3] = std::array::from_fn(|i| self.cpu.bus.peek(pc.wrapping_add(i as u16)));
+            let instruction = decode(&window, pc).unwrap();
+            let bytes = instruction.bytes();
+
+            if let Some(label) = self.symbols.label(pc) {
+                out.push_str(&format!("{label}:\n"));
+            }
+
+            out.push_str(&format!("{pc:04X}  {bytes:<9}{}\n", instruction.labelled(&self.symbols)));
+            pc = pc.wrapping_add(instruction.len as u16);
+        }
+
+        out
+    }
+
+    /// Adds a breakpoint over `start..=end` and returns its id. Watch a single
+    /// register like `$2007` with `start == end`. An empty `condition` always fires.
+    #[wasm_bindgen]
+    pub fn add_breakpoint(&mut self, kind: BreakKind, start: u16, end: u16, condition: &str) -> Result<u32, ExprError> {
+        let condition = Some(condition.trim()).filter(|condition| !condition.is_empty());
+        let id = self.debugger.add(kind, start, end, condition)?;
+        self.sync_watches();
+        Ok(id)
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        let removed = self.debugger.remove(id);
+        self.sync_watches();
+        removed
+    }
+
+    #[wasm_bindgen]
+    pub fn enable_breakpoint(&mut self, id: u32, enabled: bool) -> bool {
+        let found = self.debugger.set_enabled(id, enabled);
+        self.sync_watches();
+        found
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear();
+        self.sync_watches();
+    }
+
+    /// Stops before the next instruction, cancelling any step in progress.
+    #[wasm_bindgen]
+    pub fn pause(&mut self) {
+        self.debugger.pause(BreakReason::Pause);
+    }
+
+    #[wasm_bindgen]
+    pub fn resume(&mut self) {
+        self.debugger.resume(self.cpu.counter);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_paused(&self) -> bool {
+        self.debugger.is_paused()
+    }
+
+    /// Why the debugger paused, if it has, naming the address involved when it has a label.
+    #[wasm_bindgen]
+    pub fn break_reason(&self) -> Option<String> {
+        let reason = self.debugger.reason()?;
+        let addr = match reason {
+            BreakReason::Breakpoint { pc, .. } => Some(pc),
+            BreakReason::Watchpoint { kind, access, .. } if !kind.is_ppu() => Some(access.addr),
+            _ => None,
+        };
+
+        Some(match addr.and_then(|addr| self.symbols.label(addr)) {
+            Some(label) => format!("{reason} ({label})"),
+            None => reason.to_string(),
+        })
+    }
+
+    /// Where the CPU is, then the call or interrupt that got it there and so on outwards, one per line.
+    #[wasm_bindgen]
+    pub fn call_stack(&self) -> String {
+        let mut out = format!("{}\n", self.symbols.describe(self.cpu.counter));
+
+        for frame in self.debugger.call_stack().frames().iter().rev() {
+            let interrupt = if frame.interrupt { " (interrupt)" } else { "" };
+            out.push_str(&format!("{}{interrupt}\n", self.symbols.describe(frame.caller)));
+        }
+
+        out
+    }
+
+    /// Adds the labels in a symbol file to the ones already loaded, returning how many it had.
+    ///
+    /// An ld65 debug file also replaces the source line map.
+    #[wasm_bindgen]
+    pub fn load_symbols(&mut self, format: SymbolFormat, text: &str) -> Result<usize, SymbolError> {
+        if format == SymbolFormat::Ca65Dbg {
+            self.source.load(text)?;
+        }
+
+        let card = &self.card;
+        self.symbols.load(format, text, &|offset| prg_addrs(&card.borrow(), offset))
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_symbols(&mut self) {
+        self.symbols.clear();
+        self.source.clear();
+    }
+
+    /// Hands over the text of a source file named in the debug info, so
+    /// `source_context` can show it. False if the debug info has no such file.
+    #[wasm_bindgen]
+    pub fn add_source_text(&mut self, name: &str, text: &str) -> bool {
+        self.source.add_text(name, text)
+    }
+
+    /// `game.s:12` for the instruction about to run, whichever bank it's in.
+    #[wasm_bindgen]
+    pub fn source_location(&self) -> Option<String> {
+        self.source_line().map(|line| self.source.describe(line))
+    }
+
+    /// `radius` lines of source either side of the one about to run, with `>` marking it.
+    #[wasm_bindgen]
+    pub fn source_context(&self, radius: u32) -> Option<String> {
+        self.source.context(self.source_line()?, radius)
+    }
+
+    /// Runs until a different source line is reached, following calls into other
+    /// routines. Without a line for the current instruction it's `step_into`.
+    #[wasm_bindgen]
+    pub fn step_line(&mut self) -> usize {
+        self.step_source_line(false)
+    }
+
+    /// Like `step_line`, but runs calls made from the line through to their return.
+    #[wasm_bindgen]
+    pub fn step_line_over(&mut self) -> usize {
+        self.step_source_line(true)
+    }
+
+    /// Adds an execute breakpoint at the start of each place `line` of `file`
+    /// assembled to, returning their ids. None when no code came from that line.
+    #[wasm_bindgen]
+    pub fn add_source_breakpoint(&mut self, file: &str, line: u32, condition: &str) -> Result<Vec<u32>, ExprError> {
+        let Some(file) = self.source.file(file) else {
+            return Ok(vec![]);
+        };
+
+        let addrs = self.source.addresses(SourceLine { file, line }, &|offset| prg_addrs(&self.card.borrow(), offset));
+        address_runs(&addrs)
+            .into_iter()
+            .map(|(start, _)| self.add_breakpoint(BreakKind::Execute, start, start, condition))
+            .collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn symbol_at(&self, addr: u16) -> Option<String> {
+        self.symbols.label(addr).map(String::from)
+    }
+
+    /// The address labelled `name`, so the breakpoint UI can take labels for addresses.
+    #[wasm_bindgen]
+    pub fn symbol_address(&self, name: &str) -> Option<u16> {
+        self.symbols.address(name)
+    }
+
+    /// Starts counting the cycles spent at each PC and in each subroutine.
+    #[wasm_bindgen]
+    pub fn profiler_start(&mut self) {
+        self.profiler.start();
+    }
+
+    /// Stops counting, keeping the counts.
+    #[wasm_bindgen]
+    pub fn profiler_stop(&mut self) {
+        self.profiler.stop();
+    }
+
+    #[wasm_bindgen]
+    pub fn profiler_reset(&mut self) {
+        self.profiler.reset();
+    }
+
+    /// The `limit` most expensive routines and instructions of the last whole frame,
+    /// or of everything since the profiler started if `cumulative`.
+    #[wasm_bindgen]
+    pub fn profile_report(&self, cumulative: bool, limit: usize) -> String {
+        let profile = if cumulative { self.profiler.total() } else { self.profiler.last_frame() };
+        self.profiler.report(profile, &self.symbols, limit)
+    }
+
+    /// The profile in the collapsed stack format that flamegraph tools read.
+    #[wasm_bindgen]
+    pub fn profile_collapsed(&self, cumulative: bool) -> String {
+        let profile = if cumulative { self.profiler.total() } else { self.profiler.last_frame() };
+        self.profiler.collapsed(profile, &self.symbols)
+    }
+
+    /// Starts the Code/Data Logger, carrying on from whatever it logged before.
+    #[wasm_bindgen]
+    pub fn cdl_start(&mut self) {
+        let cdl = self.cdl.get_or_insert_with(|| {
+            let card = self.card.borrow();
+            Rc::new(RefCell::new(CodeDataLog::new(card.mapper.prg_rom().len(), card.mapper.chr_rom().len())))
+        });
+
+        self.cpu.bus.cdl = Some(cdl.clone());
+        self.ppu.borrow_mut().vbus.cdl = Some(cdl.clone());
+    }
+
+    /// Stops logging, keeping the log.
+    #[wasm_bindgen]
+    pub fn cdl_stop(&mut self) {
+        self.cpu.bus.cdl = None;
+        self.ppu.borrow_mut().vbus.cdl = None;
+    }
+
+    #[wasm_bindgen]
+    pub fn cdl_is_logging(&self) -> bool {
+        self.cpu.bus.cdl.is_some()
+    }
+
+    /// Forgets everything logged so far.
+    #[wasm_bindgen]
+    pub fn cdl_clear(&mut self) {
+        if let Some(cdl) = self.cdl.as_ref() {
+            cdl.borrow_mut().clear();
+        }
+    }
+
+    /// The log as an FCEUX `.cdl` file, empty if the logger never ran.
+    #[wasm_bindgen]
+    pub fn cdl_save(&self) -> Vec<u8> {
+        self.cdl.as_ref().map_or(vec![], |cdl| cdl.borrow().to_fceux())
+    }
+
+    /// Replaces the log with a `.cdl` file, so a later run adds to it.
+    #[wasm_bindgen]
+    pub fn cdl_load(&mut self, bytes: &[u8]) -> Result<(), CdlError> {
+        let log = {
+            let card = self.card.borrow();
+            let prg_len = card.mapper.prg_rom().len();
+            CodeDataLog::from_fceux(bytes, prg_len, card.mapper.chr_rom().len())?
+        };
+
+        match self.cdl.as_ref() {
+            Some(cdl) => *cdl.borrow_mut() = log,
+            None => self.cdl = Some(Rc::new(RefCell::new(log))),
+        }
+
+        Ok(())
+    }
+
+    /// How much of the ROM the log has seen used, e.g. `PRG: 1200 code, 300 data, 31268 of 32768 unused`.
+    #[wasm_bindgen]
+    pub fn cdl_summary(&self) -> Option<String> {
+        self.cdl.as_ref().map(|cdl| cdl.borrow().summary().to_string())
+    }
+
+    /// Adds a Game Genie, Pro Action Replay or RAM code, enabled, and returns the id to toggle or remove it by.
+    #[wasm_bindgen]
+    pub fn add_cheat(&mut self, code: &str) -> Result<u32, CheatError> {
+        self.cpu.bus.cheats.add(code)
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_cheat(&mut self, id: u32) -> bool {
+        self.cpu.bus.cheats.remove(id)
+    }
+
+    #[wasm_bindgen]
+    pub fn enable_cheat(&mut self, id: u32, enabled: bool) -> bool {
+        self.cpu.bus.cheats.set_enabled(id, enabled)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_cheats(&mut self) {
+        self.cpu.bus.cheats.clear();
+    }
+
+    /// One line per cheat, as `1 on SXIOPO: $91D9 = $AD`.
+    #[wasm_bindgen]
+    pub fn list_cheats(&self) -> String {
+        self.cpu.bus.cheats.list().iter().map(|cheat| format!("{cheat}\n")).collect()
+    }
+
+    /// Starts a RAM search over `domain` with every address a candidate, replacing any search going on.
+    #[wasm_bindgen]
+    pub fn search_start(&mut self, domain: MemoryDomain, width: SearchWidth, format: SearchFormat) {
+        let memory = self.memory(domain);
+        self.search = Some(RamSearch::new(domain, &memory, width, format));
+    }
+
+    #[wasm_bindgen]
+    pub fn search_stop(&mut self) {
+        self.search = None;
+    }
+
+    /// Keeps the candidates that compare with `value`, or with the last snapshot
+    /// without one, and returns how many are left.
+    #[wasm_bindgen]
+    pub fn search_filter(&mut self, compare: SearchCompare, value: Option<i64>) -> usize {
+        let Some(domain) = self.search.as_ref().map(|search| search.domain()) else {
+            return 0;
+        };
+
+        let memory = self.memory(domain);
+        self.search.as_mut().map_or(0, |search| search.filter(&memory, compare, value))
+    }
+
+    /// Takes the memory as it is now to compare the next filter against.
+    #[wasm_bindgen]
+    pub fn search_snapshot(&mut self) {
+        if let Some(domain) = self.search.as_ref().map(|search| search.domain()) {
+            let memory = self.memory(domain);
+            self.search.as_mut().unwrap().snapshot(&memory);
+        }
+    }
+
+    /// Takes back the last filter.
+    #[wasm_bindgen]
+    pub fn search_undo(&mut self) -> bool {
+        self.search.as_mut().is_some_and(|search| search.undo())
+    }
+
+    /// How many candidates are left and the first `limit` of them, `None` without a search.
+    #[wasm_bindgen]
+    pub fn search_report(&self, limit: usize) -> Option<String> {
+        let search = self.search.as_ref()?;
+        Some(search.report(&self.memory(search.domain()), limit))
+    }
+
+    /// Runs one instruction, or an interrupt sequence if one is due, and stays paused.
+    #[wasm_bindgen]
+    pub fn step_into(&mut self) -> usize {
+        self.debugger.resume(self.cpu.counter);
+        let cycles = self.clock();
+
+        if !self.debugger.is_paused() {
+            self.debugger.pause(BreakReason::Step);
+        }
+
+        cycles
+    }
+
+    /// Runs a `JSR` until it returns, or any other instruction like `step_into`.
+    ///
+    /// The call itself runs as the run methods are called, so the worker keeps its frame loop going until it pauses.
+    #[wasm_bindgen]
+    pub fn step_over(&mut self) -> usize {
+        let opcode = self.cpu.bus.peek(self.cpu.counter);
+
+        if !self.debugger.step_over(self.cpu.counter, self.cpu.stack, opcode) {
+            return self.step_into();
+        }
+
+        self.debugger.resume(self.cpu.counter);
+        0
+    }
+
+    /// Runs until the current routine returns with `RTS`, or `RTI` from an interrupt handler.
+    #[wasm_bindgen]
+    pub fn step_out(&mut self) {
+        self.debugger.step_out(self.cpu.stack);
+        self.debugger.resume(self.cpu.counter);
+    }
+
+    /// Runs until the instruction at `addr` is about to execute.
+    #[wasm_bindgen]
+    pub fn run_to(&mut self, addr: u16) {
+        self.debugger.run_to(addr);
+        self.debugger.resume(self.cpu.counter);
+    }
+
+    /// The instruction about to run and the registers, as a nestest.log line.
+    #[wasm_bindgen]
+    pub fn debug_line(&self) -> String {
+        self.trace_entry().to_string()
+    }
+
+    /// How many bytes `domain` holds.
+    #[wasm_bindgen]
+    pub fn memory_size(&self, domain: MemoryDomain) -> usize {
+        domain.size(&self.cpu.bus)
+    }
+
+    /// Reads a byte of `domain` without any side effects, `None` past its end.
+    #[wasm_bindgen]
+    pub fn peek(&self, domain: MemoryDomain, addr: usize) -> Option<u8> {
+        domain.peek(&self.cpu.bus, addr)
+    }
+
+    /// Reads up to `len` bytes of `domain` from `addr`, stopping at its end.
+    #[wasm_bindgen]
+    pub fn peek_range(&self, domain: MemoryDomain, addr: usize, len: usize) -> Vec<u8> {
+        domain.peek_range(&self.cpu.bus, addr, len)
+    }
+
+    /// Writes a byte of `domain`, returning false past its end.
+    #[wasm_bindgen]
+    pub fn poke(&mut self, domain: MemoryDomain, addr: usize, value: u8) -> bool {
+        domain.poke(&mut self.cpu.bus, addr, value)
     }
 
     #[wasm_bindgen]
@@ -133,3 +881,393 @@ impl Nes {
         self.ppu.borrow().screen_buffer.as_slice().to_vec()
     }
 }
+
+impl Nes {
+    /// Builds a machine around an iNES image, reporting to `host` instead of the browser.
+    pub fn with_host(rom_bytes: Vec<u8>, host: HostRef) -> Result<Self, RomError> {
+        let ines = INes::parse(rom_bytes.clone())?;
+
+        let chr_size = ines.chr_rom.contents.len();
+        host.console_log(format!("Sizes PRG {} CHR {chr_size} Allocate CHR_RAM? {:?}", ines.prg_rom.contents.len(), chr_size == 0).as_str());
+
+        host.update_prg_rom(&ines.prg_rom.contents);
+        host.update_chr_rom(&ines.chr_rom.contents);
+        let rom_hash = state::hash(&[&ines.prg_rom.contents, &ines.chr_rom.contents]);
+        let rom_md5 = ines.md5();
+
+        let vertical_mirror = ines.vertical_mirror;
+        let card = Rc::new(RefCell::new(Card::from_ines(ines)));
+        let vbus = VBus::new(card.clone(), vertical_mirror, chr_size == 0, host.clone());
+        let ppu = Rc::new(RefCell::new(Ppu::new(vbus, host.clone())));
+        let bus = Bus::new(card.clone(), ppu.clone(), host.clone());
+        let cpu = Cpu::new(bus, host.clone());
+
+        Ok(Self {
+            cpu,
+            card,
+            ppu,
+            host,
+            rom_bytes,
+            rom_hash,
+            rom_md5,
+            rom_name: String::new(),
+            last_frame: 0,
+            buttons: [0; 2],
+            rewind: Rewind::new(),
+            movie: None,
+            tracer: Tracer::new(),
+            profiler: Profiler::new(),
+            debugger: Debugger::new(),
+            symbols: Symbols::new(),
+            source: SourceMap::new(),
+            cdl: None,
+            search: None,
+        })
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn ppu(&self) -> std::cell::Ref<'_, Ppu> {
+        self.ppu.borrow()
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    /// CPU cycles executed since the machine was built, the reset sequence included.
+    pub fn total_cycles(&self) -> u64 {
+        self.cpu.total_cycles
+    }
+
+    /// Describes the instruction about to execute, along with the machine state before it runs.
+    pub fn trace_entry(&self) -> TraceEntry {
+        let ppu = self.ppu.borrow();
+        let mut entry = self.cpu.labelled_trace_entry(&self.symbols);
+        entry.scanline = ppu.scanline;
+        entry.dot = ppu.dot;
+        entry
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    pub fn tracer_mut(&mut self) -> &mut Tracer {
+        &mut self.tracer
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source
+    }
+
+    pub fn ram_search(&self) -> Option<&RamSearch> {
+        self.search.as_ref()
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cpu.bus.cheats
+    }
+
+    pub fn cdl(&self) -> Option<Ref<'_, CodeDataLog>> {
+        self.cdl.as_ref().map(|cdl| cdl.borrow())
+    }
+
+    /// Streams every traced instruction to the file at `path`, as nestest.log lines.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn trace_to_file(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.tracer.set_stream(Some(Box::new(std::io::BufWriter::new(file))));
+        Ok(())
+    }
+
+    /// Plays back `movie` from its anchor, or from a power cycle if it has none.
+    pub fn start_movie(&mut self, movie: Movie, read_only: bool) -> Result<(), MovieError> {
+        self.movie = None;
+
+        match &movie.anchor {
+            Some(anchor) => self.load_state(anchor).map_err(MovieError::BadAnchor)?,
+            None => self.power_on(),
+        }
+
+        self.begin_movie(movie, MovieMode::Playing { read_only });
+        Ok(())
+    }
+
+    pub fn movie(&self) -> Option<&Movie> {
+        self.movie.as_ref().map(|session| &session.movie)
+    }
+
+    pub fn movie_mode(&self) -> Option<MovieMode> {
+        self.movie.as_ref().map(|session| session.mode)
+    }
+
+    pub fn movie_desyncs(&self) -> &[Desync] {
+        self.movie.as_ref().map_or(&[], |session| &session.desyncs)
+    }
+
+    /// Puts the machine back the way `with_host` built it and runs the reset sequence.
+    pub fn power_on(&mut self) {
+        let fresh = Self::with_host(self.rom_bytes.clone(), self.host.clone()).expect("ROM was parsed before");
+        // cheats live on the bus but belong to the player, not the machine
+        let cheats = std::mem::take(&mut self.cpu.bus.cheats);
+        let logging = self.cdl_is_logging();
+
+        self.cpu = fresh.cpu;
+        self.cpu.bus.cheats = cheats;
+        self.ppu = fresh.ppu;
+        self.card = fresh.card;
+        self.last_frame = 0;
+        self.rewind.clear();
+        self.sync_watches();
+        if logging {
+            self.cdl_start();
+        }
+        self.reset_cpu();
+    }
+
+    fn begin_movie(&mut self, movie: Movie, mode: MovieMode) {
+        let frame = self.ppu.borrow().frame;
+        self.movie = Some(MovieSession::new(movie, mode, frame));
+        self.movie_frame(frame);
+    }
+
+    /// Hands the instruction about to run to the tracer, if its filters let it through.
+    fn trace_instruction(&mut self) {
+        let pc = self.cpu.counter;
+        let bank = match pc {
+            0x8000..=0xFFFF if self.tracer.has_bank_filter() => self.card.borrow().mapper.prg_bank(pc - 0x8000),
+            _ => None,
+        };
+
+        if self.tracer.wants(pc, bank, self.cpu.total_cycles) {
+            let entry = self.trace_entry();
+            self.tracer.record(entry);
+        }
+    }
+
+    /// The source line of the instruction about to run.
+    fn source_line(&self) -> Option<SourceLine> {
+        let pc = self.cpu.counter;
+        let prg_offset = self.card.borrow().prg_offset(pc);
+        self.source.line_at(pc, prg_offset)
+    }
+
+    fn step_source_line(&mut self, over: bool) -> usize {
+        let Some(line) = self.source_line() else {
+            return self.step_into();
+        };
+
+        let pc = self.cpu.counter;
+        let addrs = self.source.addresses(line, &|offset| prg_addrs(&self.card.borrow(), offset));
+        let Some((start, end)) = address_runs(&addrs).into_iter().find(|(start, end)| (*start..=*end).contains(&pc)) else {
+            return self.step_into();
+        };
+
+        self.debugger.step_leave(start, end, over.then_some(self.cpu.stack));
+        self.debugger.resume(pc);
+        0
+    }
+
+    /// Asks the debugger whether to stop before the instruction at PC.
+    fn break_before_instruction(&mut self) -> bool {
+        let opcode = self.cpu.bus.peek(self.cpu.counter);
+        let ppu = self.ppu.borrow();
+        let ctx = ExprContext::new(&self.cpu, &ppu, None);
+
+        self.debugger.before_instruction(&ctx, opcode)
+    }
+
+    /// Logs bus accesses only while some watchpoint could want them.
+    fn sync_watches(&mut self) {
+        let cpu = self.debugger.watches(BreakKind::CpuRead) || self.debugger.watches(BreakKind::CpuWrite);
+        let ppu = self.debugger.watches(BreakKind::PpuRead) || self.debugger.watches(BreakKind::PpuWrite);
+
+        if cpu != self.cpu.bus.accesses.is_some() {
+            self.cpu.bus.accesses = cpu.then(Vec::new);
+        }
+
+        let vbus = &mut self.ppu.borrow_mut().vbus;
+        if ppu != vbus.accesses.is_some() {
+            vbus.accesses = ppu.then(Vec::new);
+        }
+    }
+
+    /// Checks what the last instruction did on either bus against the watchpoints.
+    fn check_watchpoints(&mut self) {
+        if let Some(mut accesses) = self.cpu.bus.accesses.take() {
+            self.check_accesses(&accesses, BreakKind::CpuRead, BreakKind::CpuWrite);
+            accesses.clear();
+            self.cpu.bus.accesses = Some(accesses);
+        }
+
+        let ppu_accesses = self.ppu.borrow_mut().vbus.accesses.take();
+        if let Some(mut accesses) = ppu_accesses {
+            self.check_accesses(&accesses, BreakKind::PpuRead, BreakKind::PpuWrite);
+            accesses.clear();
+            self.ppu.borrow_mut().vbus.accesses = Some(accesses);
+        }
+    }
+
+    fn check_accesses(&mut self, accesses: &[Access], read: BreakKind, write: BreakKind) {
+        let ppu = self.ppu.borrow();
+
+        for &access in accesses {
+            if self.debugger.is_paused() {
+                return;
+            }
+
+            let ctx = ExprContext::new(&self.cpu, &ppu, Some(access));
+            self.debugger.check_access(if access.write { write } else { read }, &ctx);
+        }
+    }
+
+    /// Runs once the PPU starts a new frame.
+    fn start_frame(&mut self, frame: usize) {
+        self.movie_frame(frame);
+        self.profiler.end_frame();
+        self.cpu.bus.cheats.freeze(&mut self.cpu.bus.ram);
+
+        if self.rewind.wants_snapshot(frame) {
+            let state = self.save_state();
+            self.rewind.push(frame, state);
+        }
+    }
+
+    /// All of `domain`, read without side effects.
+    fn memory(&self, domain: MemoryDomain) -> Vec<u8> {
+        self.peek_range(domain, 0, self.memory_size(domain))
+    }
+
+    fn state_hash(&self) -> u64 {
+        state::hash(&[&self.save_state()])
+    }
+
+    /// Records or plays back the input for the frame that just started.
+    fn movie_frame(&mut self, frame: usize) {
+        let Some((mode, index)) = self.movie.as_ref().and_then(|session| Some((session.mode, session.index(frame)?))) else {
+            return;
+        };
+
+        match mode {
+            MovieMode::Recording => {
+                for (controller, buttons) in self.cpu.bus.controllers.iter_mut().zip(self.buttons) {
+                    controller.buttons = buttons;
+                }
+
+                let input = MovieFrame { commands: 0, buttons: self.buttons };
+                let hash = self.state_hash();
+                let movie = &mut self.movie.as_mut().unwrap().movie;
+
+                // frames skipped by loading a later state have no known input
+                movie.frames.resize(index, MovieFrame::default());
+                movie.hashes.resize(index, 0);
+                movie.frames.push(input);
+                movie.hashes.push(hash);
+            }
+
+            MovieMode::Playing { .. } => {
+                let session = self.movie.as_mut().unwrap();
+                let Some(input) = session.movie.frames.get(index).copied() else {
+                    session.mode = MovieMode::Finished;
+                    self.host.console_log(format!("Movie finished after {index} frames").as_str());
+                    return;
+                };
+
+                if input.commands & COMMAND_HARD_RESET != 0 {
+                    self.power_on();
+                    self.movie.as_mut().unwrap().start_frame = -(index as i64);
+                } else if input.commands & COMMAND_SOFT_RESET != 0 {
+                    self.reset_cpu();
+                }
+
+                for (controller, buttons) in self.cpu.bus.controllers.iter_mut().zip(input.buttons) {
+                    controller.buttons = buttons;
+                }
+
+                let actual = self.state_hash();
+                let session = self.movie.as_mut().unwrap();
+
+                if let Some(&expected) = session.movie.hashes.get(index)
+                    && expected != 0
+                    && expected != actual
+                {
+                    let desync = Desync { frame: index, expected, actual };
+                    self.host.console_log(desync.to_string().as_str());
+                    session.desyncs.push(desync);
+                }
+            }
+
+            MovieMode::Finished => {}
+        }
+    }
+
+    /// Loading a state while recording, or during read-write playback, rerecords from there.
+    fn movie_state_loaded(&mut self) {
+        let frame = self.last_frame;
+        let Some(session) = self.movie.as_mut() else {
+            return;
+        };
+
+        let read_write = match session.mode {
+            MovieMode::Recording => true,
+            MovieMode::Playing { read_only } => !read_only,
+            MovieMode::Finished => false,
+        };
+
+        if read_write && let Some(index) = session.index(frame) {
+            session.movie.truncate(index + 1);
+            session.movie.rerecords += 1;
+            session.mode = MovieMode::Recording;
+        }
+    }
+
+    fn reset_cpu(&mut self) {
+        self.cpu.reset();
+        self.debugger.clear_call_stack();
+    }
+}
+
+impl Default for Nes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where a PRG ROM offset shows up for the CPU, wherever its 16 KiB bank is mapped.
+fn prg_addrs(card: &Card, offset: usize) -> Vec<u16> {
+    let bank = (offset / 0x4000) as u16;
+
+    (0..2u16)
+        .filter(|window| card.mapper.prg_bank(window * 0x4000) == Some(bank))
+        .map(|window| 0x8000 + window * 0x4000 + (offset % 0x4000) as u16)
+        .collect()
+}
+
+/// Splits sorted addresses into runs of consecutive ones, as `(first, last)`.
+fn address_runs(addrs: &[u16]) -> Vec<(u16, u16)> {
+    let mut runs: Vec<(u16, u16)> = vec![];
+
+    for &addr in addrs {
+        match runs.last_mut() {
+            Some((_, last)) if last.checked_add(1) == Some(addr) => *last = addr,
+            _ => runs.push((addr, addr)),
+        }
+    }
+
+    runs
+}
@@ -22,10 +22,4 @@ impl PpuMask {
             render_sprites: byte & 0x8 != 0,
         }
     }
-
-    pub fn read_and_clear_vblank(&mut self) -> u8 {
-        let v = (self.render_sprites as u8) << 7;
-        self.render_sprites = false;
-        v
-    }
 }
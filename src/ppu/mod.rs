@@ -3,13 +3,24 @@ mod ctrl;
 mod mask;
 mod vram;
 mod vbus;
+mod state;
 
 pub use vbus::VBus;
 pub use status::PpuFlags;
 pub use mask::PpuMask;
 pub use ctrl::PpuCtrl;
 
-use crate::js::{consoleLog, forceScreenDraw};
+use crate::cdl::CodeDataLog;
+use crate::host::HostRef;
+
+pub const DOTS_PER_SCANLINE: usize = 341;
+pub const SCANLINES_PER_FRAME: usize = 262;
+/// The scanline whose second dot raises the vblank flag.
+pub const VBLANK_SCANLINE: usize = 241;
+/// The last scanline of a frame, where vblank ends.
+pub const PRE_RENDER_SCANLINE: usize = 261;
+/// How long a bit of the I/O latch holds once nothing drives it, about 600 ms.
+pub const IO_LATCH_DECAY_FRAMES: usize = 36;
 
 pub struct Ppu {
     pub status_flags: PpuFlags,
@@ -20,13 +31,28 @@ pub struct Ppu {
     pub transfer_address: u16,
     pub vram_address: u16,
     pub temp_vram_addr: u16,
+    /// The pixel within a tile to start drawing from, the low bits of the first `$2005` write.
+    pub fine_x: u8,
     pub screen_buffer: [u8; 256 * 240 * 4],
     pub dot: usize,
     pub scanline: usize,
+    pub frame: usize,
+    /// A `$2002` read landed right before vblank, so this frame's flag and NMI never happen.
+    pub suppress_vblank: bool,
+    /// Sprite memory, 64 sprites of 4 bytes each.
+    pub oam: [u8; 0x100],
+    /// Where `$2004` reads and writes in `oam`, set through `$2003`.
+    pub oam_addr: u8,
+    /// The PPU's side of the data bus. Write-only registers and unused bits read back
+    /// whatever was last on it, until it fades.
+    pub io_latch: u8,
+    /// The frame each bit of `io_latch` was last driven in.
+    pub io_latch_driven: [usize; 8],
+    host: HostRef,
 }
 
 impl Ppu {
-    pub fn new(vbus: VBus) -> Self {
+    pub fn new(vbus: VBus, host: HostRef) -> Self {
         Self {
             status_flags: PpuFlags::default(),
             mask_flags: PpuMask::default(),
@@ -36,23 +62,137 @@ impl Ppu {
             transfer_address: 0,
             vram_address: 0,
             temp_vram_addr: 0,
+            fine_x: 0,
             screen_buffer: [0u8; 256 * 240 * 4],
             dot: 0,
             scanline: 0,
+            frame: 0,
+            suppress_vblank: false,
+            oam: [0u8; 0x100],
+            oam_addr: 0,
+            io_latch: 0,
+            io_latch_driven: [0; 8],
+            host,
+        }
+    }
+
+    /// Reads a register at `$2000-$3FFF`, which mirror `$2000-$2007` every 8 bytes.
+    pub fn read_register(&mut self, addr: u16) -> u8 {
+        let (byte, driven) = match addr & 0x2007 {
+            // only the top three bits are status, the rest are the latch
+            0x2002 => (self.read_status() | (self.decayed_io_latch() & 0x1F), 0xE0),
+            0x2004 => (self.oam[self.oam_addr as usize], 0xFF),
+            // palette entries are six bits, the top two come from the latch
+            0x2007 if self.vram_address >= 0x3F00 => (self.ppu_data_read() | (self.decayed_io_latch() & 0xC0), 0x3F),
+            0x2007 => (self.ppu_data_read(), 0xFF),
+            _ => (self.decayed_io_latch(), 0x00),
+        };
+
+        self.drive_io_latch(byte, driven);
+        byte
+    }
+
+    /// Writes a register at `$2000-$3FFF`, which mirror `$2000-$2007` every 8 bytes.
+    pub fn write_register(&mut self, addr: u16, byte: u8) {
+        self.drive_io_latch(byte, 0xFF);
+
+        match addr & 0x2007 {
+            0x2000 => self.write_ctrl(byte),
+            0x2001 => self.mask_flags = PpuMask::from_byte(byte),
+            0x2003 => self.oam_addr = byte,
+            0x2004 => self.oam_data_write(byte),
+            0x2005 => self.ppu_scroll(byte),
+            0x2006 => self.ppu_addr(byte),
+            0x2007 => self.ppu_data_write(byte),
+            // `$2002` is read-only, though writing it still fills the latch
+            _ => {}
+        }
+    }
+
+    /// `io_latch`, less the bits that have faded since they were last driven.
+    pub fn decayed_io_latch(&self) -> u8 {
+        (0..8)
+            .filter(|&bit| self.frame.saturating_sub(self.io_latch_driven[bit]) < IO_LATCH_DECAY_FRAMES)
+            .fold(0, |byte, bit| byte | (self.io_latch & (1 << bit)))
+    }
+
+    /// Puts the `mask` bits of `byte` on the latch, which keeps them fresh.
+    fn drive_io_latch(&mut self, byte: u8, mask: u8) {
+        self.io_latch = (self.decayed_io_latch() & !mask) | (byte & mask);
+
+        for bit in (0..8).filter(|bit| mask & (1 << bit) != 0) {
+            self.io_latch_driven[bit] = self.frame;
+        }
+    }
+
+    /// Reads `$2002`, clearing the vblank flag and the address latch.
+    pub fn read_status(&mut self) -> u8 {
+        // reading one dot before the flag goes up reads it clear and stops it from going up at all
+        if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+            self.suppress_vblank = true;
+        }
+
+        self.write_latch = false;
+        self.status_flags.read_and_clear_vblank()
+    }
+
+    /// Whether the PPU is pulling /NMI, which it does for as long as vblank and the `$2000` enable bit are both set.
+    pub fn nmi_output(&self) -> bool {
+        self.status_flags.v_blank && self.ctrl_flags.nmi
+    }
+
+    /// Writes `$2000`, whose low bits pick the nametable scrolling starts from.
+    pub fn write_ctrl(&mut self, byte: u8) {
+        self.ctrl_flags = PpuCtrl::from_byte(byte);
+        self.temp_vram_addr = (self.temp_vram_addr & !0x0C00) | ((byte as u16 & 0x03) << 10);
+    }
+
+    /// Writes `$2005`, X scroll then Y scroll, sharing the write latch with `$2006`.
+    pub fn ppu_scroll(&mut self, byte: u8) {
+        if !self.write_latch {
+            self.temp_vram_addr = (self.temp_vram_addr & !0x001F) | (byte as u16 >> 3);
+            self.fine_x = byte & 0x07;
+        } else {
+            self.temp_vram_addr = (self.temp_vram_addr & !0x73E0) | ((byte as u16 & 0x07) << 12) | ((byte as u16 & 0xF8) << 2);
         }
+
+        self.write_latch = !self.write_latch;
     }
 
+    /// Writes `$2006`, high byte then low byte, sharing the write latch with `$2005`.
     pub fn ppu_addr(&mut self, byte: u8) {
         if !self.write_latch {
-            self.temp_vram_addr = (byte as u16 & 0x3FFF) << 8;
+            // the top bit of the 15-bit address is cleared
+            self.temp_vram_addr = (self.temp_vram_addr & 0x00FF) | ((byte as u16 & 0x3F) << 8);
         } else {
-            self.vram_address = self.temp_vram_addr | byte as u16;
+            self.temp_vram_addr = (self.temp_vram_addr & 0xFF00) | byte as u16;
+            self.vram_address = self.temp_vram_addr;
             self.transfer_address = self.vram_address;
         }
 
         self.write_latch = !self.write_latch;
     }
 
+    /// Writes `$2004`, which moves on to the next OAM byte.
+    pub fn oam_data_write(&mut self, data: u8) {
+        self.oam[self.oam_addr as usize] = data;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    /// What reading `$2000-$2007`, or a mirror of them, would return, without clearing flags,
+    /// moving the VRAM address or refreshing the latch.
+    pub fn peek_register(&self, addr: u16) -> u8 {
+        let latch = self.decayed_io_latch();
+
+        match addr & 0x2007 {
+            0x2002 => self.status_flags.to_byte() | (latch & 0x1F),
+            0x2004 => self.oam[self.oam_addr as usize],
+            0x2007 if self.vram_address >= 0x3F00 => self.vbus.peek(self.vram_address) | (latch & 0xC0),
+            0x2007 => self.vbus.peek(self.vram_address),
+            _ => latch,
+        }
+    }
+
     pub fn ppu_data_write(&mut self, data: u8) {
         let addr = self.vram_address;
         self.vbus.address = addr;
@@ -65,52 +205,64 @@ impl Ppu {
     pub fn ppu_data_read(&mut self) -> u8 {
         self.vbus.address = self.vram_address;
         self.vbus.read();
+        self.vbus.log_chr(self.vram_address, CodeDataLog::READ);
         self.vbus.data
     }
 
+    /// A fetch for drawing.
     pub fn read(&mut self, addr: u16) -> u8 {
         self.vbus.address = addr;
         self.vbus.read();
+        self.vbus.log_chr(addr, CodeDataLog::DRAWN);
         self.vbus.data
     }
 
     pub fn clock(&mut self) {
-        if self.dot == 1 && self.scanline == 241 {
-            self.status_flags.v_blank = true;
-        } else if self.dot == 1 && self.scanline == 261 {
+        if self.dot == 1 && self.scanline == VBLANK_SCANLINE {
+            self.status_flags.v_blank = !self.suppress_vblank;
+            self.suppress_vblank = false;
+        } else if self.dot == 1 && self.scanline == PRE_RENDER_SCANLINE {
             self.status_flags.v_blank = false;
         }
 
-        let tile_index = self.read(0x2000 + self.dot as u16 + self.scanline as u16 * 32) as u16;
+        // the nametable is drawn a whole tile at a time, one tile per dot of the first 32 dots on the first 30 lines
+        if self.dot < 32 && self.scanline < 30 {
+            let tile_index = self.read(0x2000 + self.dot as u16 + self.scanline as u16 * 32) as u16;
 
-        for y in 0..8 {
-            let low_byte = self.read(tile_index * 16 + y);
-            let high_byte = self.read(tile_index * 16 + 8 + y);
+            for y in 0..8 {
+                let low_byte = self.read(tile_index * 16 + y);
+                let high_byte = self.read(tile_index * 16 + 8 + y);
 
-            for x in 0..8 {
-                let two_bit = ((low_byte >> (7 - x)) & 1) | (((high_byte >> (7 - x)) & 1) << 1);
+                for x in 0..8 {
+                    let two_bit = ((low_byte >> (7 - x)) & 1) | (((high_byte >> (7 - x)) & 1) << 1);
 
-                let buff_x = x + self.dot * 8;
-                let buff_y = y as usize + self.scanline * 8;
-                let buff_addr = (buff_y as usize * 256 + buff_x as usize) * 4;
-                self.screen_buffer[buff_addr] = two_bit * 85;
-                self.screen_buffer[buff_addr + 1] = two_bit * 85;
-                self.screen_buffer[buff_addr + 2] = two_bit * 85;
-                self.screen_buffer[buff_addr + 3] = 255;
+                    let buff_x = x + self.dot * 8;
+                    let buff_y = y as usize + self.scanline * 8;
+                    let buff_addr = (buff_y * 256 + buff_x) * 4;
+                    self.screen_buffer[buff_addr] = two_bit * 85;
+                    self.screen_buffer[buff_addr + 1] = two_bit * 85;
+                    self.screen_buffer[buff_addr + 2] = two_bit * 85;
+                    self.screen_buffer[buff_addr + 3] = 255;
+                }
             }
         }
 
         self.dot += 1;
 
-        if self.dot > 341 {
+        if self.dot >= DOTS_PER_SCANLINE {
             self.dot = 0;
             self.scanline += 1;
 
-            if self.scanline > 261 {
+            if self.scanline >= SCANLINES_PER_FRAME {
                 self.scanline = 0;
+                self.frame += 1;
+                self.host.force_screen_draw();
             }
         }
+    }
 
-        forceScreenDraw();
+    /// Dots run since power-on, counting whole frames.
+    pub fn total_dots(&self) -> u64 {
+        ((self.frame * SCANLINES_PER_FRAME + self.scanline) * DOTS_PER_SCANLINE + self.dot) as u64
     }
 }
@@ -0,0 +1,52 @@
+use crate::ppu::{Ppu, PpuCtrl, PpuFlags, PpuMask};
+use crate::state::{StateError, StateReader, StateWriter};
+
+impl Ppu {
+    /// Registers, latches and timing. Memory goes in its own chunks.
+    pub(crate) fn save_state(&self, w: &mut StateWriter) {
+        w.u8(self.ctrl_flags.to_byte());
+        w.u8(self.mask_flags.to_byte());
+        w.u8(self.status_flags.to_byte());
+        w.bool(self.write_latch);
+        w.u16(self.transfer_address);
+        w.u16(self.vram_address);
+        w.u16(self.temp_vram_addr);
+        w.u16(self.dot as u16);
+        w.u16(self.scanline as u16);
+        w.u64(self.frame as u64);
+        w.u16(self.vbus.address);
+        w.u8(self.vbus.data);
+        w.bool(self.vbus.vertical_mirror);
+        w.bool(self.suppress_vblank);
+        w.u8(self.oam_addr);
+        w.u8(self.fine_x);
+        w.u8(self.io_latch);
+        for driven in self.io_latch_driven {
+            w.u64(driven as u64);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, r: &mut StateReader) -> Result<(), StateError> {
+        self.ctrl_flags = PpuCtrl::from_byte(r.u8()?);
+        self.mask_flags = PpuMask::from_byte(r.u8()?);
+        self.status_flags = PpuFlags::from_byte(r.u8()?);
+        self.write_latch = r.bool()?;
+        self.transfer_address = r.u16()?;
+        self.vram_address = r.u16()?;
+        self.temp_vram_addr = r.u16()?;
+        self.dot = r.u16()? as usize;
+        self.scanline = r.u16()? as usize;
+        self.frame = r.u64()? as usize;
+        self.vbus.address = r.u16()?;
+        self.vbus.data = r.u8()?;
+        self.vbus.vertical_mirror = r.bool()?;
+        self.suppress_vblank = r.bool()?;
+        self.oam_addr = r.u8()?;
+        self.fine_x = r.u8()?;
+        self.io_latch = r.u8()?;
+        for driven in self.io_latch_driven.iter_mut() {
+            *driven = r.u64()? as usize;
+        }
+        Ok(())
+    }
+}
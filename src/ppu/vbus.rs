@@ -1,6 +1,6 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{card::Card, js::updateCRom, ppu::vram::VRam};
+use crate::{card::Card, cdl::CodeDataLog, debugger::Access, host::HostRef, ppu::vram::VRam};
 
 pub struct VBus {
     pub address: u16,
@@ -9,11 +9,15 @@ pub struct VBus {
     pub card: Rc<RefCell<Card>>,
     pub chr_ram: Option<[u8; 0x2000]>,
     pub palette_ram: [u8; 0x20],
-    pub vertical_mirror: bool
+    pub vertical_mirror: bool,
+    /// Every access since the debugger last looked, kept only while a watchpoint needs them.
+    pub accesses: Option<Vec<Access>>,
+    /// The Code/Data Logger, while it's running.
+    pub cdl: Option<Rc<RefCell<CodeDataLog>>>,
 }
 
 impl VBus {
-    pub fn new(card: Rc<RefCell<Card>>, vertical_mirror: bool, allocate_chr_ram: bool) -> Self {
+    pub fn new(card: Rc<RefCell<Card>>, vertical_mirror: bool, allocate_chr_ram: bool, host: HostRef) -> Self {
         let mut chr_ram = None;
 
         if allocate_chr_ram {
@@ -23,93 +27,111 @@ impl VBus {
         Self {
             address: 0,
             data: 0,
-            vram: VRam::default(),
+            vram: VRam::new(host),
             card,
             chr_ram,
             vertical_mirror,
             palette_ram: [0u8; 0x20],
+            accesses: None,
+            cdl: None,
         }
     }
 
     pub fn read(&mut self) {
-        match self.address {
-            0x0000..=0x1FFF => {
-                if let Some(chr_ram) = self.chr_ram.as_ref() {
-                    self.data = chr_ram[self.address as usize];
-                    return;
-                }
-
-                let mut card = self.card.borrow_mut();
-                if let Some(byte) = card.ppu_read(self.address) {
-                    self.data = byte;
-                }
-            }
+        if let Some(byte) = self.fetch(self.address) {
+            self.data = byte;
+        }
 
-            0x2000..=0x3EFF => {
-                let mut addr = self.address & 0x0FFF;
+        self.log_access(false);
+    }
 
-                if self.vertical_mirror {
-                    addr %= 0x800;
-                } else {
-                    addr = (addr & 0x3FF) | ((addr & 0x800) >> 1);
-                }
+    pub fn write(&mut self) {
+        self.log_access(true);
+        self.store(self.address, self.data);
+    }
 
-                if let Some(byte) = self.vram.read(addr) {
-                    self.data = byte;
-                }
-            }
+    /// Reads `addr` without going through the mapper's read side effects or the debugger.
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => match self.chr_ram.as_ref() {
+                Some(chr_ram) => chr_ram[addr as usize & 0x1FFF],
+                None => self.card.borrow().mapper.ppu_peek(addr & 0x1FFF).unwrap_or(self.data),
+            },
+            0x2000..=0x3EFF => self.vram.read(self.nametable_index(addr)).unwrap_or(self.data),
+            _ => self.palette_ram[Self::palette_index(addr)],
+        }
+    }
 
-            0x3F00..=0x3FFF => {
-                let mut addr = (self.address & 0x1F) as usize;
+    /// Writes `addr` the way the PPU would, except CHR ROM takes the byte too.
+    pub fn poke(&mut self, addr: u16, byte: u8) {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF if self.chr_ram.is_none() => self.card.borrow_mut().mapper.ppu_poke(addr & 0x1FFF, byte),
+            addr => self.store(addr, byte),
+        }
+    }
 
-                if addr == 0x10 { addr = 0x00; }
-                if addr == 0x14 { addr = 0x04; }
-                if addr == 0x18 { addr = 0x08; }
-                if addr == 0x1C { addr = 0x0C; }
+    fn fetch(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x0000..=0x1FFF => match self.chr_ram.as_ref() {
+                Some(chr_ram) => Some(chr_ram[addr as usize]),
+                None => self.card.borrow_mut().ppu_read(addr),
+            },
+            0x2000..=0x3EFF => self.vram.read(self.nametable_index(addr)),
+            0x3F00..=0x3FFF => Some(self.palette_ram[Self::palette_index(addr)]),
+            _ => None,
+        }
+    }
 
-                self.data = self.palette_ram[addr];
+    fn store(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => match self.chr_ram.as_mut() {
+                Some(chr_ram) => chr_ram[addr as usize] = byte,
+                None => self.card.borrow_mut().ppu_write(addr, byte),
+            },
+            0x2000..=0x3EFF => {
+                let index = self.nametable_index(addr);
+                self.vram.write(index, byte);
             }
-
+            0x3F00..=0x3FFF => self.palette_ram[Self::palette_index(addr)] = byte & 0x3F,
             _ => {}
-        };
+        }
     }
 
-    pub fn write(&mut self) {
-        match self.address {
-            0x0000..=0x1FFF => {
-                if let Some(chr_ram) = self.chr_ram.as_mut() {
-                    chr_ram[self.address as usize] = self.data;
-                    return;
-                }
-
-                let mut card = self.card.borrow_mut();
-                card.ppu_write(self.address, self.data);
-            }
-
-            0x2000..=0x3EFF => {
-                let mut addr = self.address & 0x0FFF;
-
-                if self.vertical_mirror {
-                    addr %= 0x800;
-                } else {
-                    addr = (addr & 0x3FF) | ((addr & 0x800) >> 1);
-                }
+    /// Where a nametable address lands in the 2 KiB of VRAM after mirroring.
+    fn nametable_index(&self, addr: u16) -> u16 {
+        let addr = addr & 0x0FFF;
 
-                self.vram.write(addr, self.data);
-            }
+        if self.vertical_mirror {
+            addr % 0x800
+        } else {
+            (addr & 0x3FF) | ((addr & 0x800) >> 1)
+        }
+    }
 
-            0x3F00..=0x3FFF => {
-                let mut addr = (self.address & 0x1F) as usize;
+    /// The sprite palettes' first entries mirror the background ones.
+    fn palette_index(addr: u16) -> usize {
+        match (addr & 0x1F) as usize {
+            addr @ (0x10 | 0x14 | 0x18 | 0x1C) => addr - 0x10,
+            addr => addr,
+        }
+    }
 
-                if addr == 0x10 { addr = 0x00; }
-                if addr == 0x14 { addr = 0x04; }
-                if addr == 0x18 { addr = 0x08; }
-                if addr == 0x1C { addr = 0x0C; }
+    /// Marks the CHR ROM byte at `addr` with `flags`, if the Code/Data Logger is running.
+    pub fn log_chr(&self, addr: u16, flags: u8) {
+        let Some(cdl) = self.cdl.as_ref() else {
+            return;
+        };
 
-                self.palette_ram[addr] = self.data & 0x3F;
-            }
+        if addr < 0x2000 && self.chr_ram.is_none()
+            && let Some(offset) = self.card.borrow().chr_offset(addr)
+        {
+            cdl.borrow_mut().mark_chr(offset, flags);
+        }
+    }
 
-            _ => {}
+    fn log_access(&mut self, write: bool) {
+        if let Some(accesses) = self.accesses.as_mut() {
+            accesses.push(Access { addr: self.address, value: self.data, write });
         }
     }
 }
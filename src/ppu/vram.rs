@@ -1,33 +1,40 @@
 use std::fmt;
-use crate::js::{updateRam, updateVRam};
+
+use crate::host::HostRef;
 
 pub struct VRam {
     contents: [u8; 0x800],
+    host: HostRef,
 }
 
 impl VRam {
-    pub fn read(&self, addr: u16) -> Option<u8> {
-        if let Some(byte) = self.contents.get(addr as usize) {
-            Some(byte.clone())
-        } else {
-            None
+    pub fn new(host: HostRef) -> Self {
+        let contents = [0u8; 0x800];
+        host.update_vram(&contents);
+
+        Self {
+            contents,
+            host,
         }
     }
 
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        self.contents.get(addr as usize).copied()
+    }
+
     pub fn write(&mut self, addr: u16, byte: u8) {
         self.contents[addr as usize] = byte;
-        updateVRam(&self.contents);
+        self.host.update_vram(&self.contents);
     }
-}
 
-impl Default for VRam {
-    fn default() -> Self {
-        let contents = [0u8; 0x800];
-        updateVRam(&contents);
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
 
-        Self {
-            contents,
-        }
+    /// Replaces the whole of VRAM, `contents` must be exactly 2 KiB.
+    pub fn set_contents(&mut self, contents: &[u8]) {
+        self.contents.copy_from_slice(contents);
+        self.host.update_vram(&self.contents);
     }
 }
 
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::debugger::CallFrame;
+use crate::symbols::Symbols;
+
+/// What code outside of any call is reported as.
+const ROOT_NAME: &str = "main";
+
+/// Where the cycles went over some stretch of time.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    cycles: u64,
+    frames: u64,
+    per_pc: HashMap<u16, u64>,
+    /// Cycles spent in each call tree node itself, and how often it was entered.
+    per_node: HashMap<usize, (u64, u64)>,
+}
+
+impl Profile {
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Whole frames the profile covers.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Cycles spent running the instruction at `pc`.
+    pub fn at(&self, pc: u16) -> u64 {
+        self.per_pc.get(&pc).copied().unwrap_or(0)
+    }
+
+    fn add(&mut self, pc: u16, node: usize, cycles: u64) {
+        self.cycles += cycles;
+        *self.per_pc.entry(pc).or_default() += cycles;
+        self.per_node.entry(node).or_default().0 += cycles;
+    }
+
+    fn enter(&mut self, node: usize) {
+        self.per_node.entry(node).or_default().1 += 1;
+    }
+}
+
+/// Cycles one subroutine accounted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutineStats {
+    /// Where the routine starts, `None` for code outside of any call.
+    pub routine: Option<u16>,
+    pub calls: u64,
+    /// Cycles spent in the routine's own instructions.
+    pub self_cycles: u64,
+    /// Cycles spent in the routine and everything it called. Recursion is only counted once.
+    pub inclusive: u64,
+}
+
+/// A path through the calls made, as it's been seen.
+#[derive(Debug, Clone)]
+struct Node {
+    routine: Option<u16>,
+    parent: usize,
+    children: HashMap<u16, usize>,
+}
+
+/// Counts the CPU cycles spent at each PC and in each subroutine, following the
+/// debugger's call stack to tell which routine every instruction ran inside of.
+///
+/// It keeps the frame in progress, the last whole frame and everything since it
+/// was started, so a routine's cost can be read both per frame and overall.
+pub struct Profiler {
+    enabled: bool,
+    /// Every call path seen, parents before their children, the root first.
+    nodes: Vec<Node>,
+    /// The call stack as of the last instruction, with the node each frame led to.
+    path: Vec<(CallFrame, usize)>,
+    frame: Profile,
+    last_frame: Profile,
+    total: Profile,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            nodes: vec![Node { routine: None, parent: 0, children: HashMap::new() }],
+            path: Vec::new(),
+            frame: Profile::default(),
+            last_frame: Profile::default(),
+            total: Profile::default(),
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Stops counting, keeping what's been counted.
+    pub fn stop(&mut self) {
+        self.enabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Forgets everything counted so far.
+    pub fn reset(&mut self) {
+        *self = Self { enabled: self.enabled, ..Self::new() };
+    }
+
+    /// The last whole frame.
+    pub fn last_frame(&self) -> &Profile {
+        &self.last_frame
+    }
+
+    /// Everything since the profiler started or was reset.
+    pub fn total(&self) -> &Profile {
+        &self.total
+    }
+
+    /// Counts `cycles` spent at `pc` while inside the calls `frames`, outermost first.
+    pub fn record(&mut self, pc: u16, cycles: usize, frames: &[CallFrame]) {
+        if !self.enabled {
+            return;
+        }
+
+        let node = self.follow(frames);
+        self.frame.add(pc, node, cycles as u64);
+        self.total.add(pc, node, cycles as u64);
+    }
+
+    /// Closes off the frame in progress, which becomes `last_frame`.
+    pub fn end_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        self.last_frame = std::mem::take(&mut self.frame);
+        self.last_frame.frames = 1;
+        self.total.frames += 1;
+    }
+
+    /// Every routine with cycles in `profile`, the most expensive first.
+    pub fn routines(&self, profile: &Profile) -> Vec<RoutineStats> {
+        // children always come after their parents, so going backwards sums up whole subtrees
+        let mut inclusive = vec![0u64; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            inclusive[index] += profile.per_node.get(&index).map_or(0, |counts| counts.0);
+            if index > 0 {
+                inclusive[node.parent] += inclusive[index];
+            }
+        }
+
+        let mut routines: HashMap<Option<u16>, RoutineStats> = HashMap::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (self_cycles, calls) = profile.per_node.get(&index).copied().unwrap_or_default();
+            if inclusive[index] == 0 && calls == 0 {
+                continue;
+            }
+
+            let stats = routines.entry(node.routine).or_insert(RoutineStats {
+                routine: node.routine,
+                calls: 0,
+                self_cycles: 0,
+                inclusive: 0,
+            });
+
+            stats.calls += calls;
+            stats.self_cycles += self_cycles;
+            if !self.recursed(index) {
+                stats.inclusive += inclusive[index];
+            }
+        }
+
+        let mut routines: Vec<RoutineStats> = routines.into_values().collect();
+        routines.sort_by_key(|stats| (std::cmp::Reverse(stats.inclusive), stats.routine));
+        routines
+    }
+
+    /// A table of the `limit` most expensive routines and instructions in `profile`.
+    pub fn report(&self, profile: &Profile, symbols: &Symbols, limit: usize) -> String {
+        let share = |cycles: u64| cycles as f64 * 100.0 / profile.cycles.max(1) as f64;
+        let mut out = format!("{} frames, {} cycles\n\n", profile.frames, profile.cycles);
+
+        let _ = writeln!(out, "{:<24} {:>8} {:>10} {:>10}", "routine", "calls", "self", "inclusive");
+        for stats in self.routines(profile).iter().take(limit) {
+            let _ = writeln!(
+                out,
+                "{:<24} {:>8} {:>10} {:>10} {:5.1}%",
+                self.name(stats.routine, symbols),
+                stats.calls,
+                stats.self_cycles,
+                stats.inclusive,
+                share(stats.inclusive)
+            );
+        }
+
+        let mut hot: Vec<(u16, u64)> = profile.per_pc.iter().map(|(&pc, &cycles)| (pc, cycles)).collect();
+        hot.sort_by_key(|&(pc, cycles)| (std::cmp::Reverse(cycles), pc));
+
+        let _ = writeln!(out, "\n{:<24} {:>10}", "instruction", "cycles");
+        for (pc, cycles) in hot.into_iter().take(limit) {
+            let _ = writeln!(out, "{:<24} {:>10} {:5.1}%", symbols.describe(pc), cycles, share(cycles));
+        }
+
+        out
+    }
+
+    /// `profile` in the collapsed stack format flamegraph tools read: each call
+    /// path as `main;nmi;update 1234`, with the cycles spent in it.
+    pub fn collapsed(&self, profile: &Profile, symbols: &Symbols) -> String {
+        let mut lines: Vec<(String, u64)> = profile
+            .per_node
+            .iter()
+            .filter(|(_, counts)| counts.0 > 0)
+            .map(|(&index, counts)| (self.stack(index, symbols), counts.0))
+            .collect();
+        lines.sort();
+
+        lines.into_iter().map(|(stack, cycles)| format!("{stack} {cycles}\n")).collect()
+    }
+
+    /// The node `frames` leads to, adding any calls it hasn't seen before.
+    fn follow(&mut self, frames: &[CallFrame]) -> usize {
+        if self.path.len() == frames.len() && self.path.last().map(|(frame, _)| frame) == frames.last() {
+            return self.path.last().map_or(0, |&(_, node)| node);
+        }
+
+        let common = self.path.iter().zip(frames).take_while(|((seen, _), frame)| seen == *frame).count();
+        self.path.truncate(common);
+
+        for frame in &frames[common..] {
+            let parent = self.path.last().map_or(0, |&(_, node)| node);
+            let node = self.child(parent, frame.target);
+            self.path.push((*frame, node));
+            self.frame.enter(node);
+            self.total.enter(node);
+        }
+
+        self.path.last().map_or(0, |&(_, node)| node)
+    }
+
+    fn child(&mut self, parent: usize, routine: u16) -> usize {
+        if let Some(&node) = self.nodes[parent].children.get(&routine) {
+            return node;
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(Node { routine: Some(routine), parent, children: HashMap::new() });
+        self.nodes[parent].children.insert(routine, node);
+        node
+    }
+
+    /// Whether `index`'s routine is already further up its path.
+    fn recursed(&self, index: usize) -> bool {
+        let routine = self.nodes[index].routine;
+        let mut node = index;
+
+        while node != 0 {
+            node = self.nodes[node].parent;
+            if self.nodes[node].routine == routine {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn stack(&self, index: usize, symbols: &Symbols) -> String {
+        let mut names = vec![];
+        let mut node = index;
+
+        while node != 0 {
+            names.push(self.name(self.nodes[node].routine, symbols));
+            node = self.nodes[node].parent;
+        }
+
+        names.push(ROOT_NAME.to_string());
+        names.reverse();
+        names.join(";")
+    }
+
+    fn name(&self, routine: Option<u16>, symbols: &Symbols) -> String {
+        routine.map_or(ROOT_NAME.to_string(), |addr| symbols.name(addr))
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::memory::MemoryDomain;
+
+/// How many bytes make up each value searched for, little-endian like the 6502.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchWidth {
+    One = 1,
+    Two = 2,
+    Four = 4,
+}
+
+/// How the bytes of a value are read as a number.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFormat {
+    Unsigned,
+    Signed,
+    /// Two decimal digits a byte, the most significant byte last. Anything with a
+    /// nibble over 9 isn't a number and drops out of every filter.
+    Bcd,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchCompare {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+}
+
+impl SearchCompare {
+    fn test(self, value: i64, operand: i64) -> bool {
+        match self {
+            SearchCompare::Equal => value == operand,
+            SearchCompare::NotEqual => value != operand,
+            SearchCompare::Greater => value > operand,
+            SearchCompare::Less => value < operand,
+        }
+    }
+}
+
+/// One address still in the running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub addr: usize,
+    pub value: Option<i64>,
+    pub previous: Option<i64>,
+}
+
+/// Narrows down where a game keeps some number by comparing snapshots of a memory
+/// domain, the way cheat finders do: start, play until the number changes, keep the
+/// addresses that changed the same way, and repeat.
+///
+/// Nothing here holds on to the machine, each filter is handed the memory as it is
+/// then, so the search carries on across frames while the game runs.
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    domain: MemoryDomain,
+    width: SearchWidth,
+    format: SearchFormat,
+    candidates: Vec<usize>,
+    /// The memory as of the last snapshot, what filters against the previous value compare with.
+    previous: Vec<u8>,
+    /// The candidates and snapshot from before the last filter.
+    undo: Option<(Vec<usize>, Vec<u8>)>,
+}
+
+impl RamSearch {
+    /// Starts with every address in `memory`, a snapshot of `domain`, as a candidate.
+    pub fn new(domain: MemoryDomain, memory: &[u8], width: SearchWidth, format: SearchFormat) -> Self {
+        let end = (memory.len() + 1).saturating_sub(width as usize);
+
+        Self { domain, width, format, candidates: (0..end).collect(), previous: memory.to_vec(), undo: None }
+    }
+
+    pub fn domain(&self) -> MemoryDomain {
+        self.domain
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Keeps the candidates whose value in `memory` compares with `value`, or with
+    /// their value in the last snapshot if it's `None`. `memory` becomes the new
+    /// snapshot. Returns how many candidates are left.
+    pub fn filter(&mut self, memory: &[u8], compare: SearchCompare, value: Option<i64>) -> usize {
+        let candidates = self
+            .candidates
+            .iter()
+            .copied()
+            .filter(|&addr| {
+                let operand = value.or_else(|| self.value(&self.previous, addr));
+                match (self.value(memory, addr), operand) {
+                    (Some(current), Some(operand)) => compare.test(current, operand),
+                    _ => false,
+                }
+            })
+            .collect();
+
+        let candidates = std::mem::replace(&mut self.candidates, candidates);
+        let previous = std::mem::replace(&mut self.previous, memory.to_vec());
+        self.undo = Some((candidates, previous));
+        self.candidates.len()
+    }
+
+    /// Takes `memory` as the snapshot to compare against next, keeping every candidate.
+    pub fn snapshot(&mut self, memory: &[u8]) {
+        self.previous = memory.to_vec();
+    }
+
+    /// Puts back the candidates and snapshot from before the last filter. There's only the one.
+    pub fn undo(&mut self) -> bool {
+        let Some((candidates, previous)) = self.undo.take() else {
+            return false;
+        };
+
+        self.candidates = candidates;
+        self.previous = previous;
+        true
+    }
+
+    /// The first `limit` candidates with their values in `memory` and the last snapshot.
+    pub fn results(&self, memory: &[u8], limit: usize) -> Vec<SearchResult> {
+        self.candidates
+            .iter()
+            .take(limit)
+            .map(|&addr| SearchResult { addr, value: self.value(memory, addr), previous: self.value(&self.previous, addr) })
+            .collect()
+    }
+
+    /// The first `limit` candidates as `$0075: 9 (was 8)` lines, under a count of them all.
+    pub fn report(&self, memory: &[u8], limit: usize) -> String {
+        let show = |value: Option<i64>| value.map_or("-".to_string(), |value| value.to_string());
+        let mut out = format!("{} candidates in {}\n", self.len(), self.domain.name());
+
+        for result in self.results(memory, limit) {
+            let _ = writeln!(out, "${:04X}: {} (was {})", result.addr, show(result.value), show(result.previous));
+        }
+
+        out
+    }
+
+    /// The number at `addr` in `memory`, `None` if it runs off the end or isn't BCD.
+    fn value(&self, memory: &[u8], addr: usize) -> Option<i64> {
+        let bytes = memory.get(addr..addr + self.width as usize)?;
+
+        match self.format {
+            SearchFormat::Unsigned => Some(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as i64)),
+            SearchFormat::Signed => {
+                let bits = 64 - 8 * bytes.len();
+                let unsigned = bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as i64);
+                Some(unsigned << bits >> bits)
+            }
+            SearchFormat::Bcd => bytes.iter().rev().try_fold(0, |value, &byte| {
+                let (high, low) = (byte >> 4, byte & 0x0F);
+                (high <= 9 && low <= 9).then_some(value * 100 + (high * 10 + low) as i64)
+            }),
+        }
+    }
+}
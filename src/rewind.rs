@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+/// Most snapshots between two full keyframes, the rest are stored as deltas.
+const KEYFRAME_EVERY: usize = 60;
+
+const DEFAULT_BUDGET: usize = 4 * 1024 * 1024;
+
+struct Snapshot {
+    frame: usize,
+    keyframe: bool,
+    data: Vec<u8>,
+}
+
+/// Bounded history of save states for playing backwards.
+///
+/// Every snapshot is XORed against the keyframe it belongs to and the mostly zero
+/// result is run-length encoded, so a frame usually costs a few hundred bytes.
+/// The oldest keyframe and its deltas are dropped together once the buffer goes
+/// over its capacity or memory budget.
+pub struct Rewind {
+    capacity: usize,
+    budget: usize,
+    interval: usize,
+    snapshots: VecDeque<Snapshot>,
+    keyframe: Option<Vec<u8>>,
+    since_keyframe: usize,
+    usage: usize,
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            budget: DEFAULT_BUDGET,
+            interval: 1,
+            snapshots: VecDeque::new(),
+            keyframe: None,
+            since_keyframe: 0,
+            usage: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Maximum number of snapshots kept, 0 turns rewinding off.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Maximum bytes the encoded snapshots may take up.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    /// Frames between two snapshots.
+    pub fn set_interval(&mut self, frames: usize) {
+        self.interval = frames.max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Bytes held by the encoded snapshots and the cached keyframe.
+    pub fn usage(&self) -> usize {
+        self.usage + self.keyframe.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.keyframe = None;
+        self.since_keyframe = 0;
+        self.usage = 0;
+    }
+
+    /// Whether a snapshot is due now that the PPU has started `frame`.
+    pub fn wants_snapshot(&self, frame: usize) -> bool {
+        self.is_enabled() && frame.is_multiple_of(self.interval)
+    }
+
+    pub fn push(&mut self, frame: usize, state: Vec<u8>) {
+        let delta_base = self
+            .keyframe
+            .as_ref()
+            .filter(|key| key.len() == state.len() && self.since_keyframe < self.keyframe_every());
+
+        let snapshot = match delta_base {
+            Some(key) => {
+                let delta: Vec<u8> = state.iter().zip(key).map(|(a, b)| a ^ b).collect();
+                self.since_keyframe += 1;
+                Snapshot { frame, keyframe: false, data: encode(&delta) }
+            }
+            None => {
+                let data = encode(&state);
+                self.keyframe = Some(state);
+                self.since_keyframe = 0;
+                Snapshot { frame, keyframe: true, data }
+            }
+        };
+
+        self.usage += snapshot.data.len();
+        self.snapshots.push_back(snapshot);
+        self.evict();
+    }
+
+    /// Takes the newest snapshot from before `frame`, returning its frame and state.
+    pub fn pop(&mut self, frame: usize) -> Option<(usize, Vec<u8>)> {
+        while let Some(snapshot) = self.snapshots.pop_back() {
+            self.usage -= snapshot.data.len();
+
+            let state = if snapshot.keyframe {
+                let state = self.keyframe.take()?;
+                self.reload_keyframe(state.len());
+                state
+            } else {
+                let key = self.keyframe.as_ref()?;
+                self.since_keyframe = self.since_keyframe.saturating_sub(1);
+                decode(&snapshot.data, key.len()).iter().zip(key).map(|(a, b)| a ^ b).collect()
+            };
+
+            if snapshot.frame < frame {
+                return Some((snapshot.frame, state));
+            }
+        }
+
+        None
+    }
+
+    /// Groups get smaller with the capacity, so eviction never throws away most of the buffer.
+    fn keyframe_every(&self) -> usize {
+        (self.capacity / 4).clamp(1, KEYFRAME_EVERY)
+    }
+
+    /// Decodes the newest remaining keyframe so the deltas after it can be restored.
+    fn reload_keyframe(&mut self, len: usize) {
+        let newest = self.snapshots.iter().rposition(|snapshot| snapshot.keyframe);
+
+        self.keyframe = newest.map(|index| decode(&self.snapshots[index].data, len));
+        self.since_keyframe = newest.map_or(0, |index| self.snapshots.len() - index - 1);
+    }
+
+    fn evict(&mut self) {
+        while !self.snapshots.is_empty() && (self.snapshots.len() > self.capacity || self.usage() > self.budget) {
+            // a keyframe takes all of its deltas with it
+            loop {
+                let snapshot = self.snapshots.pop_front().unwrap();
+                self.usage -= snapshot.data.len();
+
+                if self.snapshots.front().is_none_or(|next| next.keyframe) {
+                    break;
+                }
+            }
+
+            if self.snapshots.is_empty() {
+                self.keyframe = None;
+                self.since_keyframe = 0;
+            }
+        }
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut val: usize) {
+    while val >= 0x80 {
+        out.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    out.push(val as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut val = 0;
+    let mut shift = 0;
+
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        val |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    val
+}
+
+/// Run-length encodes zeros as `(zero run, literal length, literals)` groups.
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let zeros = data[pos..].iter().take_while(|&&b| b == 0).count();
+        pos += zeros;
+
+        // short zero runs inside literals are cheaper to keep than to split on
+        let start = pos;
+        while pos < data.len() && data[pos..].iter().take(4).any(|&b| b != 0) {
+            pos += 1;
+        }
+
+        push_varint(&mut out, zeros);
+        push_varint(&mut out, pos - start);
+        out.extend_from_slice(&data[start..pos]);
+    }
+
+    out
+}
+
+fn decode(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let zeros = read_varint(data, &mut pos);
+        out.resize(out.len() + zeros, 0);
+
+        let literals = read_varint(data, &mut pos);
+        out.extend_from_slice(&data[pos..(pos + literals).min(data.len())]);
+        pos += literals;
+    }
+
+    out.resize(len, 0);
+    out
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub struct Rom {
     pub contents: Vec<u8>,
@@ -19,6 +21,70 @@ impl Rom {
     }
 
     pub fn read(&self, addr: u16) -> Option<u8> {
-        self.contents.get(addr as usize).map(|b| b.clone())
+        self.contents.get(addr as usize).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    MissingHeader,
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::MissingHeader => write!(f, "ROM does not start with an INES header"),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+/// An iNES file split into its PRG and CHR banks.
+pub struct INes {
+    pub prg_rom: Rom,
+    pub chr_rom: Rom,
+    pub vertical_mirror: bool,
+    /// Bytes of RAM the cartridge has at `$6000-$7FFF`, 0 for none.
+    pub prg_ram_size: usize,
+}
+
+impl INes {
+    pub fn parse(mut rom_bytes: Vec<u8>) -> Result<Self, RomError> {
+        let header = &[0x4E, 0x45, 0x53, 0x1A];
+        if rom_bytes.len() < 16 || !rom_bytes.starts_with(header) {
+            return Err(RomError::MissingHeader);
+        }
+
+        let prg_size = rom_bytes[4] as usize * 16 * 1024;
+        let chr_size = rom_bytes[5] as usize * 8 * 1024;
+        let vertical_mirror = rom_bytes[6] & 1 != 0;
+        // a battery means there's RAM to back up, and a size of 0 means 8 KiB for older dumps.
+        // Dumps with junk like "DiskDude!" at the end of the header can't be trusted with the size.
+        let ram_units = if rom_bytes[12..16] == [0; 4] { rom_bytes[8] } else { 0 };
+        let has_prg_ram = rom_bytes[6] & 2 != 0 || ram_units != 0;
+        let prg_ram_size = if has_prg_ram { ram_units.max(1) as usize * 8 * 1024 } else { 0 };
+
+        rom_bytes.drain(0..16);
+
+        let mut prg_rom = Rom::with_capacity(prg_size);
+        prg_rom.consume_bytes(&mut rom_bytes);
+        let mut chr_rom = Rom::with_capacity(chr_size);
+        chr_rom.consume_bytes(&mut rom_bytes);
+
+        Ok(Self {
+            prg_rom,
+            chr_rom,
+            vertical_mirror,
+            prg_ram_size,
+        })
+    }
+
+    /// The MD5 FCEUX knows a ROM by, taken over PRG and CHR without the header.
+    pub fn md5(&self) -> [u8; 16] {
+        let mut context = md5::Context::new();
+        context.consume(&self.prg_rom.contents);
+        context.consume(&self.chr_rom.contents);
+        context.compute().0
     }
 }
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::symbols::{ca65_attributes, number, SymbolError};
+
+/// Bytes of the iNES header ld65 links in ahead of PRG ROM, which segment output offsets count.
+const INES_HEADER: usize = 16;
+
+/// Where a byte of the program lives. Code in ROM is keyed by its offset into
+/// PRG ROM so each bank keeps its own lines, everything else by CPU address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Place {
+    Rom(usize),
+    Cpu(u16),
+}
+
+/// A line of one of the program's source files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceLine {
+    /// Index into `SourceMap::files`.
+    pub file: usize,
+    /// 1-based, like the assembler's error messages.
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    line: SourceLine,
+    /// Where the linker put the byte, for when its bank isn't mapped.
+    addr: u16,
+    /// Lower wins when several lines cover a byte.
+    rank: u8,
+}
+
+/// Which line of source each byte of the program came from, read from an ld65 debug file.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<String>,
+    bytes: BTreeMap<Place, Entry>,
+    /// Source text the frontend handed over, by file.
+    texts: HashMap<usize, Vec<String>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Source file names as the debug file gives them, usually relative to where ld65 ran.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// The file called `name`, which may leave off leading directories.
+    pub fn file(&self, name: &str) -> Option<usize> {
+        self.files.iter().position(|file| file == name).or_else(|| {
+            let suffix = format!("/{}", name.trim_start_matches("./"));
+            self.files.iter().position(|file| file.replace('\\', "/").ends_with(&suffix))
+        })
+    }
+
+    /// `game.s:12`
+    pub fn describe(&self, line: SourceLine) -> String {
+        format!("{}:{}", self.files[line.file], line.line)
+    }
+
+    /// Replaces the map with the one in the ld65 debug file `text`, returning how many source lines produced bytes.
+    pub fn load(&mut self, text: &str) -> Result<usize, SymbolError> {
+        self.clear();
+
+        let mut files = HashMap::new();
+        // segment id to (run address, PRG ROM offset)
+        let mut segments = HashMap::new();
+        // span id to (segment id, start, size)
+        let mut spans = HashMap::new();
+        let mut lines = vec![];
+
+        for (index, row) in text.lines().enumerate() {
+            let Some((kind, attributes)) = row.split_once(['\t', ' ']) else {
+                continue;
+            };
+
+            let attributes = ca65_attributes(attributes);
+            let get = |key: &str| attributes.iter().find(|(name, _)| *name == key).map(|(_, value)| *value);
+            let get_number = |key: &str| match get(key) {
+                Some(value) => number(value, index + 1).map(Some),
+                None => Ok(None),
+            };
+            let require = |key: &str| {
+                get_number(key)?.ok_or_else(|| SymbolError { line: index + 1, reason: format!("{kind} has no {key}") })
+            };
+
+            match kind {
+                "file" => {
+                    let name = get("name").ok_or(SymbolError { line: index + 1, reason: "file has no name".into() })?;
+                    files.insert(require("id")?, name.to_string());
+                }
+                "seg" => {
+                    // segments linked into the ROM image have an output offset
+                    let rom = get_number("ooffs")?.and_then(|offset| (offset as usize).checked_sub(INES_HEADER));
+                    segments.insert(require("id")?, (require("start")? as u16, rom));
+                }
+                "span" => {
+                    spans.insert(require("id")?, (require("seg")?, require("start")?, require("size")?));
+                }
+                "line" => {
+                    let Some(ids) = get("span") else {
+                        continue;
+                    };
+
+                    // C lines over the assembly generated for them, and that over macro bodies
+                    let rank = match get_number("type")? {
+                        Some(1) => 0,
+                        None | Some(0) => 1,
+                        _ => 2,
+                    };
+
+                    let ids = ids.split('+').map(|id| number(id, index + 1)).collect::<Result<Vec<_>, _>>()?;
+                    lines.push((require("file")?, require("line")?, rank, ids));
+                }
+                _ => {}
+            }
+        }
+
+        let mut ids: Vec<u32> = files.keys().copied().collect();
+        ids.sort();
+        let file_index: HashMap<u32, usize> = ids.iter().enumerate().map(|(index, id)| (*id, index)).collect();
+        self.files = ids.iter().map(|id| files[id].clone()).collect();
+
+        let mut mapped = 0;
+
+        for (file, line, rank, ids) in lines {
+            let Some(&file) = file_index.get(&file) else {
+                continue;
+            };
+
+            let line = SourceLine { file, line };
+            let mut produced = false;
+
+            for id in ids {
+                let Some(&(segment, start, size)) = spans.get(&id) else {
+                    continue;
+                };
+                let Some(&(seg_start, rom)) = segments.get(&segment) else {
+                    continue;
+                };
+
+                for byte in start..start + size {
+                    let addr = seg_start.wrapping_add(byte as u16);
+                    let place = match rom {
+                        Some(offset) => Place::Rom(offset + byte as usize),
+                        None => Place::Cpu(addr),
+                    };
+
+                    let entry = Entry { line, addr, rank };
+                    match self.bytes.get(&place) {
+                        Some(existing) if existing.rank <= rank => {}
+                        _ => {
+                            self.bytes.insert(place, entry);
+                        }
+                    }
+                    produced = true;
+                }
+            }
+
+            mapped += produced as usize;
+        }
+
+        Ok(mapped)
+    }
+
+    /// Hands over the text of a source file so `context` can show it, returning
+    /// false when the debug info doesn't mention a file called `name`.
+    pub fn add_text(&mut self, name: &str, text: &str) -> bool {
+        let Some(file) = self.file(name) else {
+            return false;
+        };
+
+        self.texts.insert(file, text.lines().map(String::from).collect());
+        true
+    }
+
+    /// The line that produced the byte at CPU address `addr`, which is at
+    /// `prg_offset` in PRG ROM when ROM is mapped there.
+    pub fn line_at(&self, addr: u16, prg_offset: Option<usize>) -> Option<SourceLine> {
+        let place = match prg_offset {
+            Some(offset) => Place::Rom(offset),
+            None => Place::Cpu(addr),
+        };
+
+        self.bytes.get(&place).map(|entry| entry.line)
+    }
+
+    /// Every CPU address holding a byte of `line`, in order. ROM bytes are
+    /// wherever `prg_addrs` says their bank is mapped, or where they were linked
+    /// to run when it isn't.
+    pub fn addresses(&self, line: SourceLine, prg_addrs: &dyn Fn(usize) -> Vec<u16>) -> Vec<u16> {
+        let mut addrs: Vec<u16> = self
+            .bytes
+            .iter()
+            .filter(|(_, entry)| entry.line == line)
+            .flat_map(|(place, entry)| match *place {
+                Place::Rom(offset) => {
+                    let mapped = prg_addrs(offset);
+                    if mapped.is_empty() { vec![entry.addr] } else { mapped }
+                }
+                Place::Cpu(addr) => vec![addr],
+            })
+            .collect();
+
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    /// `radius` lines either side of `line`, numbered, with `>` marking it. `None` without its source text.
+    pub fn context(&self, line: SourceLine, radius: u32) -> Option<String> {
+        let text = self.texts.get(&line.file)?;
+        let first = line.line.saturating_sub(radius).max(1);
+        let last = (line.line + radius).min(text.len() as u32);
+        let width = last.to_string().len();
+
+        Some(
+            (first..=last)
+                .map(|number| {
+                    let marker = if number == line.line { '>' } else { ' ' };
+                    format!("{marker} {number:>width$}  {}\n", text[number as usize - 1])
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+/// Identifies a nest save state.
+pub const STATE_MAGIC: &[u8; 4] = b"NEST";
+
+/// Bumped whenever a chunk changes layout. States from any other version are refused.
+pub const STATE_VERSION: u16 = 1;
+
+pub type ChunkTag = [u8; 4];
+
+pub(crate) const CHUNK_CPU: ChunkTag = *b"CPU ";
+pub(crate) const CHUNK_BUS: ChunkTag = *b"BUS ";
+pub(crate) const CHUNK_RAM: ChunkTag = *b"RAM ";
+pub(crate) const CHUNK_PPU: ChunkTag = *b"PPU ";
+pub(crate) const CHUNK_VRAM: ChunkTag = *b"VRAM";
+pub(crate) const CHUNK_PALETTE: ChunkTag = *b"PAL ";
+pub(crate) const CHUNK_OAM: ChunkTag = *b"OAM ";
+pub(crate) const CHUNK_CHR_RAM: ChunkTag = *b"CHRR";
+pub(crate) const CHUNK_MAPPER: ChunkTag = *b"MAPR";
+pub(crate) const CHUNK_CONTROLLERS: ChunkTag = *b"PADS";
+pub(crate) const CHUNK_CHEATS: ChunkTag = *b"CHT ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    RomMismatch,
+    Truncated,
+    MissingChunk(ChunkTag),
+    BadChunkLength { tag: ChunkTag, expected: usize, actual: usize },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a nest save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "save state version {version} is not supported (this build reads version {STATE_VERSION})")
+            }
+            StateError::RomMismatch => write!(f, "save state was made with a different ROM"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::MissingChunk(tag) => write!(f, "save state has no {} chunk", String::from_utf8_lossy(tag)),
+            StateError::BadChunkLength { tag, expected, actual } => write!(
+                f,
+                "save state {} chunk is {actual} bytes, expected {expected}",
+                String::from_utf8_lossy(tag)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<StateError> for JsValue {
+    fn from(err: StateError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// Little-endian byte sink the machine components write their chunks into.
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn bool(&mut self, val: bool) {
+        self.buf.push(val as u8);
+    }
+
+    pub fn u16(&mut self, val: u16) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, val: &[u8]) {
+        self.buf.extend_from_slice(val);
+    }
+
+    /// Writes a tagged, length-prefixed chunk filled in by `fill`.
+    pub fn chunk(&mut self, tag: &ChunkTag, fill: impl FnOnce(&mut StateWriter)) {
+        let mut chunk = StateWriter::default();
+        fill(&mut chunk);
+
+        self.bytes(tag);
+        self.u32(chunk.buf.len() as u32);
+        self.bytes(&chunk.buf);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reads back what a `StateWriter` wrote.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos.checked_add(len).ok_or(StateError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(StateError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Everything not read yet.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        self.pos = self.data.len();
+        rest
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+/// A save state split into its header and chunks, not yet applied to a machine.
+pub struct SaveState<'a> {
+    pub rom_hash: u64,
+    pub chunks: HashMap<ChunkTag, &'a [u8]>,
+}
+
+impl<'a> SaveState<'a> {
+    pub fn write(rom_hash: u64, fill: impl FnOnce(&mut StateWriter)) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.bytes(STATE_MAGIC);
+        writer.u16(STATE_VERSION);
+        writer.u64(rom_hash);
+        fill(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn parse(data: &'a [u8]) -> Result<Self, StateError> {
+        let mut reader = StateReader::new(data);
+
+        if reader.bytes(4).map_err(|_| StateError::BadMagic)? != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = reader.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let rom_hash = reader.u64()?;
+        let mut chunks = HashMap::new();
+
+        while !reader.is_empty() {
+            let tag = reader.array::<4>()?;
+            let len = reader.u32()? as usize;
+            chunks.insert(tag, reader.bytes(len)?);
+        }
+
+        Ok(Self { rom_hash, chunks })
+    }
+
+    pub fn chunk(&self, tag: &ChunkTag) -> Result<StateReader<'_>, StateError> {
+        self.chunks.get(tag).map(|data| StateReader::new(data)).ok_or(StateError::MissingChunk(*tag))
+    }
+
+    /// Checks every chunk has the length `reference` has for it, so applying can't fail halfway.
+    /// The cheats chunk grows with the cheats, so it only has to be there and is read up front.
+    pub fn check_layout(&self, reference: &SaveState) -> Result<(), StateError> {
+        for (tag, data) in &reference.chunks {
+            let actual = self.chunks.get(tag).ok_or(StateError::MissingChunk(*tag))?.len();
+
+            if actual != data.len() && *tag != CHUNK_CHEATS {
+                return Err(StateError::BadChunkLength { tag: *tag, expected: data.len(), actual });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// FNV-1a, used to tie a save state to its ROM and to compare machine states.
+pub fn hash(parts: &[&[u8]]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+
+    hash
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+/// Symbol files from the assemblers and emulators homebrew gets built and debugged with.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFormat {
+    /// The debug info file ld65 writes with `--dbgfile`.
+    Ca65Dbg,
+    /// FCEUX name lists, `$C123#name#comment` per line.
+    FceuxNl,
+    /// Mesen label files, `P:0123:name:comment` per line.
+    MesenMlb,
+}
+
+impl SymbolFormat {
+    /// Guesses the format from a file name, `game.nes.0.nl` included.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        match name.rsplit('.').next()?.to_ascii_lowercase().as_str() {
+            "dbg" => Some(SymbolFormat::Ca65Dbg),
+            "nl" => Some(SymbolFormat::FceuxNl),
+            "mlb" => Some(SymbolFormat::MesenMlb),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    /// 1-based line of the symbol file.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "symbol file line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl From<SymbolError> for JsValue {
+    fn from(err: SymbolError) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// Labels for CPU addresses, merged from any number of symbol files.
+///
+/// An address keeps the first label it was given, except that cheap locals like
+/// `@loop` give way to a proper label for the same spot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    /// Shared so traces can hold on to a label without copying it.
+    labels: BTreeMap<u16, Rc<str>>,
+    addresses: BTreeMap<String, u16>,
+}
+
+impl Symbols {
+    pub const fn new() -> Self {
+        Self { labels: BTreeMap::new(), addresses: BTreeMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.labels.clear();
+        self.addresses.clear();
+    }
+
+    pub fn insert(&mut self, addr: u16, name: &str) {
+        let is_local = |name: &str| name.starts_with('@');
+
+        match self.labels.get(&addr) {
+            Some(existing) if !is_local(existing) || is_local(name) => {}
+            _ => {
+                self.labels.insert(addr, Rc::from(name));
+            }
+        }
+
+        self.addresses.entry(name.to_string()).or_insert(addr);
+    }
+
+    /// The label at exactly `addr`.
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(|label| &**label)
+    }
+
+    /// The label at exactly `addr`, as a handle that's cheap to keep.
+    pub(crate) fn shared_label(&self, addr: u16) -> Option<Rc<str>> {
+        self.labels.get(&addr).cloned()
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.labels.iter().map(|(&addr, name)| (addr, &**name))
+    }
+
+    /// The label at `addr`, or `$C123` when there is none.
+    pub fn name(&self, addr: u16) -> String {
+        match self.label(addr) {
+            Some(label) => label.to_string(),
+            None => format!("${addr:04X}"),
+        }
+    }
+
+    /// Names a zero page address, or `$12` when it has no label.
+    pub fn name_zp(&self, addr: u8) -> String {
+        match self.label(addr as u16) {
+            Some(label) => label.to_string(),
+            None => format!("${addr:02X}"),
+        }
+    }
+
+    /// `$C126 update_player+3` for an address inside a labelled routine, or just `$C126`.
+    pub fn describe(&self, addr: u16) -> String {
+        // anything further than this past a label is unlikely to belong to it
+        const REACH: u16 = 0x100;
+
+        match self.labels.range(..=addr).next_back() {
+            Some((&start, label)) if start == addr => format!("${addr:04X} {label}"),
+            Some((&start, label)) if addr - start < REACH => format!("${addr:04X} {label}+{}", addr - start),
+            _ => format!("${addr:04X}"),
+        }
+    }
+
+    /// Adds every label in `text`, returning how many there were.
+    ///
+    /// `prg_addrs` says where a PRG ROM offset shows up for the CPU, which Mesen
+    /// label files need since they label ROM rather than addresses.
+    pub fn load(&mut self, format: SymbolFormat, text: &str, prg_addrs: &dyn Fn(usize) -> Vec<u16>) -> Result<usize, SymbolError> {
+        let labels = match format {
+            SymbolFormat::Ca65Dbg => parse_ca65_dbg(text)?,
+            SymbolFormat::FceuxNl => parse_fceux_nl(text)?,
+            SymbolFormat::MesenMlb => parse_mesen_mlb(text, prg_addrs)?,
+        };
+
+        for (addr, name) in labels.iter() {
+            self.insert(*addr, name);
+        }
+
+        Ok(labels.len())
+    }
+}
+
+pub(crate) fn number(text: &str, line: usize) -> Result<u32, SymbolError> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix('$')) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+
+    parsed.map_err(|_| SymbolError { line, reason: format!("`{text}` is not a number") })
+}
+
+fn hex(text: &str, line: usize) -> Result<u32, SymbolError> {
+    u32::from_str_radix(text, 16).map_err(|_| SymbolError { line, reason: format!("`{text}` is not a hex address") })
+}
+
+/// Splits `key=value,key="quoted, value"` into pairs.
+pub(crate) fn ca65_attributes(text: &str) -> Vec<(&str, &str)> {
+    let mut attributes = vec![];
+    let mut rest = text;
+
+    while let Some((key, after)) = rest.split_once('=') {
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted[end..].trim_start_matches('"'))
+            }
+            None => after.split_at(after.find(',').unwrap_or(after.len())),
+        };
+
+        attributes.push((key.trim(), value));
+        rest = next.strip_prefix(',').unwrap_or(next);
+    }
+
+    attributes
+}
+
+/// Labels from ld65's `sym` lines. Equates are constants rather than places, so they're left out.
+fn parse_ca65_dbg(text: &str) -> Result<Vec<(u16, String)>, SymbolError> {
+    let mut labels = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let Some(attributes) = line.strip_prefix("sym\t").or_else(|| line.strip_prefix("sym ")) else {
+            continue;
+        };
+
+        let attributes = ca65_attributes(attributes);
+        let get = |key: &str| attributes.iter().find(|(name, _)| *name == key).map(|(_, value)| *value);
+
+        if get("type") != Some("lab") {
+            continue;
+        }
+
+        let name = get("name").ok_or(SymbolError { line: index + 1, reason: "symbol has no name".into() })?;
+        let val = get("val").ok_or(SymbolError { line: index + 1, reason: format!("label `{name}` has no value") })?;
+        labels.push((number(val, index + 1)? as u16, name.to_string()));
+    }
+
+    Ok(labels)
+}
+
+fn parse_fceux_nl(text: &str) -> Result<Vec<(u16, String)>, SymbolError> {
+    let mut labels = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let Some(entry) = line.trim().strip_prefix('$') else {
+            continue;
+        };
+
+        let mut fields = entry.split('#');
+        // `$0200/10` labels ten bytes, the label goes on the first
+        let addr = fields.next().unwrap_or_default().split('/').next().unwrap_or_default();
+        let addr = hex(addr, index + 1)? as u16;
+
+        match fields.next().map(str::trim) {
+            Some(name) if !name.is_empty() => labels.push((addr, name.to_string())),
+            // a comment without a name
+            _ => {}
+        }
+    }
+
+    Ok(labels)
+}
+
+fn parse_mesen_mlb(text: &str, prg_addrs: &dyn Fn(usize) -> Vec<u16>) -> Result<Vec<(u16, String)>, SymbolError> {
+    let mut labels = vec![];
+
+    for (index, line) in text.lines().enumerate() {
+        let mut fields = line.trim().splitn(4, ':');
+        let (Some(kind), Some(addr), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+
+        if name.is_empty() {
+            continue;
+        }
+
+        let offset = hex(addr.split('-').next().unwrap_or_default(), index + 1)? as usize;
+
+        // Mesen 2 spells the memory types out, Mesen 1 used a letter
+        let addrs = match kind {
+            "P" | "NesPrgRom" => prg_addrs(offset),
+            "R" | "NesInternalRam" => vec![offset as u16],
+            "W" | "S" | "NesWorkRam" | "NesSaveRam" => {
+                // only 8K of work RAM shows up, at $6000-$7FFF
+                let addr = u16::try_from(offset).ok().filter(|&offset| offset <= 0x1FFF).and_then(|offset| offset.checked_add(0x6000));
+                let addr = addr.ok_or(SymbolError { line: index + 1, reason: format!("work RAM offset ${offset:X} is past $1FFF") })?;
+                vec![addr]
+            }
+            "G" | "NesMemory" => vec![offset as u16],
+            _ => continue,
+        };
+
+        labels.extend(addrs.into_iter().map(|addr| (addr, name.to_string())));
+    }
+
+    Ok(labels)
+}
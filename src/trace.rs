@@ -0,0 +1,300 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::cpu::{AddrMode, Instruction};
+use crate::Nes;
+
+/// The machine state right before an instruction executes.
+///
+/// Only raw values are kept. The nestest.log text is put together when the entry
+/// gets displayed, so tracing into the ring buffer doesn't format anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub instruction: Instruction,
+    /// What the operand reached in memory, read just before the instruction ran.
+    pub operand: OperandPeek,
+    /// The label of the address the operand names, if it has one.
+    pub label: Option<Rc<str>>,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub scanline: usize,
+    pub dot: usize,
+    pub cycle: u64,
+}
+
+/// Memory a traced operand reached. Which fields mean anything depends on the addressing mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperandPeek {
+    /// The zero page pointer `(zp,X)` reads through, or the base address `(zp),Y` reads.
+    pub pointer: u16,
+    /// The effective address, or where an indirect `JMP` goes.
+    pub addr: u16,
+    /// The value at the effective address.
+    pub value: u8,
+}
+
+impl TraceEntry {
+    /// Splits the entry into the same named fields `parse_log_line` produces.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let star = if self.instruction.info().official { "" } else { "*" };
+
+        vec![
+            ("PC", format!("{:04X}", self.instruction.addr)),
+            ("bytes", self.instruction.bytes().to_string()),
+            ("disassembly", format!("{star}{}", self.disassembly())),
+            ("A", format!("{:02X}", self.a)),
+            ("X", format!("{:02X}", self.x)),
+            ("Y", format!("{:02X}", self.y)),
+            ("P", format!("{:02X}", self.p)),
+            ("SP", format!("{:02X}", self.sp)),
+            ("PPU", format!("{},{}", self.scanline, self.dot)),
+            ("CYC", self.cycle.to_string()),
+        ]
+    }
+
+    /// The instruction the way nestest.log shows it, with memory operands annotated
+    /// with the effective address and the value stored there.
+    pub fn disassembly(&self) -> impl fmt::Display + '_ {
+        Disassembly(self)
+    }
+
+    fn write_disassembly(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let info = self.instruction.info();
+        let mnemonic = info.mnemonic;
+        let byte = self.instruction.operand as u8;
+        let word = self.instruction.operand;
+        let OperandPeek { pointer, addr, value } = self.operand;
+        let label = self.label.as_deref();
+        let (zp, abs) = (Name { label, addr: byte as u16, width: 2 }, Name { label, addr: word, width: 4 });
+
+        match info.mode {
+            AddrMode::Implied => write!(out, "{mnemonic}"),
+            AddrMode::Accumulator => write!(out, "{mnemonic} A"),
+            AddrMode::Immediate => write!(out, "{mnemonic} #${byte:02X}"),
+            AddrMode::ZeroPage => write!(out, "{mnemonic} {zp} = {value:02X}"),
+            AddrMode::ZeroPageX => write!(out, "{mnemonic} {zp},X @ {addr:02X} = {value:02X}"),
+            AddrMode::ZeroPageY => write!(out, "{mnemonic} {zp},Y @ {addr:02X} = {value:02X}"),
+            AddrMode::Absolute if self.instruction.target().is_some() => write!(out, "{mnemonic} {abs}"),
+            AddrMode::Absolute => write!(out, "{mnemonic} {abs} = {value:02X}"),
+            AddrMode::AbsoluteX => write!(out, "{mnemonic} {abs},X @ {addr:04X} = {value:02X}"),
+            AddrMode::AbsoluteY => write!(out, "{mnemonic} {abs},Y @ {addr:04X} = {value:02X}"),
+            AddrMode::Indirect => write!(out, "{mnemonic} ({abs}) = {addr:04X}"),
+            AddrMode::IndirectX => write!(out, "{mnemonic} ({zp},X) @ {pointer:02X} = {addr:04X} = {value:02X}"),
+            AddrMode::IndirectY => write!(out, "{mnemonic} ({zp}),Y = {pointer:04X} @ {addr:04X} = {value:02X}"),
+            AddrMode::Relative => {
+                let target = Name { label, addr: self.instruction.target().unwrap(), width: 4 };
+                write!(out, "{mnemonic} {target}")
+            }
+        }
+    }
+}
+
+/// Formats the entry exactly like a line of nestest.log.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let star = if self.instruction.info().official { ' ' } else { '*' };
+        write!(f, "{:04X}  {:<9}{star}", self.instruction.addr, self.instruction.bytes())?;
+
+        let mut column = Column { out: f, len: 0 };
+        self.write_disassembly(&mut column)?;
+        let padding = 32usize.saturating_sub(column.len);
+
+        write!(
+            f,
+            "{:padding$}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            "",
+            self.a,
+            self.x,
+            self.y,
+            self.p,
+            self.sp,
+            self.scanline,
+            self.dot,
+            self.cycle
+        )
+    }
+}
+
+struct Disassembly<'a>(&'a TraceEntry);
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_disassembly(f)
+    }
+}
+
+/// An address by its label, or as `$12`/`$1234` when it has none.
+struct Name<'a> {
+    label: Option<&'a str>,
+    addr: u16,
+    width: usize,
+}
+
+impl fmt::Display for Name<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.label {
+            Some(label) => f.write_str(label),
+            None => write!(f, "${:0width$X}", self.addr, width = self.width),
+        }
+    }
+}
+
+/// Passes text through while counting it, so a column can be padded without building a string first.
+struct Column<'a, W> {
+    out: &'a mut W,
+    len: usize,
+}
+
+impl<W: fmt::Write> fmt::Write for Column<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.len += text.chars().count();
+        self.out.write_str(text)
+    }
+}
+
+/// Splits a nestest.log style line into named fields.
+///
+/// Only the fields present in the line are returned, so logs without the PPU or
+/// cycle columns can still be compared on the rest.
+pub fn parse_log_line(line: &str) -> Option<Vec<(&'static str, String)>> {
+    let regs_start = line.find(" A:")?;
+    let (head, regs) = line.split_at(regs_start);
+
+    let mut tokens = head.split_whitespace();
+    let pc = tokens.next()?;
+    let mut bytes = vec![];
+    let mut disassembly = vec![];
+
+    for token in tokens {
+        if disassembly.is_empty() && token.len() == 2 && u8::from_str_radix(token, 16).is_ok() {
+            bytes.push(token.to_ascii_uppercase());
+        } else {
+            disassembly.push(token);
+        }
+    }
+
+    let mut fields = vec![
+        ("PC", pc.to_ascii_uppercase()),
+        ("bytes", bytes.join(" ")),
+        ("disassembly", disassembly.join(" ")),
+    ];
+
+    for name in ["A", "X", "Y", "P", "SP"] {
+        let value = regs.split_whitespace().find_map(|token| token.strip_prefix(name)?.strip_prefix(':'))?;
+        fields.push((name, value.to_ascii_uppercase()));
+    }
+
+    if let Some(ppu) = regs.split("PPU:").nth(1) {
+        let ppu = ppu.split("CYC").next().unwrap_or("");
+        let mut parts = ppu.split(',').map(|part| part.trim().parse::<usize>());
+
+        if let (Some(Ok(scanline)), Some(Ok(dot))) = (parts.next(), parts.next()) {
+            fields.push(("PPU", format!("{scanline},{dot}")));
+        }
+    }
+
+    if let Some(cycle) = regs.split("CYC:").nth(1) {
+        fields.push(("CYC", cycle.split_whitespace().next().unwrap_or("").to_string()));
+    }
+
+    Some(fields)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// The first line where the emulator stopped agreeing with a golden log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+    pub fields: Vec<FieldDiff>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "trace diverges at line {}", self.line)?;
+        writeln!(f, "  expected: {}", self.expected)?;
+        writeln!(f, "  actual:   {}", self.actual)?;
+
+        for diff in &self.fields {
+            writeln!(f, "  {}: expected {}, got {}", diff.field, diff.expected, diff.actual)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Steps `nes` one instruction per line of `golden` and stops at the first mismatch.
+///
+/// If the first line starts somewhere other than the current program counter, the
+/// CPU jumps there first, the way nestest is run in automated mode from `$C000`.
+/// Returns the number of lines that matched.
+pub fn compare_log(nes: &mut Nes, golden: &str) -> Result<usize, Divergence> {
+    let mut matched = 0;
+
+    for (index, line) in golden.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(expected) = parse_log_line(line) else {
+            return Err(Divergence {
+                line: index + 1,
+                expected: line.to_string(),
+                actual: String::from("<unparseable golden line>"),
+                fields: vec![],
+            });
+        };
+
+        if matched == 0
+            && let Some(pc) = expected.iter().find(|(name, _)| *name == "PC").and_then(|(_, pc)| u16::from_str_radix(pc, 16).ok())
+        {
+            nes.cpu_mut().counter = pc;
+        }
+
+        if !nes.is_running() {
+            return Err(Divergence {
+                line: index + 1,
+                expected: line.to_string(),
+                actual: String::from("<CPU halted>"),
+                fields: vec![],
+            });
+        }
+
+        let entry = nes.trace_entry();
+        let actual = entry.fields();
+
+        let fields: Vec<FieldDiff> = expected
+            .iter()
+            .filter_map(|(name, want)| {
+                let got = &actual.iter().find(|(field, _)| field == name)?.1;
+                (got != want).then(|| FieldDiff { field: name, expected: want.clone(), actual: got.clone() })
+            })
+            .collect();
+
+        if !fields.is_empty() {
+            return Err(Divergence {
+                line: index + 1,
+                expected: line.to_string(),
+                actual: entry.to_string(),
+                fields,
+            });
+        }
+
+        nes.clock();
+        matched += 1;
+    }
+
+    Ok(matched)
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, Write};
+
+use crate::trace::TraceEntry;
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Traces `count` instructions every time execution reaches `pc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceTrigger {
+    pub pc: u16,
+    pub count: u32,
+}
+
+/// Decides which instructions get traced and keeps the most recent ones.
+///
+/// Entries land in a ring buffer the frontend drains in bulk. Native builds can
+/// also stream every entry to a writer, which sees them even after the ring
+/// buffer has dropped them.
+pub struct Tracer {
+    enabled: bool,
+    pc_range: Option<(u16, u16)>,
+    bank: Option<u16>,
+    start_cycle: u64,
+    trigger: Option<TraceTrigger>,
+    /// Instructions left to trace since the trigger last fired.
+    triggered: u32,
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+    #[cfg(not(target_arch = "wasm32"))]
+    stream: Option<Box<dyn Write>>,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            pc_range: None,
+            bank: None,
+            start_cycle: 0,
+            trigger: None,
+            triggered: 0,
+            capacity: DEFAULT_CAPACITY,
+            entries: VecDeque::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            stream: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.enabled = false;
+        self.triggered = 0;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Only trace instructions whose PC lies in `start..=end`.
+    pub fn set_pc_range(&mut self, range: Option<(u16, u16)>) {
+        self.pc_range = range;
+    }
+
+    /// Only trace instructions running from this 16 KiB PRG bank, which leaves out code in RAM.
+    pub fn set_bank(&mut self, bank: Option<u16>) {
+        self.bank = bank;
+    }
+
+    pub fn has_bank_filter(&self) -> bool {
+        self.bank.is_some()
+    }
+
+    /// Ignore everything before the CPU has run `cycle` cycles.
+    pub fn set_start_cycle(&mut self, cycle: u64) {
+        self.start_cycle = cycle;
+    }
+
+    /// Trace only the instructions after `trigger.pc` is reached, rearming once they're done.
+    pub fn set_trigger(&mut self, trigger: Option<TraceTrigger>) {
+        self.trigger = trigger;
+        self.triggered = 0;
+    }
+
+    /// Most entries kept, the oldest go first.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Takes every buffered entry, oldest first.
+    pub fn drain(&mut self) -> Vec<TraceEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    /// Also writes every traced entry to `stream` as a nestest.log line.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_stream(&mut self, stream: Option<Box<dyn Write>>) {
+        self.stream = stream;
+    }
+
+    /// Flushes the stream, if there is one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.stream.as_mut().map_or(Ok(()), |stream| stream.flush())
+    }
+
+    /// Whether the instruction at `pc` should be traced. `bank` only needs to be
+    /// looked up when there is a bank filter.
+    ///
+    /// Runs once per instruction while tracing, and counts towards the trigger.
+    pub fn wants(&mut self, pc: u16, bank: Option<u16>, cycle: u64) -> bool {
+        if !self.enabled || cycle < self.start_cycle {
+            return false;
+        }
+
+        if let Some(trigger) = self.trigger {
+            if self.triggered == 0 && pc == trigger.pc {
+                self.triggered = trigger.count;
+            }
+
+            if self.triggered == 0 {
+                return false;
+            }
+
+            self.triggered -= 1;
+        }
+
+        let in_range = self.pc_range.is_none_or(|(start, end)| (start..=end).contains(&pc));
+        in_range && (self.bank.is_none() || self.bank == bank)
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(stream) = self.stream.as_mut() {
+            // a full disk shouldn't stop the emulator, the ring buffer still has the entry
+            let _ = writeln!(stream, "{entry}");
+        }
+
+        self.entries.push_back(entry);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,105 @@
+//! The Code/Data Logger marks how a hand-assembled program uses its ROM.
+
+mod common;
+
+use common::{rom_with, START};
+use nest::CodeDataLog;
+
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xA9, 0x00]),       // LDA #$00
+    (0x8002, &[0xAD, 0x00, 0x90]), // LDA $9000
+    (0x8005, &[0xA9, 0x20]),       // LDA #$20
+    (0x8007, &[0x85, 0x00]),       // STA $00
+    (0x8009, &[0xA9, 0x90]),       // LDA #$90
+    (0x800B, &[0x85, 0x01]),       // STA $01
+    (0x800D, &[0xA0, 0x00]),       // LDY #$00
+    (0x800F, &[0xB1, 0x00]),       // LDA ($00),Y
+    (0x8011, &[0x6C, 0x10, 0x90]), // JMP ($9010)
+    (0x8020, &[0xAD, 0x07, 0x20]), // LDA $2007
+    (0x8023, &[0x4C, 0x23, 0x80]), // JMP $8023
+    (0x9010, &[0x20, 0x80]),       // .word $8020
+];
+
+#[test]
+fn marks_how_prg_is_used() {
+    let mut nes = common::boot(rom_with(PROGRAM, START));
+    nes.cdl_start();
+    // again, now that the logger can see the reset vector being read
+    nes.reset();
+    nes.run_frame();
+
+    let cdl = nes.cdl().unwrap();
+    let prg = &cdl.prg;
+    assert_eq!(prg.len(), 0x4000);
+
+    assert_eq!(prg[0x0000], CodeDataLog::OPCODE | CodeDataLog::CODE);
+    assert_eq!(prg[0x0001], CodeDataLog::CODE);
+    assert_eq!(prg[0x1000], CodeDataLog::DATA);
+    assert_eq!(prg[0x1020], CodeDataLog::DATA | CodeDataLog::INDIRECT_DATA);
+    assert_eq!(prg[0x1010], CodeDataLog::DATA);
+    assert_eq!(prg[0x0020], CodeDataLog::OPCODE | CodeDataLog::CODE | CodeDataLog::INDIRECT_CODE);
+    assert_eq!(prg[0x0026], 0, "never reached");
+
+    // the reset vector, read through $FFFC in the last 8 KiB window
+    assert_eq!(prg[0x3FFC], CodeDataLog::DATA | CodeDataLog::WINDOW);
+}
+
+#[test]
+fn marks_how_chr_is_used() {
+    let mut nes = common::boot(rom_with(PROGRAM, START));
+    nes.cdl_start();
+    nes.run_frame();
+
+    let cdl = nes.cdl().unwrap();
+    // tile 0 is the only one the blank nametable draws, and `$2007` was read at $0000
+    assert_eq!(cdl.chr[0x0000], CodeDataLog::DRAWN | CodeDataLog::READ);
+    assert_eq!(cdl.chr[0x0001], CodeDataLog::DRAWN);
+    assert_eq!(cdl.chr[0x0010], 0);
+
+    let summary = cdl.summary();
+    assert_eq!((summary.drawn, summary.read, summary.chr_unused), (16, 1, 0x2000 - 16));
+}
+
+#[test]
+fn saves_fceux_files() {
+    let mut nes = common::boot(rom_with(PROGRAM, START));
+    nes.cdl_start();
+    nes.run_frame();
+
+    let saved = nes.cdl_save();
+    assert_eq!(saved.len(), 0x4000 + 0x2000);
+    assert_eq!(saved[0], CodeDataLog::CODE, "FCEUX has no opcode bit");
+
+    let mut other = common::boot(rom_with(PROGRAM, START));
+    other.cdl_start();
+    other.cdl_load(&saved).unwrap();
+    assert_eq!(other.cdl_save(), saved);
+    assert_eq!(other.cdl_load(&saved[1..]).unwrap_err().to_string(), "CDL file is 24575 bytes, but this ROM needs 24576");
+}
+
+#[test]
+fn stops_and_clears() {
+    let mut nes = common::boot(rom_with(PROGRAM, START));
+    nes.cdl_start();
+    nes.cdl_stop();
+    assert!(!nes.cdl_is_logging());
+    nes.cdl_clear();
+    nes.run_frame();
+
+    assert!(nes.cdl_save().iter().all(|&flags| flags == 0));
+    assert!(nes.cdl_summary().unwrap().starts_with("PRG: 0 code, 0 data, 16384 of 16384 unused"));
+}
+
+#[test]
+fn keeps_logging_across_a_power_cycle() {
+    let mut nes = common::boot(rom_with(PROGRAM, START));
+    nes.cdl_start();
+    nes.power_on();
+    assert!(nes.cdl_is_logging());
+    nes.run_frame();
+    assert_eq!(nes.cdl().unwrap().prg[0x0020], CodeDataLog::OPCODE | CodeDataLog::CODE | CodeDataLog::INDIRECT_CODE);
+
+    nes.cdl_stop();
+    nes.power_on();
+    assert!(!nes.cdl_is_logging(), "a stopped logger stays stopped");
+}
@@ -0,0 +1,135 @@
+//! Game Genie codes patch ROM reads, RAM codes freeze bytes every frame.
+
+mod common;
+
+use common::chr_ram_rom;
+use nest::{Cheat, CheatKind, MemoryDomain};
+
+/// Copies the byte at `$9000` to `$00` forever.
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xAD, 0x00, 0x90]), // LDA $9000
+    (0x8003, &[0x85, 0x00]),       // STA $00
+    (0x8005, &[0x4C, 0x00, 0x80]), // JMP $8000
+    (0x9000, &[0x11]),
+];
+
+/// The Game Genie code for `value` at `addr`, 8 letters when there's a compare byte.
+fn game_genie(addr: u16, value: u8, compare: Option<u8>) -> String {
+    let (a, v) = (addr, value as u16);
+    let c = compare.unwrap_or(0) as u16;
+    let mut n = vec![
+        (v & 7) | (v >> 4 & 8),
+        (v >> 4 & 7) | (a >> 4 & 8),
+        (a >> 4 & 7) | if compare.is_some() { 8 } else { 0 },
+        (a >> 12 & 7) | (a & 8),
+        (a & 7) | (a >> 8 & 8),
+        (a >> 8 & 7) | if compare.is_some() { c & 8 } else { v & 8 },
+    ];
+    if compare.is_some() {
+        n.extend([(c & 7) | (c >> 4 & 8), (c >> 4 & 7) | (v & 8)]);
+    }
+
+    n.into_iter().map(|n| b"APZLGITYEOXUKSVN"[n as usize] as char).collect()
+}
+
+#[test]
+fn decodes_codes() {
+    let cheat = Cheat::decode("sxio-po").unwrap();
+    assert_eq!((cheat.kind, cheat.addr, cheat.value, cheat.compare), (CheatKind::RomPatch, 0x91D9, 0xAD, None));
+    assert_eq!(cheat.code, "SXIOPO");
+
+    let code = game_genie(0xF00F, 0x9E, Some(0xC5));
+    let cheat = Cheat::decode(&code).unwrap();
+    assert_eq!((cheat.addr, cheat.value, cheat.compare), (0xF00F, 0x9E, Some(0xC5)));
+
+    let cheat = Cheat::decode("0075:09").unwrap();
+    assert_eq!((cheat.kind, cheat.addr, cheat.value), (CheatKind::Freeze, 0x0075, 0x09));
+    assert_eq!(Cheat::decode("07FF:A0").unwrap().addr, 0x07FF);
+
+    let cheat = Cheat::decode("0000 7509").unwrap();
+    assert_eq!((cheat.kind, cheat.addr, cheat.value), (CheatKind::Freeze, 0x0075, 0x09));
+    assert_eq!(Cheat::decode("01007509").unwrap_err().reason, "only Pro Action Replay codes starting with 00 freeze RAM");
+
+    // without a colon, six letters are Game Genie even when they're also hex
+    assert_eq!(Cheat::decode("AEAEAE").unwrap().kind, CheatKind::RomPatch);
+    assert_eq!(Cheat::decode("07FFA0").unwrap_err().reason, "not a Game Genie, Pro Action Replay or RAM code like 0075:09");
+
+    assert_eq!(Cheat::decode("SXIOP").unwrap_err().to_string(), "cheat `SXIOP`: Game Genie codes are 6 or 8 letters");
+    assert_eq!(Cheat::decode("6000:01").unwrap_err().reason, "RAM codes can only freeze $0000-$1FFF");
+    assert_eq!(Cheat::decode("0075:1FF").unwrap_err().reason, "the value isn't a hex byte");
+}
+
+#[test]
+fn patches_rom_reads() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    let id = nes.add_cheat(&game_genie(0x9000, 0x42, None)).unwrap();
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x42));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x9000), Some(0x42));
+
+    assert!(nes.enable_cheat(id, false));
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x11));
+
+    // the compare byte keeps the patch off ROM that doesn't hold it
+    nes.add_cheat(&game_genie(0x9000, 0x55, Some(0x10))).unwrap();
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x11));
+
+    nes.add_cheat(&game_genie(0x9000, 0x66, Some(0x11))).unwrap();
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x66));
+
+    assert!(nes.remove_cheat(3));
+    assert!(!nes.remove_cheat(3));
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x11));
+}
+
+#[test]
+fn freezes_ram_every_frame() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.add_cheat("0810:07").unwrap();
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(0x07), "through the mirror");
+
+    nes.poke(MemoryDomain::SystemRam, 0x10, 0x00);
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(0x07));
+
+    nes.clear_cheats();
+    nes.poke(MemoryDomain::SystemRam, 0x10, 0x00);
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(0x00));
+}
+
+#[test]
+fn cheats_are_saved_in_save_states() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.add_cheat("SXIOPO").unwrap();
+    let id = nes.add_cheat("0075:09").unwrap();
+    nes.enable_cheat(id, false);
+    let saved = nes.save_state();
+
+    nes.clear_cheats();
+    nes.load_state(&saved).unwrap();
+    assert_eq!(nes.list_cheats(), "1 on SXIOPO: $91D9 = $AD\n2 off 0075:09: freeze $0075 = $09\n");
+    assert_eq!(nes.add_cheat("0076:01"), Ok(3), "ids carry on");
+
+    let mut other = common::boot(chr_ram_rom(PROGRAM));
+    other.load_state(&saved).unwrap();
+    assert_eq!(other.cheats().list(), &nes.cheats().list()[..2]);
+}
+
+#[test]
+fn cheats_survive_a_power_cycle() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.add_cheat(&game_genie(0x9000, 0x42, None)).unwrap();
+    nes.add_cheat("0810:07").unwrap();
+
+    nes.power_on();
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x00), Some(0x42));
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(0x07));
+    assert_eq!(nes.cheats().list().len(), 2);
+}
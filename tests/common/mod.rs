@@ -0,0 +1,69 @@
+//! Hand-assembled NROM images and the bundled test ROMs for the integration tests and
+//! benches, booting them, and looking at RAM afterwards.
+
+// each test crate uses its own share of this
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use nest::{Nes, NullHost};
+
+/// NMI, reset and IRQ vectors for a program that starts at `$8000` and takes no interrupts.
+pub const START: [u16; 3] = [0x0000, 0x8000, 0x0000];
+
+/// An iNES image with `prg_banks` 16 KiB banks of PRG ROM and `chr_banks` 8 KiB banks of
+/// zeroed CHR ROM, none meaning CHR RAM. `flags` is the rest of the header from byte 6 on.
+/// Each piece of `program` goes at its CPU address counted from `$8000`, and the NMI,
+/// reset and IRQ `vectors` take the last six bytes of PRG.
+pub fn ines(prg_banks: u8, chr_banks: u8, flags: &[u8], program: &[(u16, &[u8])], vectors: [u16; 3]) -> Vec<u8> {
+    let mut prg = vec![0u8; prg_banks as usize * 0x4000];
+    for (addr, bytes) in program {
+        let at = (addr - 0x8000) as usize;
+        prg[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+    let top = prg.len() - 6;
+    for (slot, vector) in vectors.iter().enumerate() {
+        prg[top + 2 * slot..top + 2 * slot + 2].copy_from_slice(&vector.to_le_bytes());
+    }
+
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, prg_banks, chr_banks];
+    rom.extend(flags);
+    rom.resize(16, 0);
+    rom.extend(prg);
+    rom.resize(rom.len() + chr_banks as usize * 0x2000, 0);
+    rom
+}
+
+/// NROM-128 with 8 KiB of CHR ROM, running `program`.
+pub fn rom_with(program: &[(u16, &[u8])], vectors: [u16; 3]) -> Vec<u8> {
+    ines(1, 1, &[], program, vectors)
+}
+
+/// NROM-128 with CHR RAM, running `program` from `$8000`.
+pub fn chr_ram_rom(program: &[(u16, &[u8])]) -> Vec<u8> {
+    ines(1, 0, &[], program, START)
+}
+
+/// The bundled ROMs, golden logs and expectations.
+pub fn test_roms() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_roms")
+}
+
+/// One of the ROMs in `test_roms/`.
+pub fn test_rom(name: &str) -> Vec<u8> {
+    fs::read(test_roms().join(name)).unwrap()
+}
+
+/// `rom` on a machine with nothing to report to, through its reset sequence.
+pub fn boot(rom: Vec<u8>) -> Nes {
+    let mut nes = Nes::with_host(rom, Rc::new(NullHost)).unwrap();
+    nes.reset();
+    nes
+}
+
+/// The byte at `addr` in the console's 2 KiB of RAM.
+pub fn ram(nes: &Nes, addr: usize) -> u8 {
+    nes.cpu().bus.ram.contents()[addr]
+}
@@ -0,0 +1,178 @@
+//! Breakpoints, watchpoints and stepping against a small hand-assembled program.
+
+mod common;
+
+use common::chr_ram_rom;
+use nest::{Access, BreakKind, BreakReason, Nes};
+
+/// `main` calls `outer`, which calls `inner`, then pokes RAM and `$2007` and loops. It runs
+/// on CHR RAM, which `$2007` can write.
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xA2, 0x00]),       // LDX #$00
+    (0x8002, &[0x20, 0x30, 0x80]), // main: JSR outer
+    (0x8005, &[0xE8]),             // INX
+    (0x8006, &[0x8E, 0x00, 0x03]), // STX $0300
+    (0x8009, &[0xAD, 0x07, 0x20]), // LDA $2007
+    (0x800C, &[0x8D, 0x07, 0x20]), // STA $2007
+    (0x800F, &[0x4C, 0x02, 0x80]), // JMP main
+    (0x8030, &[0xA9, 0x10]),       // outer: LDA #$10
+    (0x8032, &[0x20, 0x40, 0x80]), // JSR inner
+    (0x8035, &[0x60]),             // RTS
+    (0x8040, &[0xC8]),             // inner: INY
+    (0x8041, &[0x60]),             // RTS
+];
+
+fn pc(nes: &Nes) -> u16 {
+    nes.cpu().counter
+}
+
+#[test]
+fn stops_before_an_execute_breakpoint() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    let id = nes.add_breakpoint(BreakKind::Execute, 0x8005, 0x8005, "").unwrap();
+
+    nes.run_frame();
+    assert!(nes.is_paused());
+    assert_eq!(pc(&nes), 0x8005);
+    assert_eq!(nes.cpu().reg_x, 0);
+    assert_eq!(nes.debugger().reason(), Some(BreakReason::Breakpoint { id, pc: 0x8005 }));
+
+    // nothing runs while paused
+    assert_eq!(nes.run_frame(), 0);
+    assert_eq!(nes.clock(), 0);
+    assert_eq!(pc(&nes), 0x8005);
+
+    // resuming runs past the breakpoint it stopped on, and around the loop into it again
+    nes.resume();
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8005);
+    assert_eq!(nes.cpu().reg_x, 1);
+
+    assert!(nes.enable_breakpoint(id, false));
+    nes.resume();
+    assert!(nes.run_frame() > 0);
+    assert!(!nes.is_paused());
+}
+
+#[test]
+fn conditions_gate_breakpoints() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.add_breakpoint(BreakKind::Execute, 0x8005, 0x8005, "A == 0x10 && X > 3").unwrap();
+
+    nes.run_frame();
+    assert!(nes.is_paused());
+    assert_eq!(pc(&nes), 0x8005);
+    assert_eq!(nes.cpu().reg_x, 4);
+}
+
+#[test]
+fn rejects_bad_conditions() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+
+    let err = nes.add_breakpoint(BreakKind::Execute, 0x8000, 0x8000, "A == ").unwrap_err();
+    assert_eq!(err.position, 4);
+
+    let err = nes.add_breakpoint(BreakKind::Execute, 0x8000, 0x8000, "Q > 1").unwrap_err();
+    assert_eq!(err.to_string(), "condition error at column 1: unknown name `Q`");
+
+    assert!(nes.debugger().breakpoints().is_empty());
+}
+
+#[test]
+fn watches_cpu_writes_and_register_reads() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    let write = nes.add_breakpoint(BreakKind::CpuWrite, 0x0300, 0x0300, "value == 2").unwrap();
+
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8009, "stops once the writing instruction is done");
+    assert_eq!(
+        nes.debugger().reason(),
+        Some(BreakReason::Watchpoint { id: write, kind: BreakKind::CpuWrite, access: Access { addr: 0x0300, value: 2, write: true } })
+    );
+
+    nes.remove_breakpoint(write);
+    let read = nes.add_breakpoint(BreakKind::CpuRead, 0x2007, 0x2007, "").unwrap();
+    nes.resume();
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x800C);
+    assert!(matches!(nes.debugger().reason(), Some(BreakReason::Watchpoint { id, access, .. }) if id == read && access.addr == 0x2007));
+
+    nes.clear_breakpoints();
+    assert!(nes.cpu().bus.accesses.is_none(), "stops logging once nothing watches");
+}
+
+#[test]
+fn watches_the_ppu_bus() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.add_breakpoint(BreakKind::PpuWrite, 0x0000, 0x1FFF, "").unwrap();
+
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x800F);
+    assert!(matches!(nes.debugger().reason(), Some(BreakReason::Watchpoint { kind: BreakKind::PpuWrite, access, .. }) if access.addr == 0 && access.write));
+    assert_eq!(nes.break_reason().unwrap(), format!("watchpoint 1: PPU write $0000 = ${:02X}", nes.cpu().reg_a));
+}
+
+#[test]
+fn steps_into_over_and_out_of_calls() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.pause();
+
+    nes.step_into();
+    assert_eq!(pc(&nes), 0x8002);
+    nes.step_into();
+    assert_eq!(pc(&nes), 0x8030, "steps into the JSR");
+    nes.step_into();
+    nes.step_into();
+    assert_eq!(pc(&nes), 0x8040);
+
+    nes.step_out();
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8035, "stops right after inner's RTS");
+    assert_eq!(nes.debugger().reason(), Some(BreakReason::Step));
+
+    nes.step_into();
+    assert_eq!(pc(&nes), 0x8005);
+    let y = nes.cpu().reg_y;
+
+    // around the loop back to the call
+    for _ in 0..5 {
+        nes.step_over();
+    }
+    assert_eq!(pc(&nes), 0x8002);
+
+    nes.step_over();
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8005);
+    assert_eq!(nes.cpu().reg_y, y.wrapping_add(1));
+    assert!(nes.is_paused());
+}
+
+#[test]
+fn step_out_skips_nested_returns() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.run_to(0x8030);
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8030);
+
+    nes.step_out();
+    nes.run_frame();
+    assert_eq!(pc(&nes), 0x8005);
+}
+
+#[test]
+fn breakpoints_interrupt_steps() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.run_to(0x8002);
+    nes.run_frame();
+
+    let id = nes.add_breakpoint(BreakKind::Execute, 0x8040, 0x8040, "").unwrap();
+    nes.step_over();
+    nes.run_frame();
+    assert_eq!(nes.debugger().reason(), Some(BreakReason::Breakpoint { id, pc: 0x8040 }));
+
+    // the step over was cancelled, so nothing stops at its return address
+    nes.remove_breakpoint(id);
+    nes.resume();
+    assert!(nes.run_frame() > 0);
+    assert!(!nes.is_paused());
+}
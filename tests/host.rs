@@ -0,0 +1,45 @@
+//! Everything the machine tells its host goes through the `Host` trait, so a native host sees it all.
+
+mod common;
+
+use std::rc::Rc;
+
+use common::{rom_with, START};
+use nest::{Nes, RecordingHost};
+
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xA9, 0xF0]),       // LDA #$F0
+    (0x8002, &[0x49, 0x0F]),       // EOR #$0F
+    (0x8004, &[0x85, 0x10]),       // STA $10
+    (0x8006, &[0x4C, 0x06, 0x80]), // JMP $8006
+];
+
+#[test]
+fn records_every_callback() {
+    let host = Rc::new(RecordingHost::default());
+    let rom = rom_with(PROGRAM, START);
+    let mut nes = Nes::with_host(rom.clone(), host.clone()).unwrap();
+
+    assert_eq!(host.prg_rom.borrow().as_slice(), &rom[16..16 + 0x4000]);
+    assert_eq!(host.chr_rom.borrow().len(), 0x2000);
+    assert!(host.logs.borrow()[0].starts_with("Sizes PRG 16384 CHR 8192"));
+
+    nes.reset();
+    assert!(host.logs.borrow().iter().any(|log| log.starts_with("CPU Registers:")));
+
+    for _ in 0..3 {
+        nes.clock();
+    }
+    assert_eq!(host.ram.borrow()[0x10], 0xFF);
+
+    let rows = host.tracelog.borrow();
+    let inst: Vec<_> = rows.iter().map(|row| row.inst.as_str()).collect();
+    assert_eq!(inst, ["LDA #$F0", "EOR #$0F", "STA $10"]);
+    assert_eq!(rows[1].pg, "8002");
+    assert_eq!(rows[2].reg_a, "FF");
+    drop(rows);
+
+    nes.run_frame();
+    assert_eq!(*host.screen_draws.borrow(), 1);
+    assert_eq!(host.vram.borrow().len(), 0x800);
+}
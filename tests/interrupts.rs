@@ -0,0 +1,117 @@
+//! IRQ and NMI are taken on the cycle the 6502 polls them, quirks included.
+
+mod common;
+
+use common::{boot, ram};
+use nest::{IrqLine, IrqSource};
+
+/// An NROM image with `main` at `$8000`, `nmi` at `$8100` and `irq` at `$8200`.
+fn rom_with(main: &[u8], nmi: &[u8], irq: &[u8]) -> Vec<u8> {
+    common::rom_with(&[(0x8000, main), (0x8100, nmi), (0x8200, irq)], [0x8100, 0x8000, 0x8200])
+}
+
+const IRQ_COPIES_02_TO_03: &[u8] = &[0xA5, 0x02, 0x85, 0x03, 0x4C, 0x04, 0x82]; // LDA $02, STA $03, JMP *
+
+#[test]
+fn irq_waits_one_instruction_after_cli() {
+    // SEI, CLI, INC $02, JMP *
+    let mut nes = boot(rom_with(&[0x78, 0x58, 0xE6, 0x02, 0x4C, 0x04, 0x80], &[], IRQ_COPIES_02_TO_03));
+    nes.cpu_mut().bus.irq.assert(IrqSource::External);
+
+    assert_eq!(nes.clock(), 2); // SEI
+    assert_eq!(nes.clock(), 2); // CLI
+    assert_eq!(nes.clock(), 5); // INC $02 still runs
+    assert_eq!(nes.clock(), 7); // then the IRQ
+    assert_eq!(nes.cpu().counter, 0x8200);
+
+    nes.run_cycles(20);
+    assert_eq!(ram(&nes, 3), 1);
+}
+
+#[test]
+fn irq_still_fires_right_after_sei() {
+    // CLI, NOP, SEI, INC $02, JMP *
+    let mut nes = boot(rom_with(&[0x58, 0xEA, 0x78, 0xE6, 0x02, 0x4C, 0x05, 0x80], &[], IRQ_COPIES_02_TO_03));
+
+    nes.clock(); // CLI
+    nes.clock(); // NOP
+    nes.cpu_mut().bus.irq.assert(IrqSource::External);
+
+    assert_eq!(nes.clock(), 2); // SEI
+    assert_eq!(nes.clock(), 7); // the IRQ polled before SEI took effect
+    assert_eq!(nes.cpu().counter, 0x8200);
+
+    // the status pushed has I set, because SEI had already run, and B clear
+    assert_eq!(ram(&nes, 0x1FB) & 0x14, 0x04);
+    nes.run_cycles(20);
+    assert_eq!(ram(&nes, 3), 0);
+}
+
+#[test]
+fn irq_is_masked_while_i_is_set() {
+    // INC $02, JMP $8000
+    let mut nes = boot(rom_with(&[0xE6, 0x02, 0x4C, 0x00, 0x80], &[], IRQ_COPIES_02_TO_03));
+    nes.cpu_mut().bus.irq.assert(IrqSource::External);
+
+    nes.run_cycles(100);
+    assert!(nes.cpu().counter < 0x8100);
+}
+
+#[test]
+fn sources_share_the_line() {
+    let mut line = IrqLine::default();
+    line.assert(IrqSource::Mapper);
+    line.assert(IrqSource::FrameCounter);
+    line.release(IrqSource::Mapper);
+
+    assert!(line.is_asserted());
+
+    line.release(IrqSource::FrameCounter);
+    assert!(!line.is_asserted());
+}
+
+#[test]
+fn nmi_hijacks_a_brk_in_progress() {
+    // enable NMI, burn ~27000 cycles so vblank lands in the NOP sled at $0200, then run it
+    let main = [
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+        0xA2, 0x15, //                   LDX #21
+        0xA0, 0x00, //                   outer: LDY #0
+        0x88, 0xD0, 0xFD, //             inner: DEY, BNE inner
+        0xCA, 0xD0, 0xF8, //             DEX, BNE outer
+        0x4C, 0x00, 0x02, //             JMP $0200
+    ];
+    // copy the pushed status to $12, count in $11, stop further NMIs and spin
+    let nmi = [0x68, 0x48, 0x29, 0x10, 0x85, 0x12, 0xE6, 0x11, 0xA9, 0x00, 0x8D, 0x00, 0x20, 0x4C, 0x0D, 0x81];
+    // count BRKs that went through the IRQ vector in $10 and spin
+    let irq = [0xE6, 0x10, 0x4C, 0x02, 0x82];
+
+    let mut nes = boot(rom_with(&main, &nmi, &irq));
+    let mut sled = vec![0u8; 0x800];
+    sled[0x200..0x7F0].fill(0xEA);
+    nes.cpu_mut().bus.ram.set_contents(&sled);
+
+    while nes.cpu().counter != 0x0200 {
+        nes.clock();
+    }
+    let start = nes.save_state();
+    assert!(nes.ppu().scanline < 241, "the sled has to start before vblank");
+
+    let mut hijacked = vec![];
+    for offset in 0..0x200 {
+        nes.load_state(&start).unwrap();
+        let mut patched = nes.cpu().bus.ram.contents().to_vec();
+        patched[0x200 + offset] = 0x00;
+        nes.cpu_mut().bus.ram.set_contents(&patched);
+
+        nes.run_cycles(1000);
+
+        if ram(&nes, 0x12) == 0x10 {
+            assert_eq!(ram(&nes, 0x10), 0, "a hijacked BRK never reaches the IRQ vector");
+            hijacked.push(offset);
+        }
+    }
+
+    // an NMI edge from the NOP's last cycle up to BRK's fourth cycle is a hijack
+    assert!(!hijacked.is_empty() && hijacked.len() <= 3, "hijacked at {hijacked:?}");
+}
@@ -0,0 +1,101 @@
+//! Memory domains read without side effects and write straight into memory.
+
+mod common;
+
+use common::test_rom;
+use nest::{BreakKind, MemoryDomain};
+
+#[test]
+fn sizes_follow_the_cartridge() {
+    let nes = common::boot(test_rom("7_Graphics.nes"));
+
+    let sizes: Vec<usize> = MemoryDomain::ALL.iter().map(|&domain| nes.memory_size(domain)).collect();
+    assert_eq!(sizes, [0x10000, 0x800, 0x4000, 0x800, 0x20, 0x100, 0x8000, 0x2000, 0]);
+
+    // no CHR ROM, so the CHR domain is the PPU's CHR RAM
+    assert_eq!(common::boot(test_rom("6_Instructions2.nes")).memory_size(MemoryDomain::Chr), 0x2000);
+}
+
+#[test]
+fn peeking_registers_has_no_side_effects() {
+    let mut nes = common::boot(test_rom("7_Graphics.nes"));
+    nes.run_frame();
+    nes.run_until_vblank();
+    assert!(nes.ppu().status_flags.v_blank);
+
+    let vram_address = nes.ppu().vram_address;
+    let status = nes.peek(MemoryDomain::CpuBus, 0x2002).unwrap();
+    let data = nes.peek(MemoryDomain::CpuBus, 0x2007).unwrap();
+
+    assert_eq!(status & 0x80, 0x80);
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x2002), Some(status), "vblank is still set");
+    assert!(nes.ppu().status_flags.v_blank);
+    assert_eq!(nes.ppu().vram_address, vram_address, "the VRAM address didn't move");
+    assert_eq!(nes.peek(MemoryDomain::PpuBus, vram_address as usize), Some(data));
+}
+
+#[test]
+fn watchpoints_never_see_peeks() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.add_breakpoint(BreakKind::CpuRead, 0x0000, 0xFFFF, "").unwrap();
+    nes.add_breakpoint(BreakKind::PpuRead, 0x0000, 0x3FFF, "").unwrap();
+
+    nes.peek_range(MemoryDomain::CpuBus, 0, 0x10000);
+    nes.peek_range(MemoryDomain::PpuBus, 0, 0x4000);
+
+    assert_eq!(nes.cpu().bus.accesses.as_deref(), Some(&[][..]));
+    assert!(nes.ppu().vbus.accesses.as_ref().unwrap().is_empty());
+}
+
+#[test]
+fn pokes_show_up_through_every_view() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+
+    assert!(nes.poke(MemoryDomain::SystemRam, 0x0123, 0xAB));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x0123), Some(0xAB));
+
+    assert!(nes.poke(MemoryDomain::PrgRom, 0x4010, 0xEA));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0xC010), Some(0xEA));
+    assert!(nes.poke(MemoryDomain::CpuBus, 0x8011, 0x60));
+    assert_eq!(nes.peek(MemoryDomain::PrgRom, 0x0011), Some(0x60));
+
+    assert!(nes.poke(MemoryDomain::Nametables, 0x0005, 0x42));
+    assert_eq!(nes.peek(MemoryDomain::PpuBus, 0x2005), Some(0x42));
+
+    assert!(nes.poke(MemoryDomain::PpuBus, 0x3F10, 0xFF));
+    assert_eq!(nes.peek(MemoryDomain::Palette, 0x00), Some(0x3F), "palette entries are six bits, $3F10 mirrors $3F00");
+
+    assert!(nes.poke(MemoryDomain::Chr, 0x0100, 0x99));
+    assert_eq!(nes.peek(MemoryDomain::PpuBus, 0x0100), Some(0x99));
+
+    assert!(nes.poke(MemoryDomain::Oam, 0xFF, 0x12));
+    assert_eq!(nes.peek(MemoryDomain::Oam, 0xFF), Some(0x12));
+}
+
+#[test]
+fn stops_at_the_end_of_a_domain() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+
+    assert_eq!(nes.peek(MemoryDomain::Palette, 0x20), None);
+    assert!(!nes.poke(MemoryDomain::Palette, 0x20, 0));
+    assert_eq!(nes.peek(MemoryDomain::PrgRam, 0), None);
+    assert_eq!(nes.peek_range(MemoryDomain::Oam, 0xF0, 0x40).len(), 0x10);
+    assert!(nes.peek_range(MemoryDomain::Oam, 0x200, 0x10).is_empty());
+    assert!(nes.peek_range(MemoryDomain::PrgRam, 0, 0x10).is_empty());
+    assert_eq!(nes.peek_range(MemoryDomain::CpuBus, 0xFFFE, usize::MAX).len(), 2);
+
+    let size = nes.memory_size(MemoryDomain::PrgRom);
+    let tail: Vec<_> = (size - 4..size).map_while(|addr| nes.peek(MemoryDomain::PrgRom, addr)).collect();
+    assert_eq!(nes.peek_range(MemoryDomain::PrgRom, size - 4, 0x10), tail);
+}
+
+#[test]
+fn sprite_memory_is_saved() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.poke(MemoryDomain::Oam, 0x40, 0x77);
+    let saved = nes.save_state();
+
+    let mut other = common::boot(test_rom("6_Instructions2.nes"));
+    other.load_state(&saved).unwrap();
+    assert_eq!(other.peek(MemoryDomain::Oam, 0x40), Some(0x77));
+}
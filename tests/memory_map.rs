@@ -0,0 +1,96 @@
+//! The CPU sees RAM and the PPU registers through their mirrors, and cartridge RAM at `$6000`.
+
+mod common;
+
+use common::{ines, START};
+use nest::{MemoryDomain, Nes};
+
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xA9, 0x42]),       // LDA #$42
+    (0x8002, &[0x8D, 0x12, 0x08]), // STA $0812
+    (0x8005, &[0xAD, 0x12, 0x18]), // LDA $1812
+    (0x8008, &[0x8D, 0x00, 0x60]), // STA $6000
+    (0x800B, &[0xA9, 0x21]),       // LDA #$21
+    (0x800D, &[0x8D, 0xFE, 0x3F]), // STA $3FFE, $2006
+    (0x8010, &[0xA9, 0x05]),       // LDA #$05
+    (0x8012, &[0x8D, 0x0E, 0x20]), // STA $200E, $2006
+    (0x8015, &[0xAD, 0x00, 0x60]), // LDA $6000
+    (0x8018, &[0x8D, 0x17, 0x20]), // STA $2017, $2007
+    (0x801B, &[0xA9, 0x7D]),       // LDA #$7D
+    (0x801D, &[0x8D, 0x0D, 0x30]), // STA $300D, $2005
+    (0x8020, &[0x4C, 0x20, 0x80]), // JMP $8020
+];
+
+const INSTRUCTIONS: usize = 12;
+
+/// NROM-128 with CHR RAM. `header_tail` is the iNES header from flags 6 on.
+fn rom(header_tail: &[u8]) -> Vec<u8> {
+    ines(1, 0, header_tail, PROGRAM, START)
+}
+
+/// `rom`, run up to the `JMP`.
+fn run_to_jmp(header_tail: &[u8]) -> Nes {
+    let mut nes = common::boot(rom(header_tail));
+    for _ in 0..INSTRUCTIONS {
+        nes.clock();
+    }
+    nes
+}
+
+#[test]
+fn goes_through_the_mirrors() {
+    let nes = run_to_jmp(&[0x02]);
+
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x12), Some(0x42));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x0012), Some(0x42));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x1012), Some(0x42));
+
+    assert_eq!(nes.memory_size(MemoryDomain::PrgRam), 0x2000);
+    assert_eq!(nes.peek(MemoryDomain::PrgRam, 0), Some(0x42));
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x6000), Some(0x42));
+
+    assert_eq!(nes.peek(MemoryDomain::PpuBus, 0x2105), Some(0x42));
+
+    let ppu = nes.ppu();
+    assert_eq!(ppu.fine_x, 5);
+    assert_eq!(ppu.temp_vram_addr & 0x001F, 0x0F, "coarse X");
+    assert!(ppu.write_latch, "one `$2005` write in");
+}
+
+#[test]
+fn sizes_cartridge_ram_from_the_header() {
+    let nes = run_to_jmp(&[0x00]);
+    assert_eq!(nes.memory_size(MemoryDomain::PrgRam), 0);
+    assert_ne!(nes.peek(MemoryDomain::PpuBus, 0x2105), Some(0x42), "nothing was at $6000");
+
+    assert_eq!(run_to_jmp(&[0x00, 0x00, 0x02]).memory_size(MemoryDomain::PrgRam), 0x4000);
+
+    // a ripper's tag in the header isn't a RAM size
+    assert_eq!(run_to_jmp(&[0x00, b'D', b'i', b's', b'k', b'D', b'u', b'd', b'e', b'!']).memory_size(MemoryDomain::PrgRam), 0);
+}
+
+#[test]
+fn cartridge_ram_is_saved() {
+    let mut nes = run_to_jmp(&[0x02]);
+    let saved = nes.save_state();
+
+    nes.poke(MemoryDomain::PrgRam, 0, 0x00);
+    nes.load_state(&saved).unwrap();
+    assert_eq!(nes.peek(MemoryDomain::PrgRam, 0), Some(0x42));
+}
+
+#[test]
+fn swapping_the_rom_swaps_the_board() {
+    let mut nes = run_to_jmp(&[0x00]);
+    nes.swap_rom(rom(&[0x03]));
+    for _ in 0..INSTRUCTIONS {
+        nes.clock();
+    }
+
+    assert_eq!(nes.memory_size(MemoryDomain::PrgRam), 0x2000);
+    assert_eq!(nes.peek(MemoryDomain::PrgRam, 0), Some(0x42));
+    assert!(nes.ppu().vbus.vertical_mirror);
+
+    let saved = nes.save_state();
+    run_to_jmp(&[0x03]).load_state(&saved).expect("the same layout as a fresh boot");
+}
@@ -0,0 +1,188 @@
+//! Movies replay recorded input bit-exactly and report the frame they stop doing so.
+
+mod common;
+
+use common::{rom_with, START};
+use nest::{Movie, MovieError, MovieMode, Nes};
+
+/// An NROM image that adds controller 1's A button into `$01` and counts loops in `$00`.
+fn input_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #1, STA $4016
+        0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #0, STA $4016
+        0xAD, 0x16, 0x40, 0x29, 0x01, // LDA $4016, AND #1
+        0x18, 0x65, 0x01, 0x85, 0x01, // CLC, ADC $01, STA $01
+        0xE6, 0x00, 0x4C, 0x00, 0x80, // INC $00, JMP $8000
+    ];
+
+    rom_with(&[(0x8000, &code)], START)
+}
+
+fn run_to_frame(nes: &mut Nes, frame: usize) {
+    while nes.ppu().frame < frame {
+        nes.clock();
+    }
+}
+
+/// Records `frames` frames holding A on every third one.
+fn record(frames: usize) -> (Nes, String) {
+    let mut nes = common::boot(input_rom());
+    nes.record_movie(true);
+
+    for frame in 1..=frames {
+        run_to_frame(&mut nes, frame);
+        nes.set_buttons(0, if frame % 3 == 0 { 0x01 } else { 0x00 });
+    }
+
+    let fm2 = nes.export_movie().unwrap();
+    (nes, fm2)
+}
+
+#[test]
+fn plays_back_bit_exactly() {
+    let (recorded, fm2) = record(12);
+    let mut nes = common::boot(input_rom());
+
+    nes.play_movie(&fm2, true).unwrap();
+    run_to_frame(&mut nes, 12);
+
+    assert!(nes.movie_desyncs().is_empty(), "{}", nes.movie_desync_report());
+    assert_ne!(nes.cpu().bus.ram.contents()[1], 0);
+    assert!(nes.save_state() == recorded.save_state());
+}
+
+#[test]
+fn reports_the_frame_playback_desyncs_on() {
+    let (_, fm2) = record(12);
+    let mut movie = Movie::from_fm2(&fm2).unwrap();
+    movie.frames[7].buttons[0] = 0x80;
+
+    let mut nes = common::boot(input_rom());
+    nes.start_movie(movie, true).unwrap();
+    run_to_frame(&mut nes, 12);
+
+    let desyncs = nes.movie_desyncs();
+    assert!(!desyncs.is_empty());
+    assert_eq!(desyncs[0].frame, 7);
+    assert!(nes.movie_desync_report().starts_with("movie desynced at frame 7"));
+}
+
+#[test]
+fn loading_a_state_rerecords_unless_read_only() {
+    let mut nes = common::boot(input_rom());
+    nes.record_movie(true);
+    run_to_frame(&mut nes, 5);
+    let saved = nes.save_state();
+    run_to_frame(&mut nes, 10);
+
+    nes.load_state(&saved).unwrap();
+    assert_eq!(nes.movie_rerecords(), 1);
+    assert_eq!(nes.movie().unwrap().frames.len(), 6);
+
+    let fm2 = nes.export_movie().unwrap();
+    nes.play_movie(&fm2, true).unwrap();
+    run_to_frame(&mut nes, 3);
+    nes.load_state(&saved).unwrap();
+
+    assert_eq!(nes.movie_rerecords(), 1);
+    assert_eq!(nes.movie_mode(), Some(MovieMode::Playing { read_only: true }));
+
+    nes.play_movie(&fm2, false).unwrap();
+    nes.load_state(&saved).unwrap();
+
+    assert_eq!(nes.movie_rerecords(), 2);
+    assert_eq!(nes.movie_mode(), Some(MovieMode::Recording));
+}
+
+#[test]
+fn movies_anchored_to_a_save_state_start_from_it() {
+    let mut nes = common::boot(input_rom());
+    run_to_frame(&mut nes, 4);
+    nes.record_movie(false);
+    let anchor = nes.save_state();
+    run_to_frame(&mut nes, 8);
+
+    let fm2 = nes.export_movie().unwrap();
+    let mut other = common::boot(input_rom());
+    other.play_movie(&fm2, true).unwrap();
+
+    assert_eq!(other.ppu().frame, 4);
+    assert!(other.save_state() == anchor);
+}
+
+#[test]
+fn reads_fceux_input_lines() {
+    let fm2 = "version 3\nemuVersion 20604\nrerecordCount 7\nport0 1\nport1 1\nport2 0\n|0|R......A|........||\n|1|.L..T...|..D.....||\n";
+    let movie = Movie::from_fm2(fm2).unwrap();
+
+    assert_eq!(movie.rerecords, 7);
+    assert_eq!(movie.frames.len(), 2);
+    assert_eq!(movie.frames[0].buttons, [0x81, 0x00]);
+    assert_eq!(movie.frames[1].commands, 1);
+    assert_eq!(movie.frames[1].buttons, [0x48, 0x20]);
+    assert_eq!(Movie::from_fm2(&movie.to_fm2([0; 16])).unwrap(), movie);
+}
+
+/// A Super Mario Bros. (World) movie header laid out the way FCEUX 2.2.3 writes one, with a
+/// few frames of input. The checksum is the one FCEUX puts in every movie of that ROM.
+const FCEUX_SMB: &str = "\
+version 3
+emuVersion 22020
+rerecordCount 1711
+palFlag 0
+romFilename Super Mario Bros. (World)
+romChecksum base64:jjYwGG411HcjG/j9UOVM3Q==
+guid 7F58DC2C-FFC9-2AF8-7CA1-2DCD4D26D4B5
+fourscore 0
+microphone 0
+port0 1
+port1 1
+port2 0
+FDS 0
+NewPPU 0
+RAMInitOption 0
+RAMInitSeed 0
+|1|........|........||
+|0|........|........||
+|0|....T...|........||
+";
+
+/// Super Mario Bros.' PRG and CHR hash to 8E3630186E35D477231BF8FD50E54CDD.
+const SMB_MD5: [u8; 16] = [0x8E, 0x36, 0x30, 0x18, 0x6E, 0x35, 0xD4, 0x77, 0x23, 0x1B, 0xF8, 0xFD, 0x50, 0xE5, 0x4C, 0xDD];
+
+#[test]
+fn matches_fceux_headers() {
+    let movie = Movie::from_fm2(FCEUX_SMB).unwrap();
+    assert_eq!(movie.rom_filename, "Super Mario Bros. (World)");
+    assert_eq!(movie.rerecords, 1711);
+    assert_eq!(movie.anchor, None);
+    assert_eq!(movie.frames.len(), 3);
+
+    let fm2 = movie.to_fm2(SMB_MD5);
+    let header = |text: &str, key: &str| text.lines().find(|line| line.split(' ').next() == Some(key)).map(str::to_string);
+    for key in ["version", "rerecordCount", "romFilename", "romChecksum", "port0", "port1"] {
+        assert_eq!(header(&fm2, key), header(FCEUX_SMB, key), "{key}");
+    }
+    assert!(fm2.ends_with("|1|........|........||\n|0|........|........||\n|0|....T...|........||\n"));
+
+    let anchored = FCEUX_SMB.replace("RAMInitSeed 0\n", "RAMInitSeed 0\nsavestate base64:RkNTWA==\n");
+    assert!(matches!(Movie::from_fm2(&anchored), Err(MovieError::BadLine { line: 17, .. })), "an FCEUX state isn't a nest one");
+}
+
+#[test]
+fn exports_what_fceux_checks() {
+    let rom = include_bytes!("../test_roms/1_Example.nes").to_vec();
+    let mut nes = common::boot(rom);
+    nes.set_rom_name("1_Example.nes");
+    nes.record_movie(true);
+    let fm2 = nes.export_movie().unwrap();
+
+    assert!(fm2.contains("\nromFilename 1_Example\n"));
+    // `tail -c +17 1_Example.nes | md5sum` is 1c328e6063a02f7ff31afb06fb97f33a
+    assert!(fm2.contains("\nromChecksum base64:HDKOYGOgL3/zGvsG+5fzOg==\n"));
+
+    nes.record_movie(false);
+    let fm2 = nes.export_movie().unwrap();
+    assert!(fm2.contains("\nnestSavestate base64:"));
+    assert!(!fm2.contains("\nsavestate "));
+}
@@ -0,0 +1,102 @@
+//! The opcode table agrees with the CPU, and the disassembler reads any byte slice.
+
+mod common;
+
+use std::rc::Rc;
+
+use common::{boot, rom_with, START};
+use nest::{disassemble, AddrMode, Nes, RecordingHost, OPCODES};
+
+/// Unstable opcodes the CPU doesn't run, plus `HLT` and the branches whose timing depends on flags.
+fn skipped(opcode: usize) -> bool {
+    matches!(opcode, 0x8B | 0x93 | 0x9B | 0x9C | 0x9E | 0x9F | 0xAB | 0xBB)
+        || matches!(OPCODES[opcode].mnemonic, "HLT")
+        || OPCODES[opcode].mode == AddrMode::Relative
+}
+
+#[test]
+fn table_cycles_match_execution() {
+    for opcode in (0..256).filter(|&op| !skipped(op)) {
+        // zero operands keep every indexed access on page zero, so no penalty applies
+        let mut nes = boot(rom_with(&[(0x8000, &[opcode as u8, 0x00, 0x00])], START));
+
+        let start = nes.cpu().counter;
+        let cycles = nes.clock();
+        let info = &OPCODES[opcode];
+
+        assert_eq!(cycles, info.cycles as usize, "{opcode:02X} {}", info.mnemonic);
+
+        if !matches!(info.mnemonic, "BRK" | "JMP" | "JSR" | "RTI" | "RTS") {
+            assert_eq!(nes.cpu().counter, start + info.size() as u16, "{opcode:02X} {}", info.mnemonic);
+        }
+    }
+}
+
+#[test]
+fn tracelog_names_match_the_table() {
+    for opcode in (0..256).filter(|&op| !skipped(op) && OPCODES[op].mnemonic != "BRK") {
+        let host = Rc::new(RecordingHost::default());
+        let mut nes = Nes::with_host(rom_with(&[(0x8000, &[opcode as u8, 0x00, 0x00])], START), host.clone()).unwrap();
+        nes.reset();
+        nes.clock();
+
+        let rows = host.tracelog.borrow();
+        let mnemonic = rows[0].inst.split(' ').next().unwrap();
+        assert_eq!(mnemonic, OPCODES[opcode].mnemonic, "{opcode:02X}");
+    }
+}
+
+#[test]
+fn page_cross_penalty_is_only_on_indexed_reads() {
+    let penalised = |mnemonic: &str, mode| OPCODES.iter().find(|op| op.mnemonic == mnemonic && op.mode == mode).unwrap().page_cross;
+
+    assert!(penalised("LDA", AddrMode::AbsoluteX));
+    assert!(penalised("LAX", AddrMode::IndirectY));
+    assert!(penalised("NOP", AddrMode::AbsoluteX));
+    assert!(penalised("BNE", AddrMode::Relative));
+    assert!(!penalised("STA", AddrMode::AbsoluteY));
+    assert!(!penalised("INC", AddrMode::AbsoluteX));
+    assert!(!penalised("DCP", AddrMode::IndirectY));
+    assert!(!penalised("LDA", AddrMode::ZeroPageX));
+}
+
+#[test]
+fn disassembles_a_prg_slice() {
+    let code = [
+        0xA9, 0x10, // LDA #$10
+        0x9D, 0x00, 0x02, // STA $0200,X
+        0xB1, 0x20, // LDA ($20),Y
+        0xD0, 0xF7, // BNE back to $C000
+        0x6C, 0xFC, 0xFF, // JMP ($FFFC)
+        0x0A, // ASL A
+        0xC7, 0x30, // DCP $30
+        0x20, 0x34, // JSR cut short
+    ];
+
+    let lines: Vec<String> = disassemble(&code, 0xC000).iter().map(|ins| format!("{:04X} {ins}", ins.addr)).collect();
+
+    assert_eq!(
+        lines,
+        [
+            "C000 LDA #$10",
+            "C002 STA $0200,X",
+            "C005 LDA ($20),Y",
+            "C007 BNE $C000",
+            "C009 JMP ($FFFC)",
+            "C00C ASL A",
+            "C00D DCP $30",
+            "C00F .byte $20, $34",
+        ]
+    );
+}
+
+#[test]
+fn reports_targets_and_bytes() {
+    let listing = disassemble(&[0x20, 0x00, 0x90, 0x10, 0x02, 0x1A], 0x8000);
+
+    assert_eq!(listing[0].target(), Some(0x9000));
+    assert_eq!(listing[0].bytes()[..], [0x20, 0x00, 0x90]);
+    assert_eq!(listing[1].target(), Some(0x8007));
+    assert!(!listing[2].info().official);
+    assert!(listing.iter().all(|ins| ins.is_complete()));
+}
@@ -0,0 +1,77 @@
+//! Reads nothing answers see the last byte on the bus, and the PPU keeps a bus of its own that fades.
+
+mod common;
+
+use common::{chr_ram_rom, ram};
+use nest::MemoryDomain;
+
+#[test]
+fn unmapped_reads_see_the_address_high_byte() {
+    let mut nes = common::boot(chr_ram_rom(&[
+        (0x8000, &[0xA9, 0xFF, 0x8D, 0x03, 0x20]), // LDA #$FF, STA $2003
+        (0x8005, &[0xAD, 0x02, 0x20, 0x85, 0x00]), // LDA $2002, STA $00
+        (0x800A, &[0xAD, 0x00, 0x20, 0x85, 0x01]), // LDA $2000, STA $01
+        (0x800F, &[0xAD, 0x16, 0x40, 0x85, 0x02]), // LDA $4016, STA $02
+        (0x8014, &[0xAD, 0x18, 0x40, 0x85, 0x03]), // LDA $4018, STA $03
+        (0x8019, &[0xAD, 0x00, 0x50, 0x85, 0x04]), // LDA $5000, STA $04
+        (0x801E, &[0xAD, 0x00, 0x60, 0x85, 0x05]), // LDA $6000, STA $05
+        (0x8023, &[0x4C, 0x23, 0x80]),             // JMP $8023
+    ]));
+    nes.run_frame();
+
+    assert_eq!(ram(&nes, 0x00) & 0x1F, 0x1F, "the low bits of `$2002` are the PPU's latch");
+    assert_eq!(ram(&nes, 0x01), ram(&nes, 0x00), "write-only registers read the latch");
+    assert_eq!(ram(&nes, 0x02), 0x40, "controllers leave the top bits open");
+    assert_eq!(ram(&nes, 0x03), 0x40);
+    assert_eq!(ram(&nes, 0x04), 0x50);
+    assert_eq!(ram(&nes, 0x05), 0x60, "no cartridge RAM");
+}
+
+#[test]
+fn the_ppu_latch_fades_a_bit_at_a_time() {
+    let mut nes = common::boot(chr_ram_rom(&[
+        (0x8000, &[0xA9, 0x3F, 0x8D, 0x06, 0x20]), // LDA #$3F, STA $2006
+        (0x8005, &[0xA9, 0x00, 0x8D, 0x06, 0x20]), // LDA #$00, STA $2006
+        (0x800A, &[0xA9, 0xFF, 0x8D, 0x03, 0x20]), // LDA #$FF, STA $2003
+        (0x800F, &[0xA5, 0x10, 0xF0, 0xFC]),       // wait: LDA $10, BEQ wait
+        (0x8013, &[0xAD, 0x07, 0x20, 0x85, 0x00]), // LDA $2007, STA $00
+        (0x8018, &[0x4C, 0x18, 0x80]),             // JMP $8018
+    ]));
+    nes.poke(MemoryDomain::Palette, 0, 0x3F);
+
+    nes.run_frame();
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x2000), Some(0xFF));
+
+    for _ in 0..20 {
+        nes.run_frame();
+    }
+    nes.poke(MemoryDomain::SystemRam, 0x10, 1);
+    nes.run_frame();
+    assert_eq!(ram(&nes, 0x00), 0xFF, "palette reads fill the top two bits from the latch");
+
+    for _ in 0..20 {
+        nes.run_frame();
+    }
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x2000), Some(0x3F), "only the bits the palette read drove are left");
+
+    for _ in 0..20 {
+        nes.run_frame();
+    }
+    assert_eq!(nes.peek(MemoryDomain::CpuBus, 0x3FF8), Some(0x00));
+}
+
+#[test]
+fn the_latch_is_saved() {
+    let program: &[(u16, &[u8])] = &[
+        (0x8000, &[0xA9, 0xA5, 0x8D, 0x03, 0x20]), // LDA #$A5, STA $2003
+        (0x8005, &[0x4C, 0x05, 0x80]),             // JMP $8005
+    ];
+    let mut nes = common::boot(chr_ram_rom(program));
+    nes.run_frame();
+    let saved = nes.save_state();
+
+    let mut other = common::boot(chr_ram_rom(program));
+    assert_eq!(other.peek(MemoryDomain::CpuBus, 0x2001), Some(0x00));
+    other.load_state(&saved).unwrap();
+    assert_eq!(other.peek(MemoryDomain::CpuBus, 0x2001), Some(0xA5));
+}
@@ -0,0 +1,113 @@
+//! The PPU runs inside CPU cycles, so register reads see it mid-instruction.
+
+mod common;
+
+use common::{boot, rom_with, START};
+
+#[test]
+fn vblank_polling_sees_the_flag_as_it_rises() {
+    let code = [
+        0xAD, 0x02, 0x20, // LDA $2002
+        0x10, 0xFB, //       BPL $8000
+        0xE6, 0x00, //       INC $00
+        0x4C, 0x00, 0x80, // JMP $8000
+    ];
+
+    let mut nes = boot(rom_with(&[(0x8000, &code)], START));
+
+    while nes.cpu().bus.ram.contents()[0] == 0 {
+        nes.clock();
+    }
+
+    // the loop noticed within one pass of the 7 cycle poll, INC included
+    let ppu = nes.ppu();
+    assert_eq!(ppu.scanline, 241);
+    assert!(ppu.dot <= 2 + 3 * 12, "noticed at dot {}", ppu.dot);
+    assert!(!ppu.status_flags.v_blank, "the read should have cleared the flag");
+    drop(ppu);
+
+    // finishing frame 0 and running frames 1 to 4 passes four more vblanks, though
+    // like on hardware a poll that lands right on the flag going up misses a frame
+    for _ in 0..5 {
+        nes.run_frame();
+    }
+    assert!((4..=5).contains(&nes.cpu().bus.ram.contents()[0]));
+}
+
+#[test]
+fn status_reads_outside_vblank_see_it_clear() {
+    let code = [
+        0xAD, 0x02, 0x20, // LDA $2002
+        0x85, 0x00, //       STA $00
+        0x4C, 0x00, 0x80, // JMP $8000
+    ];
+
+    let mut nes = boot(rom_with(&[(0x8000, &code)], START));
+    nes.run_cycles(1000);
+
+    assert_eq!(nes.cpu().bus.ram.contents()[0] & 0x80, 0);
+}
+
+/// Counts NMIs in `$00` and, once `$01` is set, toggles the `$2000` enable bit during vblank.
+fn nmi_rom() -> Vec<u8> {
+    let mut code = vec![
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+        0x4C, 0x05, 0x80, //             JMP $8005
+    ];
+    code.resize(0x100, 0);
+    code.extend([
+        0xE6, 0x00, //                   NMI: INC $00
+        0xA5, 0x01, //                   LDA $01
+        0xF0, 0x0A, //                   BEQ done
+        0xA9, 0x00, 0x8D, 0x00, 0x20, // LDA #0, STA $2000
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+        0x40, //                         done: RTI
+    ]);
+
+    rom_with(&[(0x8000, &code)], [0x8100, 0x8000, 0x0000])
+}
+
+#[test]
+fn vblank_raises_one_nmi_per_frame() {
+    let mut nes = boot(nmi_rom());
+
+    for _ in 0..5 {
+        nes.run_frame();
+    }
+
+    assert_eq!(nes.cpu().bus.ram.contents()[0], 5);
+    // the return address and a status with B clear and bit 5 set are on the stack
+    assert_eq!(nes.cpu().bus.ram.contents()[0x1FB] & 0x30, 0x20);
+}
+
+#[test]
+fn enabling_nmi_during_vblank_raises_another() {
+    let mut nes = boot(nmi_rom());
+    nes.run_frame();
+    nes.cpu_mut().bus.ram.set_contents(&[0, 1].into_iter().chain([0u8; 0x7FE]).collect::<Vec<_>>());
+
+    // every toggle in the handler lands in the same vblank and takes the NMI again,
+    // so the handler keeps re-entering until vblank ends
+    nes.run_frame();
+
+    assert!(nes.cpu().bus.ram.contents()[0] > 1);
+}
+
+
+#[test]
+fn status_reads_racing_vblank_swallow_the_nmi() {
+    let mut rom = nmi_rom();
+    // poll $2002 in a 7 cycle loop instead of idling
+    rom[16 + 5..16 + 11].copy_from_slice(&[0xAD, 0x02, 0x20, 0x4C, 0x05, 0x80]);
+
+    let mut nes = boot(rom);
+
+    for _ in 0..30 {
+        nes.run_frame();
+    }
+
+    // the loop drifts 8 dots a frame against vblank, so it lands on the race every
+    // few frames and those frames lose their NMI, while most others get one
+    let nmis = nes.cpu().bus.ram.contents()[0];
+    assert!((20..30).contains(&nmis), "{nmis} NMIs in 30 frames");
+}
@@ -0,0 +1,97 @@
+//! The profiler splits cycles between instructions and the routines they ran in.
+
+mod common;
+
+use common::chr_ram_rom;
+use nest::{CallFrame, Profiler, RoutineStats, SymbolFormat, Symbols};
+
+/// `main` loops calling `work`, which calls `leaf`. One time around is 31 cycles.
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0x20, 0x10, 0x80]), // main: JSR work
+    (0x8003, &[0x4C, 0x00, 0x80]), // JMP main
+    (0x8010, &[0x20, 0x20, 0x80]), // work: JSR leaf
+    (0x8013, &[0xEA]),             // NOP
+    (0x8014, &[0x60]),             // RTS
+    (0x8020, &[0xEA]),             // leaf: NOP
+    (0x8021, &[0x60]),             // RTS
+];
+
+const INSTRUCTIONS_PER_LOOP: usize = 7;
+
+const SYMBOLS: &str = "$8000#main#\n$8010#work#\n$8020#leaf#\n";
+
+fn stats(routine: Option<u16>, calls: u64, self_cycles: u64, inclusive: u64) -> RoutineStats {
+    RoutineStats { routine, calls, self_cycles, inclusive }
+}
+
+#[test]
+fn attributes_cycles_to_routines() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.load_symbols(SymbolFormat::FceuxNl, SYMBOLS).unwrap();
+    nes.profiler_start();
+
+    for _ in 0..100 * INSTRUCTIONS_PER_LOOP {
+        nes.clock();
+    }
+
+    let profiler = nes.profiler();
+    let total = profiler.total();
+    assert_eq!(total.cycles(), 3100);
+    assert_eq!(total.at(0x8000), 600, "a JSR counts against its caller");
+    assert_eq!(total.at(0x8021), 600, "an RTS against the routine it leaves");
+
+    assert_eq!(
+        profiler.routines(total),
+        [stats(None, 0, 900, 3100), stats(Some(0x8010), 100, 1400, 2200), stats(Some(0x8020), 100, 800, 800)]
+    );
+
+    assert_eq!(nes.profile_collapsed(true), "main 900\nmain;work 1400\nmain;work;leaf 800\n");
+}
+
+#[test]
+fn reports_the_last_frame_and_everything() {
+    let mut nes = common::boot(chr_ram_rom(PROGRAM));
+    nes.load_symbols(SymbolFormat::FceuxNl, SYMBOLS).unwrap();
+    nes.profiler_start();
+    nes.run_frame();
+    nes.run_frame();
+
+    let profiler = nes.profiler();
+    assert_eq!(profiler.last_frame().frames(), 1);
+    assert_eq!(profiler.total().frames(), 2);
+    assert!(profiler.last_frame().cycles() > 29_000);
+    assert!(profiler.total().cycles() > profiler.last_frame().cycles());
+
+    let report = nes.profile_report(false, 2);
+    assert!(report.starts_with(&format!("1 frames, {} cycles\n", profiler.last_frame().cycles())), "{report}");
+    assert!(report.contains("\nmain "));
+    assert!(report.contains("\nwork "));
+    assert!(!report.contains("\nleaf "), "only the top two");
+    assert!(report.contains("\ninstruction"));
+
+    nes.profiler_stop();
+    nes.run_frame();
+    assert_eq!(nes.profiler().total().frames(), 2, "stopped");
+
+    nes.profiler_reset();
+    assert_eq!(nes.profiler().total().cycles(), 0);
+    assert_eq!(nes.profile_collapsed(true), "");
+}
+
+#[test]
+fn counts_recursion_once() {
+    let mut profiler = Profiler::new();
+    profiler.start();
+
+    let call = |target| CallFrame { caller: 0x8000, target, sp: 0xFD, interrupt: false };
+    profiler.record(0x8000, 6, &[]);
+    profiler.record(0x9000, 6, &[call(0x9000)]);
+    profiler.record(0x9000, 6, &[call(0x9000), call(0x9000)]);
+    profiler.record(0x9003, 6, &[call(0x9000), call(0x9000)]);
+    profiler.record(0x9003, 6, &[call(0x9000)]);
+
+    assert_eq!(profiler.routines(profiler.total()), [stats(None, 0, 6, 30), stats(Some(0x9000), 2, 24, 24)]);
+
+    let symbols = Symbols::new();
+    assert_eq!(profiler.collapsed(profiler.total(), &symbols), "main 6\nmain;$9000 12\nmain;$9000;$9000 12\n");
+}
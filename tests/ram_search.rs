@@ -0,0 +1,82 @@
+//! RAM search narrows memory down to where a number lives, a filter at a time.
+
+mod common;
+
+use common::chr_ram_rom;
+use nest::{MemoryDomain, RamSearch, SearchCompare, SearchFormat, SearchResult, SearchWidth};
+
+fn addrs(search: &RamSearch, memory: &[u8]) -> Vec<usize> {
+    search.results(memory, usize::MAX).iter().map(|result| result.addr).collect()
+}
+
+#[test]
+fn filters_against_the_snapshot_and_constants() {
+    let mut memory = vec![0u8; 8];
+    let mut search = RamSearch::new(MemoryDomain::SystemRam, &memory, SearchWidth::One, SearchFormat::Unsigned);
+    assert_eq!(search.len(), 8);
+
+    memory[2] = 5;
+    memory[5] = 3;
+    assert_eq!(search.filter(&memory, SearchCompare::Greater, None), 2);
+
+    memory[5] = 1;
+    assert_eq!(search.filter(&memory, SearchCompare::Less, None), 1);
+    assert_eq!(addrs(&search, &memory), [5]);
+
+    assert!(search.undo());
+    assert!(!search.undo(), "only the last filter");
+    assert_eq!(search.filter(&memory, SearchCompare::Equal, Some(5)), 1);
+    assert_eq!(search.results(&memory, 10), [SearchResult { addr: 2, value: Some(5), previous: Some(5) }]);
+
+    search.snapshot(&[0, 0, 4, 0, 0, 0, 0, 0]);
+    assert_eq!(search.filter(&memory, SearchCompare::NotEqual, None), 1);
+}
+
+#[test]
+fn reads_wider_signed_and_bcd_values() {
+    let memory = [0x34, 0x12, 0xFF, 0xFF, 0x99, 0x09, 0x1A, 0x00];
+
+    let mut search = RamSearch::new(MemoryDomain::SystemRam, &memory, SearchWidth::Two, SearchFormat::Unsigned);
+    assert_eq!(search.len(), 7, "the last byte can't start a word");
+    search.filter(&memory, SearchCompare::Equal, Some(0x1234));
+    assert_eq!(addrs(&search, &memory), [0]);
+
+    let mut search = RamSearch::new(MemoryDomain::SystemRam, &memory, SearchWidth::Two, SearchFormat::Signed);
+    search.filter(&memory, SearchCompare::Equal, Some(-1));
+    assert_eq!(addrs(&search, &memory), [2]);
+
+    let mut search = RamSearch::new(MemoryDomain::SystemRam, &memory, SearchWidth::Two, SearchFormat::Bcd);
+    search.filter(&memory, SearchCompare::Equal, Some(999));
+    assert_eq!(addrs(&search, &memory), [4]);
+    search.undo();
+    search.filter(&memory, SearchCompare::NotEqual, Some(0));
+    assert!(!addrs(&search, &memory).contains(&5), "$1A isn't decimal");
+
+    let mut search = RamSearch::new(MemoryDomain::SystemRam, &memory, SearchWidth::Four, SearchFormat::Unsigned);
+    search.filter(&memory, SearchCompare::Equal, Some(0xFFFF_1234));
+    assert_eq!(addrs(&search, &memory), [0]);
+}
+
+#[test]
+fn searches_while_the_game_runs() {
+    let mut nes = common::boot(chr_ram_rom(&[(0x8000, &[0xE6, 0x20, 0x4C, 0x00, 0x80])]));
+    nes.search_start(MemoryDomain::SystemRam, SearchWidth::One, SearchFormat::Unsigned);
+    nes.poke(MemoryDomain::SystemRam, 0x20, 0);
+    nes.search_snapshot();
+
+    nes.run_frame();
+    nes.poke(MemoryDomain::SystemRam, 0x20, 100);
+    assert_eq!(nes.search_filter(SearchCompare::Greater, None), 1);
+
+    nes.run_frame();
+    nes.poke(MemoryDomain::SystemRam, 0x20, 150);
+    assert_eq!(nes.search_filter(SearchCompare::Greater, None), 1);
+    assert_eq!(nes.search_report(4).unwrap(), "1 candidates in System RAM\n$0020: 150 (was 150)\n", "filtering takes a new snapshot");
+
+    assert!(nes.search_undo());
+    assert_eq!(nes.ram_search().unwrap().len(), 1);
+
+    nes.search_stop();
+    assert_eq!(nes.search_report(4), None);
+    assert_eq!(nes.search_filter(SearchCompare::Equal, Some(0)), 0);
+}
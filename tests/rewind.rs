@@ -0,0 +1,85 @@
+//! Rewinding restores the exact states the machine went through.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{rom_with, START};
+use nest::Nes;
+
+/// An NROM image that increments `$00` forever, so every frame has a different state.
+fn counter_rom() -> Vec<u8> {
+    rom_with(&[(0x8000, &[0xE6, 0x00, 0x4C, 0x00, 0x80])], START)
+}
+
+/// Runs until the PPU reaches `frame`, remembering the state at every frame boundary.
+fn run_to_frame(nes: &mut Nes, frame: usize, states: &mut HashMap<usize, Vec<u8>>) {
+    while nes.ppu().frame < frame {
+        let before = nes.ppu().frame;
+        nes.clock();
+
+        if nes.ppu().frame != before {
+            states.insert(nes.ppu().frame, nes.save_state());
+        }
+    }
+}
+
+#[test]
+fn steps_back_one_snapshot_at_a_time() {
+    let mut nes = common::boot(counter_rom());
+    let mut states = HashMap::new();
+    nes.set_rewind_capacity(100);
+
+    run_to_frame(&mut nes, 40, &mut states);
+    assert_eq!(nes.rewind_len(), 40);
+
+    for frame in (1..40).rev() {
+        assert!(nes.rewind_step());
+        assert_eq!(nes.ppu().frame, frame);
+        assert!(nes.save_state() == states[&frame], "state differs after rewinding to frame {frame}");
+    }
+
+    assert!(!nes.rewind_step());
+}
+
+#[test]
+fn keeps_recording_after_a_rewind() {
+    let mut nes = common::boot(counter_rom());
+    let mut states = HashMap::new();
+    nes.set_rewind_capacity(100);
+    nes.set_rewind_interval(2);
+
+    run_to_frame(&mut nes, 20, &mut states);
+    assert!(nes.rewind_step());
+    assert!(nes.rewind_step());
+    assert_eq!(nes.ppu().frame, 16);
+
+    run_to_frame(&mut nes, 30, &mut states);
+    assert!(nes.rewind_step());
+    assert_eq!(nes.ppu().frame, 28);
+    assert!(nes.save_state() == states[&28]);
+}
+
+#[test]
+fn stays_within_the_memory_budget() {
+    let mut nes = common::boot(counter_rom());
+    let mut states = HashMap::new();
+    nes.set_rewind_capacity(1000);
+    nes.set_rewind_budget(6 * 1024);
+
+    run_to_frame(&mut nes, 150, &mut states);
+
+    assert!(nes.rewind_usage() <= 6 * 1024, "{} bytes used", nes.rewind_usage());
+    assert!(nes.rewind_len() > 0 && nes.rewind_len() < 150, "{} snapshots, {} bytes", nes.rewind_len(), nes.rewind_usage());
+    assert!(nes.rewind_step());
+    assert!(nes.save_state() == states[&149]);
+}
+
+#[test]
+fn is_off_until_given_a_capacity() {
+    let mut nes = common::boot(counter_rom());
+    run_to_frame(&mut nes, 5, &mut HashMap::new());
+
+    assert_eq!(nes.rewind_len(), 0);
+    assert!(!nes.rewind_step());
+}
@@ -0,0 +1,58 @@
+//! Frame-granular execution stops where the PPU says it should.
+
+mod common;
+
+use common::test_rom;
+
+#[test]
+fn runs_whole_frames() {
+    let mut nes = common::boot(test_rom("7_Graphics.nes"));
+
+    // the reset already used up some of frame 0
+    nes.run_frame();
+
+    for frame in 1..=3 {
+        let cycles = nes.run_frame();
+        let ppu = nes.ppu();
+
+        assert_eq!(ppu.frame, frame + 1);
+        assert!(ppu.scanline == 0 && ppu.dot < 3 * 8, "stopped at {},{}", ppu.scanline, ppu.dot);
+        assert!((29_780..=29_782).contains(&cycles), "{cycles} cycles");
+    }
+}
+
+#[test]
+fn keeps_the_ppu_going_after_a_halt() {
+    let mut nes = common::boot(test_rom("1_Example.nes"));
+    nes.run_frame();
+    let halted_at = nes.total_cycles();
+
+    assert!(!nes.is_running());
+    assert!((29_780..=29_782).contains(&nes.run_frame()));
+    assert_eq!(nes.total_cycles(), halted_at);
+}
+
+#[test]
+fn stops_right_after_vblank_starts() {
+    let mut nes = common::boot(test_rom("7_Graphics.nes"));
+
+    for _ in 0..2 {
+        nes.run_until_vblank();
+        let ppu = nes.ppu();
+
+        assert_eq!(ppu.scanline, 241);
+        assert!((2..2 + 3 * 8).contains(&ppu.dot), "stopped at dot {}", ppu.dot);
+        assert!(ppu.status_flags.v_blank);
+    }
+}
+
+#[test]
+fn runs_at_least_the_requested_cycles() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    let before = nes.total_cycles();
+
+    let ran = nes.run_cycles(100);
+
+    assert!((100..107).contains(&ran));
+    assert_eq!(nes.total_cycles() - before, ran as u64);
+}
@@ -0,0 +1,71 @@
+//! Save states round-trip the machine and reject anything they can't restore.
+
+mod common;
+
+use common::test_rom;
+use nest::{Nes, StateError, STATE_VERSION};
+
+fn run_to_halt(nes: &mut Nes) {
+    while nes.is_running() && nes.total_cycles() < 1_000_000 {
+        nes.clock();
+    }
+}
+
+#[test]
+fn resumes_from_a_saved_state() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    for _ in 0..40 {
+        nes.clock();
+    }
+
+    let saved = nes.save_state();
+    run_to_halt(&mut nes);
+    let finished = nes.save_state();
+
+    nes.load_state(&saved).unwrap();
+    assert_eq!(nes.save_state(), saved);
+
+    run_to_halt(&mut nes);
+    assert_eq!(nes.save_state(), finished);
+}
+
+#[test]
+fn restores_into_a_fresh_machine() {
+    let mut nes = common::boot(test_rom("7_Graphics.nes"));
+    run_to_halt(&mut nes);
+    let saved = nes.save_state();
+
+    let mut other = common::boot(test_rom("7_Graphics.nes"));
+    other.load_state(&saved).unwrap();
+
+    assert_eq!(other.save_state(), saved);
+    assert_eq!(other.ppu().vbus.vram.contents(), nes.ppu().vbus.vram.contents());
+}
+
+#[test]
+fn rejects_states_it_cannot_restore() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    let saved = nes.save_state();
+
+    assert_eq!(nes.load_state(b"NOPE"), Err(StateError::BadMagic));
+    assert_eq!(nes.load_state(&saved[..saved.len() - 1]), Err(StateError::Truncated));
+
+    for version in [0, STATE_VERSION + 1] {
+        let mut other_version = saved.clone();
+        other_version[4..6].copy_from_slice(&version.to_le_bytes());
+        assert_eq!(nes.load_state(&other_version), Err(StateError::UnsupportedVersion(version)));
+    }
+
+    // NROM without PRG RAM saves an empty mapper chunk, so a byte in it doesn't belong
+    let mapper = saved.windows(4).position(|tag| tag == b"MAPR").unwrap();
+    let mut grown = saved.clone();
+    grown[mapper + 4..mapper + 8].copy_from_slice(&1u32.to_le_bytes());
+    grown.insert(mapper + 8, 0);
+    assert_eq!(nes.load_state(&grown), Err(StateError::BadChunkLength { tag: *b"MAPR", expected: 0, actual: 1 }));
+
+    let other = common::boot(test_rom("1_Example.nes")).save_state();
+    assert_eq!(nes.load_state(&other), Err(StateError::RomMismatch));
+
+    // a rejected state leaves the machine as it was
+    assert_eq!(nes.save_state(), saved);
+}
@@ -0,0 +1,149 @@
+//! Source lines from ld65 debug info: resolving PC, stepping by line and breaking on a line.
+
+mod common;
+
+use common::ines;
+use nest::{MemoryDomain, Nes, SourceLine, SourceMap, SymbolFormat};
+
+const GAME_S: &str = "\
+.segment \"CODE\"
+reset:
+    ldx #0
+loop:
+    jsr update
+    inx
+    jmp loop
+update:
+    incr counter
+    rts
+";
+
+/// What ld65 writes for `GAME_S` linked at `$8000`, with `incr` from `macros.inc` expanding to two `INC`s.
+const DBG: &str = "\
+version\tmajor=2,minor=0
+file\tid=0,name=\"src/game.s\",size=120,mtime=0x60000000,mod=0
+file\tid=1,name=\"src/macros.inc\",size=40,mtime=0x60000000,mod=0
+seg\tid=0,name=\"CODE\",start=0x008000,size=0x0015,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16
+seg\tid=1,name=\"ZEROPAGE\",start=0x000010,size=0x0001,addrsize=zeropage,type=rw
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=3
+span\tid=2,seg=0,start=5,size=1
+span\tid=3,seg=0,start=6,size=3
+span\tid=4,seg=0,start=16,size=4
+span\tid=5,seg=0,start=16,size=2
+span\tid=6,seg=0,start=18,size=2
+span\tid=7,seg=0,start=20,size=1
+line\tid=0,file=0,line=1
+line\tid=1,file=0,line=3,span=0
+line\tid=2,file=0,line=5,span=1
+line\tid=3,file=0,line=6,span=2
+line\tid=4,file=0,line=7,span=3
+line\tid=5,file=1,line=2,type=2,count=1,span=5
+line\tid=6,file=1,line=3,type=2,count=1,span=6
+line\tid=7,file=0,line=9,span=4
+line\tid=8,file=0,line=10,span=7
+sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=2,val=0x8000,seg=0,type=lab
+sym\tid=1,name=\"update\",addrsize=absolute,scope=0,def=8,val=0x8010,seg=0,type=lab
+";
+
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0xA2, 0x00]),       // reset: LDX #0
+    (0x8002, &[0x20, 0x10, 0x80]), // loop: JSR update
+    (0x8005, &[0xE8]),             // INX
+    (0x8006, &[0x4C, 0x02, 0x80]), // JMP loop
+    (0x8010, &[0xE6, 0x10]),       // update: INC counter
+    (0x8012, &[0xE6, 0x10]),       // INC counter
+    (0x8014, &[0x60]),             // RTS
+];
+
+fn location(nes: &Nes) -> String {
+    nes.source_location().unwrap_or_default()
+}
+
+#[test]
+fn maps_bytes_to_lines() {
+    let mut map = SourceMap::new();
+    assert_eq!(map.load(DBG), Ok(8));
+    assert_eq!(map.files(), ["src/game.s", "src/macros.inc"]);
+    assert_eq!(map.file("game.s"), Some(0));
+    assert_eq!(map.file("macros.inc"), Some(1));
+    assert_eq!(map.file("ame.s"), None);
+
+    // the macro's invocation wins over its body
+    let update = SourceLine { file: 0, line: 9 };
+    assert_eq!(map.line_at(0x8012, Some(0x12)), Some(update));
+    assert_eq!(map.describe(update), "src/game.s:9");
+    assert_eq!(map.addresses(update, &|_| vec![]), [0x8010, 0x8011, 0x8012, 0x8013]);
+
+    // ROM lines go by where their bank is, not the address asked about
+    assert_eq!(map.line_at(0xC000, Some(0x00)), Some(SourceLine { file: 0, line: 3 }));
+    assert_eq!(map.line_at(0x8000, None), None);
+
+    let err = map.load("span\tid=0,seg=0,start=0\n").unwrap_err();
+    assert_eq!(err.to_string(), "symbol file line 1: span has no size");
+}
+
+#[test]
+fn resolves_pc_through_mirrors() {
+    let mut nes = common::boot(ines(1, 0, &[], PROGRAM, [0x0000, 0xC000, 0x0000]));
+    nes.load_symbols(SymbolFormat::Ca65Dbg, DBG).unwrap();
+    assert_eq!(nes.cpu().counter, 0xC000);
+    assert_eq!(location(&nes), "src/game.s:3");
+}
+
+#[test]
+fn steps_by_source_line() {
+    let mut nes = common::boot(ines(1, 0, &[], PROGRAM, [0x0000, 0xC000, 0x0000]));
+    nes.load_symbols(SymbolFormat::Ca65Dbg, DBG).unwrap();
+    nes.pause();
+
+    let step = |nes: &mut Nes, over: bool| {
+        if over { nes.step_line_over() } else { nes.step_line() };
+        nes.run_frame();
+        assert!(nes.is_paused());
+        (nes.cpu().counter, location(nes))
+    };
+
+    assert_eq!(step(&mut nes, false), (0xC002, "src/game.s:5".into()));
+    assert_eq!(step(&mut nes, false), (0x8010, "src/game.s:9".into()), "into the call");
+    assert_eq!(step(&mut nes, false), (0x8014, "src/game.s:10".into()), "the whole macro in one step");
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(2));
+    assert_eq!(step(&mut nes, false), (0xC005, "src/game.s:6".into()), "back out of it");
+    assert_eq!(step(&mut nes, true), (0xC006, "src/game.s:7".into()));
+    assert_eq!(step(&mut nes, true), (0x8002, "src/game.s:5".into()));
+    assert_eq!(step(&mut nes, true), (0x8005, "src/game.s:6".into()), "over the call");
+    assert_eq!(nes.peek(MemoryDomain::SystemRam, 0x10), Some(4));
+}
+
+#[test]
+fn breaks_on_a_source_line() {
+    let mut nes = common::boot(ines(1, 0, &[], PROGRAM, [0x0000, 0xC000, 0x0000]));
+    nes.load_symbols(SymbolFormat::Ca65Dbg, DBG).unwrap();
+
+    // once where it was linked, once in the mirror
+    let ids = nes.add_source_breakpoint("game.s", 10, "").unwrap();
+    assert_eq!(ids.len(), 2);
+    assert!(nes.add_source_breakpoint("game.s", 4, "").unwrap().is_empty(), "a label on its own makes no code");
+    assert!(nes.add_source_breakpoint("other.s", 3, "").unwrap().is_empty());
+    assert!(nes.add_source_breakpoint("game.s", 9, "A ==").is_err());
+
+    nes.run_frame();
+    assert!(nes.is_paused());
+    assert_eq!(nes.cpu().counter, 0x8014);
+    assert_eq!(location(&nes), "src/game.s:10");
+}
+
+#[test]
+fn shows_source_around_pc() {
+    let mut nes = common::boot(ines(1, 0, &[], PROGRAM, [0x0000, 0xC000, 0x0000]));
+    nes.load_symbols(SymbolFormat::Ca65Dbg, DBG).unwrap();
+    assert_eq!(nes.source_context(1), None, "no source text yet");
+
+    assert!(nes.add_source_text("src/game.s", GAME_S));
+    assert!(!nes.add_source_text("other.s", ""));
+    assert_eq!(nes.source_context(1).unwrap(), "  2  reset:\n> 3      ldx #0\n  4  loop:\n");
+
+    nes.clear_symbols();
+    assert_eq!(nes.source_location(), None);
+    assert!(nes.source_map().is_empty());
+}
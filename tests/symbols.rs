@@ -0,0 +1,116 @@
+//! Symbol files name addresses in traces, disassembly, break reasons and the call stack.
+
+mod common;
+
+use common::{ines, START};
+use nest::{BreakKind, SymbolFormat, Symbols};
+
+/// `main` calls `outer`, which calls `inner` and stores to `counter`.
+const PROGRAM: &[(u16, &[u8])] = &[
+    (0x8000, &[0x20, 0x30, 0x80]), // main: JSR outer
+    (0x8003, &[0x4C, 0x00, 0x80]), // JMP main
+    (0x8030, &[0x20, 0x40, 0x80]), // outer: JSR inner
+    (0x8033, &[0x60]),             // RTS
+    (0x8040, &[0xE6, 0x10]),       // inner: INC counter
+    (0x8042, &[0x60]),             // RTS
+];
+
+const NL: &str = "$8000#main#entry point\n$8030#outer#\n$8040#inner#\n$0010#counter#\n$0200/100##sprites, no name\n";
+
+#[test]
+fn reads_fceux_name_lists() {
+    let mut nes = common::boot(ines((0x4000 / 0x4000) as u8, 0, &[], PROGRAM, START));
+    assert_eq!(nes.load_symbols(SymbolFormat::FceuxNl, NL), Ok(4));
+
+    assert_eq!(nes.symbol_at(0x8030).as_deref(), Some("outer"));
+    assert_eq!(nes.symbol_address("counter"), Some(0x0010));
+    assert_eq!(nes.symbol_at(0x0200), None);
+
+    let err = nes.load_symbols(SymbolFormat::FceuxNl, "$8000#main#\n$80G0#oops#\n").unwrap_err();
+    assert_eq!(err.to_string(), "symbol file line 2: `80G0` is not a hex address");
+}
+
+#[test]
+fn reads_ca65_debug_files() {
+    let dbg = concat!(
+        "version\tmajor=2,minor=0\n",
+        "file\tid=0,name=\"game.s\",size=100,mtime=0x5F000000,mod=0\n",
+        "sym\tid=0,name=\"main\",addrsize=absolute,scope=0,def=1,ref=2,val=0x8000,seg=0,type=lab\n",
+        "sym\tid=1,name=\"@loop\",addrsize=absolute,scope=0,def=3,val=0x8000,seg=0,type=lab\n",
+        "sym\tid=2,name=\"SPEED\",addrsize=zeropage,scope=0,def=4,val=0x3,type=equ\n",
+        "sym\tid=3,name=\"counter\",addrsize=zeropage,scope=0,def=5,val=0x10,seg=1,type=lab\n",
+    );
+
+    let mut symbols = Symbols::new();
+    assert_eq!(symbols.load(SymbolFormat::Ca65Dbg, dbg, &|_| vec![]), Ok(3));
+
+    // the local doesn't displace the proper label, but can still be looked up
+    assert_eq!(symbols.label(0x8000), Some("main"));
+    assert_eq!(symbols.address("@loop"), Some(0x8000));
+    assert_eq!(symbols.address("SPEED"), None);
+    assert_eq!(symbols.name_zp(0x10), "counter");
+}
+
+#[test]
+fn places_mesen_rom_labels_where_their_bank_is_mapped() {
+    let mlb = "P:0030:outer\nNesPrgRom:0040:inner:comment\nR:0010:counter\nW:0000:save_slot\nP:4000:reset\n";
+
+    // a 16K board mirrors its only bank into both halves
+    let mut nes = common::boot(ines((0x4000 / 0x4000) as u8, 0, &[], PROGRAM, START));
+    assert_eq!(nes.load_symbols(SymbolFormat::MesenMlb, mlb), Ok(6));
+    assert_eq!(nes.symbol_at(0x8030).as_deref(), Some("outer"));
+    assert_eq!(nes.symbol_at(0xC030).as_deref(), Some("outer"));
+    assert_eq!(nes.symbol_at(0x6000).as_deref(), Some("save_slot"));
+
+    let mut nes = common::boot(ines((0x8000 / 0x4000) as u8, 0, &[], PROGRAM, START));
+    assert_eq!(nes.load_symbols(SymbolFormat::MesenMlb, mlb), Ok(5));
+    assert_eq!(nes.symbol_at(0xC030), None);
+    assert_eq!(nes.symbol_at(0xC000).as_deref(), Some("reset"));
+
+    let err = nes.load_symbols(SymbolFormat::MesenMlb, "W:1FFF:last\nS:A000:beyond\n").unwrap_err();
+    assert_eq!(err.to_string(), "symbol file line 2: work RAM offset $A000 is past $1FFF");
+
+    assert_eq!(SymbolFormat::from_file_name("game.nes.0.nl"), Some(SymbolFormat::FceuxNl));
+    assert_eq!(SymbolFormat::from_file_name("game.MLB"), Some(SymbolFormat::MesenMlb));
+}
+
+#[test]
+fn labels_traces_and_disassembly() {
+    let mut nes = common::boot(ines((0x4000 / 0x4000) as u8, 0, &[], PROGRAM, START));
+    nes.load_symbols(SymbolFormat::FceuxNl, NL).unwrap();
+
+    assert!(nes.debug_line().starts_with("8000  20 30 80  JSR outer "), "{}", nes.debug_line());
+
+    let disasm = nes.disassemble(0x8040, 2);
+    assert_eq!(disasm, "inner:\n8040  E6 10    INC counter\n8042  60       RTS\n");
+
+    nes.clear_symbols();
+    assert!(nes.debug_line().starts_with("8000  20 30 80  JSR $8030 "));
+}
+
+#[test]
+fn follows_the_call_stack() {
+    let mut nes = common::boot(ines((0x4000 / 0x4000) as u8, 0, &[], PROGRAM, START));
+    nes.load_symbols(SymbolFormat::FceuxNl, NL).unwrap();
+    let id = nes.add_breakpoint(BreakKind::Execute, 0x8042, 0x8042, "").unwrap();
+
+    nes.run_frame();
+    assert_eq!(nes.break_reason(), Some(format!("breakpoint {id} at $8042")));
+    assert_eq!(nes.call_stack(), "$8042 inner+2\n$8030 outer\n$8000 main\n");
+    assert_eq!(nes.debugger().call_stack().frames().len(), 2);
+
+    nes.step_out();
+    nes.run_frame();
+    assert_eq!(nes.cpu().counter, 0x8033);
+    assert_eq!(nes.call_stack(), "$8033 outer+3\n$8000 main\n");
+
+    nes.step_out();
+    nes.run_frame();
+    assert_eq!(nes.call_stack(), "$8003 main+3\n");
+
+    // breaking on a labelled address names it
+    nes.add_breakpoint(BreakKind::Execute, 0x8040, 0x8040, "").unwrap();
+    nes.resume();
+    nes.run_frame();
+    assert!(nes.break_reason().unwrap().ends_with(" at $8040 (inner)"));
+}
@@ -0,0 +1,172 @@
+//! Boots every ROM in `test_roms/` that has a `.expect` file next to it, runs it
+//! until `HLT` and compares the machine against the expectations.
+//!
+//! An expectation file is a list of `key = value` lines, `#` starts a comment:
+//!
+//! ```text
+//! a = $5A             registers: a, x, y, sp, p, pc
+//! cycles = 14         CPU cycles from power-on, the 7 cycle reset included, up to
+//!                     and including the HLT opcode fetch
+//! max_cycles = 100000 optional budget before the run counts as hung
+//! ram $0000 = 5A 5A   bytes from that address on, within $0000-$07FF; RAM not
+//!                     listed must be zero
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use nest::{Nes, NullHost};
+
+const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+const RAM_SIZE: usize = 0x800;
+
+#[derive(Default)]
+struct Expectation {
+    registers: Vec<(String, u16)>,
+    cycles: Option<u64>,
+    max_cycles: Option<u64>,
+    ram: Vec<(u16, Vec<u8>)>,
+}
+
+fn parse_number(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+
+    match value.strip_prefix('$') {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|_| format!("invalid number {value:?}"))
+}
+
+fn parse_expectation(text: &str) -> Result<Expectation, String> {
+    let mut expect = Expectation::default();
+
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or(format!("line {}: expected `key = value`", line_no + 1))?;
+        let key = key.trim();
+
+        match key {
+            "a" | "x" | "y" | "sp" | "p" | "pc" => {
+                expect.registers.push((key.to_string(), parse_number(value)? as u16));
+            }
+            "cycles" => expect.cycles = Some(parse_number(value)?),
+            "max_cycles" => expect.max_cycles = Some(parse_number(value)?),
+            _ if key.starts_with("ram ") => {
+                let addr = parse_number(&key[4..])?;
+                let bytes = value
+                    .split_whitespace()
+                    .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("line {}: invalid byte {b:?}", line_no + 1)))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if addr + bytes.len() as u64 > RAM_SIZE as u64 {
+                    return Err(format!("line {}: ram ${addr:04X} with {} bytes runs past $07FF", line_no + 1, bytes.len()));
+                }
+
+                expect.ram.push((addr as u16, bytes));
+            }
+            _ => return Err(format!("line {}: unknown key {key:?}", line_no + 1)),
+        }
+    }
+
+    Ok(expect)
+}
+
+/// Runs one ROM against its expectation, returning every mismatch found.
+fn check_rom(rom_path: &Path, expect: &Expectation) -> Vec<String> {
+    let rom_bytes = fs::read(rom_path).expect("ROM next to expectation file");
+    let mut nes = match Nes::with_host(rom_bytes, Rc::new(NullHost)) {
+        Ok(nes) => nes,
+        Err(err) => return vec![err.to_string()],
+    };
+    nes.reset();
+
+    let max_cycles = expect.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES);
+    while nes.is_running() && nes.total_cycles() < max_cycles {
+        nes.clock();
+    }
+
+    if nes.is_running() {
+        return vec![format!("still running after {max_cycles} cycles")];
+    }
+
+    let mut failures = vec![];
+    let cpu = nes.cpu();
+
+    for (name, want) in &expect.registers {
+        let got = match name.as_str() {
+            "a" => cpu.reg_a as u16,
+            "x" => cpu.reg_x as u16,
+            "y" => cpu.reg_y as u16,
+            "sp" => cpu.stack as u16,
+            "p" => cpu.flags.to_byte() as u16,
+            _ => cpu.counter,
+        };
+
+        if got != *want {
+            failures.push(format!("{name}: expected ${want:02X}, got ${got:02X}"));
+        }
+    }
+
+    if let Some(want) = expect.cycles
+        && nes.total_cycles() != want
+    {
+        failures.push(format!("cycles: expected {want}, got {}", nes.total_cycles()));
+    }
+
+    let mut want_ram = [0u8; RAM_SIZE];
+    for (addr, bytes) in &expect.ram {
+        let start = *addr as usize;
+        want_ram[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    for (addr, (want, got)) in want_ram.iter().zip(cpu.bus.ram.contents()).enumerate() {
+        if want != got {
+            failures.push(format!("ram ${addr:04X}: expected ${want:02X}, got ${got:02X}"));
+        }
+    }
+
+    failures
+}
+
+#[test]
+fn rejects_ram_past_the_end() {
+    assert!(parse_expectation("ram $07FE = 01 02").is_ok());
+    assert_eq!(parse_expectation("a = $01\nram $07FE = 01 02 03").err().unwrap(), "line 2: ram $07FE with 3 bytes runs past $07FF");
+    assert_eq!(parse_expectation("ram $1000 = 01 02").err().unwrap(), "line 1: ram $1000 with 2 bytes runs past $07FF");
+}
+
+#[test]
+fn bundled_test_roms() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_roms");
+    let mut expectations: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("test_roms directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "expect"))
+        .collect();
+    expectations.sort();
+
+    assert!(!expectations.is_empty(), "no .expect files in {}", dir.display());
+
+    let mut report = vec![];
+    for expect_path in &expectations {
+        let rom_path = expect_path.with_extension("nes");
+        let name = rom_path.file_name().unwrap().to_string_lossy().to_string();
+
+        let failures = match parse_expectation(&fs::read_to_string(expect_path).unwrap()) {
+            Ok(expect) => check_rom(&rom_path, &expect),
+            Err(err) => vec![format!("{}: {err}", expect_path.display())],
+        };
+
+        for failure in failures {
+            report.push(format!("{name}: {failure}"));
+        }
+    }
+
+    assert!(report.is_empty(), "test ROM mismatches:\n{}", report.join("\n"));
+}
@@ -0,0 +1,154 @@
+//! Replays every `test_roms/*.log` golden trace against its ROM, and checks the
+//! nestest.log formatter and comparer on hand-written lines and on an excerpt of
+//! the reference log itself.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use common::{ines, rom_with, test_rom, test_roms, START};
+use nest::{compare_log, decode, parse_log_line, Nes, OperandPeek, RecordingHost, TraceEntry};
+
+/// The opening of nestest.log, the reference trace for the nestest ROM. Unlike the
+/// `test_roms` logs it wasn't written by this emulator.
+const NESTEST_EXCERPT: &str = "\
+C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10
+C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12
+C5F9  86 10     STX $10 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 45 CYC:15
+C5FB  86 11     STX $11 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 54 CYC:18
+C5FD  20 2D C7  JSR $C72D                       A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 63 CYC:21
+C72D  EA        NOP                             A:00 X:00 Y:00 P:26 SP:FB PPU:  0, 81 CYC:27
+C72E  38        SEC                             A:00 X:00 Y:00 P:26 SP:FB PPU:  0, 87 CYC:29
+C72F  B0 04     BCS $C735                       A:00 X:00 Y:00 P:27 SP:FB PPU:  0, 93 CYC:31
+C735  EA        NOP                             A:00 X:00 Y:00 P:27 SP:FB PPU:  0,102 CYC:34
+C736  18        CLC                             A:00 X:00 Y:00 P:27 SP:FB PPU:  0,108 CYC:36
+C737  B0 03     BCS $C73C                       A:00 X:00 Y:00 P:26 SP:FB PPU:  0,114 CYC:38
+C739  4C 3C C7  JMP $C73C                       A:00 X:00 Y:00 P:26 SP:FB PPU:  0,120 CYC:40
+C73C  EA        NOP                             A:00 X:00 Y:00 P:26 SP:FB PPU:  0,129 CYC:43
+";
+
+#[test]
+fn golden_logs_match() {
+    let mut logs: Vec<PathBuf> = fs::read_dir(test_roms())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    logs.sort();
+
+    assert!(!logs.is_empty());
+
+    for log_path in logs {
+        let golden = fs::read_to_string(&log_path).unwrap();
+        let mut nes = common::boot(fs::read(log_path.with_extension("nes")).unwrap());
+
+        match compare_log(&mut nes, &golden) {
+            Ok(matched) => assert_eq!(matched, golden.lines().count(), "{}", log_path.display()),
+            Err(divergence) => panic!("{}: {divergence}", log_path.display()),
+        }
+    }
+}
+
+#[test]
+fn nestest_excerpt_matches() {
+    // nestest runs from $C000, the top half of NROM-128's mirrored bank, so each
+    // line's bytes go 16 KiB lower in PRG
+    let lines: Vec<(u16, Vec<u8>)> = NESTEST_EXCERPT
+        .lines()
+        .map(|line| {
+            let fields = parse_log_line(line).unwrap();
+            let pc = u16::from_str_radix(&fields[0].1, 16).unwrap();
+            (pc - 0x4000, fields[1].1.split(' ').map(|byte| u8::from_str_radix(byte, 16).unwrap()).collect())
+        })
+        .collect();
+    let program: Vec<(u16, &[u8])> = lines.iter().map(|(addr, bytes)| (*addr, bytes.as_slice())).collect();
+    let mut nes = common::boot(rom_with(&program, START));
+
+    assert_eq!(compare_log(&mut nes, NESTEST_EXCERPT), Ok(NESTEST_EXCERPT.lines().count()));
+}
+
+#[test]
+fn traces_prg_ram_operands() {
+    let program: &[u8] = &[
+        0xA9, 0x5A, // LDA #$5A
+        0x8D, 0x00, 0x60, // STA $6000
+        0xA9, 0x00, // LDA #$00
+        0xAD, 0x00, 0x60, // LDA $6000
+    ];
+    // battery-backed PRG RAM at $6000
+    let mut nes = common::boot(ines(1, 1, &[0x02], &[(0x8000, program)], START));
+
+    for _ in 0..3 {
+        nes.clock();
+    }
+
+    assert_eq!(nes.trace_entry().disassembly().to_string(), "LDA $6000 = 5A");
+}
+
+#[test]
+fn formats_nestest_lines() {
+    let mut entry = TraceEntry {
+        instruction: decode(&[0x4C, 0xF5, 0xC5], 0xC000).unwrap(),
+        operand: OperandPeek::default(),
+        label: None,
+        a: 0,
+        x: 0,
+        y: 0,
+        p: 0x24,
+        sp: 0xFD,
+        scanline: 0,
+        dot: 21,
+        cycle: 7,
+    };
+
+    assert_eq!(
+        entry.to_string(),
+        "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+    );
+
+    entry.instruction = decode(&[0x04, 0xA9], 0xC000).unwrap();
+
+    let line = entry.to_string();
+    assert!(line.starts_with("C000  04 A9    *NOP $A9 = 00 "), "{line}");
+    assert_eq!(parse_log_line(&line).unwrap(), entry.fields());
+}
+
+#[test]
+fn reports_first_diverging_field() {
+    let golden = fs::read_to_string(test_roms().join("1_Example.log")).unwrap();
+    let tampered = golden.replacen("A:5A X:00", "A:5B X:00", 1);
+    let mut nes = common::boot(test_rom("1_Example.nes"));
+
+    let divergence = compare_log(&mut nes, &tampered).unwrap_err();
+
+    assert_eq!(divergence.line, 2);
+    assert_eq!(divergence.fields.len(), 1);
+    assert_eq!(divergence.fields[0].field, "A");
+    assert_eq!(divergence.fields[0].expected, "5B");
+    assert_eq!(divergence.fields[0].actual, "5A");
+}
+
+#[test]
+fn tracelog_rows_show_operands() {
+    let host = Rc::new(RecordingHost::default());
+    let mut nes = Nes::with_host(test_rom("1_Example.nes"), host.clone()).unwrap();
+    nes.reset();
+
+    for _ in 0..3 {
+        nes.clock();
+    }
+
+    let golden = fs::read_to_string(test_roms().join("1_Example.log")).unwrap();
+    let rows = host.tracelog.borrow();
+    assert_eq!(rows.len(), 3);
+
+    for (row, line) in rows.iter().zip(golden.lines()) {
+        let expected = parse_log_line(line).unwrap();
+        assert_eq!(row.pg, expected[0].1);
+        assert_eq!(row.by.trim_end(), expected[1].1);
+        assert_eq!(row.inst, expected[2].1);
+    }
+}
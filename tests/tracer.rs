@@ -0,0 +1,102 @@
+//! The tracer's filters, ring buffer and file stream, checked against a golden log.
+
+mod common;
+
+use std::fs;
+
+use common::{test_rom, test_roms};
+use nest::{Nes, TraceTrigger};
+
+fn golden() -> Vec<String> {
+    fs::read_to_string(test_roms().join("6_Instructions2.log")).unwrap().lines().map(String::from).collect()
+}
+
+fn run(nes: &mut Nes, instructions: usize) -> Vec<String> {
+    for _ in 0..instructions {
+        nes.clock();
+    }
+
+    nes.drain_trace().lines().map(String::from).collect()
+}
+
+#[test]
+fn traces_nothing_until_started() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    assert!(run(&mut nes, 5).is_empty());
+
+    nes.trace_start();
+    assert_eq!(run(&mut nes, 5), golden()[5..10]);
+
+    nes.trace_stop();
+    assert!(run(&mut nes, 5).is_empty());
+}
+
+#[test]
+fn ring_buffer_keeps_the_newest_entries() {
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.set_trace_capacity(4);
+    nes.trace_start();
+
+    assert_eq!(run(&mut nes, 10), golden()[6..10]);
+}
+
+#[test]
+fn filters_by_pc_range_and_bank() {
+    let golden = golden();
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.set_trace_pc_range(0x8004, 0x8008);
+    nes.trace_start();
+
+    let lines = run(&mut nes, 10);
+    let expected: Vec<&String> = golden[..10].iter().filter(|line| ("8004".."8009").contains(&&line[..4])).collect();
+    assert!(!lines.is_empty());
+    assert_eq!(lines.iter().collect::<Vec<_>>(), expected);
+
+    // NROM-128 mirrors its only bank, so nothing runs from bank 1
+    nes.clear_trace_pc_range();
+    nes.set_trace_bank(Some(1));
+    assert!(run(&mut nes, 5).is_empty());
+
+    nes.set_trace_bank(Some(0));
+    assert_eq!(run(&mut nes, 5).len(), 5);
+}
+
+#[test]
+fn waits_for_the_start_cycle() {
+    let golden = golden();
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.set_trace_start_cycle(20);
+    nes.trace_start();
+
+    let lines = run(&mut nes, 10);
+    let first = golden.iter().position(|line| line.split("CYC:").nth(1).unwrap().parse::<u64>().unwrap() >= 20).unwrap();
+
+    assert_eq!(lines, golden[first..10]);
+}
+
+#[test]
+fn traces_a_few_instructions_after_a_trigger() {
+    let golden = golden();
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    let pc = u16::from_str_radix(&golden[3][..4], 16).unwrap();
+    nes.tracer_mut().set_trigger(Some(TraceTrigger { pc, count: 2 }));
+    nes.trace_start();
+
+    assert_eq!(run(&mut nes, 10), golden[3..5]);
+}
+
+#[test]
+fn streams_to_a_file() {
+    let path = std::env::temp_dir().join(format!("nest-trace-{}.log", std::process::id()));
+    let mut nes = common::boot(test_rom("6_Instructions2.nes"));
+    nes.set_trace_capacity(1);
+    nes.trace_to_file(&path).unwrap();
+    nes.trace_start();
+
+    run(&mut nes, 20);
+    nes.tracer_mut().flush().unwrap();
+
+    let written = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(written.lines().collect::<Vec<_>>(), golden()[..20]);
+}
@@ -0,0 +1,134 @@
+//! The stable unofficial opcodes, checked for results, flags and cycle counts.
+
+mod common;
+
+use common::{ram, rom_with, START};
+
+#[test]
+fn lax_and_sax_move_a_and_x_together() {
+    // LDA #$F0, STA $10, LAX $10, LDA #$3C, SAX $11, LDY #$01, LAX $000F,Y
+    let mut nes = common::boot(rom_with(&[(0x8000, &[0xA9, 0xF0, 0x85, 0x10, 0xA7, 0x10, 0xA9, 0x3C, 0x87, 0x11, 0xA0, 0x01, 0xBF, 0x0F, 0x00])], START));
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 3); // LAX zp
+    assert_eq!((nes.cpu().reg_a, nes.cpu().reg_x), (0xF0, 0xF0));
+    assert!(nes.cpu().flags.negative);
+
+    nes.clock();
+    assert_eq!(nes.clock(), 3); // SAX zp
+    assert_eq!(ram(&nes, 0x11), 0x30);
+
+    nes.clock();
+    assert_eq!(nes.clock(), 4); // LAX abs,Y without a page cross
+    assert_eq!(nes.cpu().reg_x, 0xF0);
+}
+
+#[test]
+fn read_modify_write_combos() {
+    let mut nes = common::boot(rom_with(&[(0x8000, &[
+        0xA9, 0x05, 0x85, 0x20, // LDA #$05, STA $20
+        0xC7, 0x20, // DCP $20
+        0x38, 0xE7, 0x20, // SEC, ISB $20
+        0xA9, 0x01, 0x85, 0x21, 0x0F, 0x21, 0x00, // LDA #$01, STA $21, SLO $0021
+        0x18, 0xA9, 0xFF, 0x27, 0x21, // CLC, LDA #$FF, RLA $21
+        0x53, 0x30, // SRE ($30),Y
+        0x18, 0xA9, 0x10, 0x7B, 0x00, 0x00, // CLC, LDA #$10, RRA $0000,Y
+    ])], START));
+
+    nes.run_cycles(5);
+    assert_eq!(nes.clock(), 5); // DCP zp
+    assert_eq!(ram(&nes, 0x20), 0x04);
+    assert!(nes.cpu().flags.carry && !nes.cpu().flags.zero); // $05 >= $04
+
+    nes.clock();
+    assert_eq!(nes.clock(), 5); // ISB zp
+    assert_eq!(ram(&nes, 0x20), 0x05);
+    assert_eq!(nes.cpu().reg_a, 0x00);
+    assert!(nes.cpu().flags.zero && nes.cpu().flags.carry);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 6); // SLO abs
+    assert_eq!(ram(&nes, 0x21), 0x02);
+    assert_eq!(nes.cpu().reg_a, 0x03);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 5); // RLA zp
+    assert_eq!(ram(&nes, 0x21), 0x04);
+    assert_eq!(nes.cpu().reg_a, 0x04);
+
+    // ($30) points at $0000, so SRE and RRA work on $00
+    assert_eq!(nes.clock(), 8); // SRE (zp),Y
+    assert_eq!(nes.cpu().reg_a, 0x04);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 7); // RRA abs,Y always pays the indexing cycle
+    assert_eq!(nes.cpu().reg_a, 0x10);
+}
+
+#[test]
+fn immediate_combos() {
+    let mut nes = common::boot(rom_with(&[(0x8000, &[
+        0xA9, 0x81, 0x0B, 0xFF, // LDA #$81, ANC #$FF
+        0xA9, 0x03, 0x4B, 0xFF, // LDA #$03, ALR #$FF
+        0x38, 0xA9, 0xC0, 0x6B, 0xFF, // SEC, LDA #$C0, ARR #$FF
+        0xA9, 0x0F, 0xA2, 0x3C, 0xCB, 0x0C, // LDA #$0F, LDX #$3C, AXS #$0C
+        0x38, 0xA9, 0x10, 0xEB, 0x01, // SEC, LDA #$10, SBC #$01
+    ])], START));
+
+    nes.clock();
+    assert_eq!(nes.clock(), 2); // ANC
+    assert_eq!(nes.cpu().reg_a, 0x81);
+    assert!(nes.cpu().flags.carry);
+
+    nes.clock();
+    assert_eq!(nes.clock(), 2); // ALR
+    assert_eq!(nes.cpu().reg_a, 0x01);
+    assert!(nes.cpu().flags.carry);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 2); // ARR
+    assert_eq!(nes.cpu().reg_a, 0xE0);
+    assert!(nes.cpu().flags.carry && !nes.cpu().flags.overflow);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 2); // AXS
+    assert_eq!(nes.cpu().reg_x, 0x00);
+    assert!(nes.cpu().flags.zero && nes.cpu().flags.carry);
+
+    nes.clock();
+    nes.clock();
+    assert_eq!(nes.clock(), 2); // $EB SBC
+    assert_eq!(nes.cpu().reg_a, 0x0F);
+}
+
+#[test]
+fn nops_skip_their_operands() {
+    let mut nes = common::boot(rom_with(&[(0x8000, &[
+        0x1A, // NOP
+        0x80, 0xFF, // NOP #imm
+        0x04, 0x00, // NOP zp
+        0x14, 0x00, // NOP zp,X
+        0x0C, 0x00, 0x00, // NOP abs
+        0xA2, 0xFF, 0x1C, 0x01, 0x00, // LDX #$FF, NOP abs,X crossing a page
+    ])], START));
+
+    let cycles: Vec<usize> = (0..7).map(|_| nes.clock()).collect();
+
+    assert_eq!(cycles, [2, 2, 3, 4, 4, 2, 5]);
+    assert_eq!(nes.cpu().counter, 0x800F);
+    assert_eq!(nes.cpu().reg_a, 0x00);
+}
+
+#[test]
+fn traces_name_unofficial_opcodes() {
+    let nes = common::boot(rom_with(&[(0x8000, &[0xC7, 0x20])], START));
+    let line = nes.trace_entry().to_string();
+
+    assert!(line.contains("*DCP $20 = 00"), "{line}");
+}
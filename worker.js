@@ -1,4 +1,4 @@
-import init, { Nes } from "./pkg/nest.js";
+import init, { MemoryDomain, Nes, SearchCompare, SearchFormat, SearchWidth, SymbolFormat } from "./pkg/nest.js";
 
 let nes;
 
@@ -9,36 +9,216 @@ onmessage = async (e) => {
         await init();
         nes = new Nes();
         await nes.reset();
+        // the panel only shows the tail, so don't hold on to more than that between frames
+        nes.set_trace_capacity(TRACELOG_ROWS);
+        nextFrameAt = performance.now();
         clockLoop();
+    } else if (type == "trace") {
+        if (payload.enabled) {
+            nes.trace_start();
+        } else {
+            nes.trace_stop();
+        }
+    } else if (type == "debug") {
+        debugCommand(payload);
     } else if (type == "drawScreen") {
         const buffer = nes.get_screen_buffer();
         postMessage({ type: "drawScreen", buffer })
     }
 };
 
+const FRAME_MS = 1000 / 60;
+const TRACELOG_ROWS = 50;
+const DISASM_ROWS = 16;
+const SOURCE_RADIUS = 5;
+const PROFILE_ROWS = 12;
+const SEARCH_ROWS = 20;
+// the profile and search reports only need to keep up with someone reading them
+const PANELS_EVERY_FRAMES = 30;
+let searching = false;
+let profiling = false;
+let profileCumulative = false;
+let framesSincePanels = 0;
+let nextFrameAt = 0;
+
+// one emulated frame per 60 Hz tick; a halted CPU still lets the PPU draw
 function clockLoop() {
-    if (!nes.is_running()) return;
+    const wasPaused = nes.is_paused();
+    nes.run_frame();
+    postTraceLines();
+    postMemory();
+
+    if (++framesSincePanels >= PANELS_EVERY_FRAMES) {
+        framesSincePanels = 0;
+        if (profiling) {
+            postProfile();
+        }
+        // the candidates' values keep changing while the game runs
+        if (searching) {
+            postSearch();
+        }
+    }
 
-    const CPU_HZ = 1; // 1789773
-    const FPS = 1; // 60
-    const CPU_CYCLES_PER_FRAME = Math.floor(CPU_HZ / FPS);
-    let cyclesToRun = CPU_CYCLES_PER_FRAME;
+    if (nes.is_paused() && !wasPaused) {
+        postDebugState();
+    }
 
-    const BATCH_SIZE = 500;
+    // if we fall more than a frame behind, drop the backlog instead of racing to catch up
+    const now = performance.now();
+    nextFrameAt = Math.max(nextFrameAt + FRAME_MS, now - FRAME_MS);
+    setTimeout(clockLoop, Math.max(0, nextFrameAt - now));
+}
 
-    function runBatch() {
-        let batchCycles = 0;
-        while (batchCycles < BATCH_SIZE && cyclesToRun > 0) {
-            batchCycles += nes.clock();
-            cyclesToRun -= batchCycles;
-        }
+// the memory panels, read without side effects once a frame
+function postMemory() {
+    postMessage({ type: "updateRam", bytes: nes.peek_range(MemoryDomain.SystemRam, 0, nes.memory_size(MemoryDomain.SystemRam)) });
+    postMessage({ type: "updateVRam", bytes: nes.peek_range(MemoryDomain.Nametables, 0, nes.memory_size(MemoryDomain.Nametables)) });
+}
 
-        if (cyclesToRun > 0) {
-            setTimeout(runBatch, 0);
-        } else {
-            requestAnimationFrame(clockLoop);
-        }
+function postSearch() {
+    postMessage({ type: "search", report: nes.search_report(SEARCH_ROWS) ?? "" });
+}
+
+function postProfile() {
+    postMessage({ type: "profile", report: nes.profile_report(profileCumulative, PROFILE_ROWS) });
+}
+
+function postTraceLines() {
+    const lines = nes.drain_trace();
+    if (lines) {
+        postMessage({ type: "traceLines", lines });
+    }
+}
+
+// where execution stands and what runs next, for the debugger panel
+function postDebugState() {
+    const line = nes.debug_line();
+    const pc = parseInt(line.slice(0, 4), 16);
+
+    postMessage({
+        type: "debugState",
+        paused: nes.is_paused(),
+        reason: nes.break_reason() ?? "",
+        line,
+        disasm: nes.disassemble(pc, DISASM_ROWS),
+        callStack: nes.call_stack(),
+        cdl: nes.cdl_summary() ?? "",
+        cheats: nes.list_cheats(),
+        location: nes.source_location() ?? "",
+        source: nes.source_context(SOURCE_RADIUS) ?? "",
+    });
+}
+
+// a label from the loaded symbols, or a hex address with or without the `$`
+function resolveAddress(text) {
+    const addr = nes.symbol_address(text.trim()) ?? parseInt(text.trim().replace(/^\$/, ""), 16);
+    if (Number.isNaN(addr)) {
+        throw `\`${text}\` is neither an address nor a label`;
+    }
+    return addr;
+}
+
+const SYMBOL_FORMATS = { dbg: SymbolFormat.Ca65Dbg, nl: SymbolFormat.FceuxNl, mlb: SymbolFormat.MesenMlb };
+
+function debugCommand({ command, ...args }) {
+    switch (command) {
+        case "pause": nes.pause(); break;
+        case "resume": nes.resume(); break;
+        case "stepInto": nes.step_into(); break;
+        case "stepOver": nes.step_over(); break;
+        case "stepOut": nes.step_out(); break;
+        case "stepLine": nes.step_line(); break;
+        case "stepLineOver": nes.step_line_over(); break;
+        case "runTo":
+            try {
+                nes.run_to(resolveAddress(args.addr));
+            } catch (message) {
+                postMessage({ type: "debugError", message });
+            }
+            break;
+        case "addBreakpoint":
+            try {
+                // `game.s:12` breaks wherever that line assembled to
+                const sourceLine = args.start.match(/^(.+):(\d+)$/);
+                if (sourceLine) {
+                    const ids = nes.add_source_breakpoint(sourceLine[1], Number(sourceLine[2]), args.condition);
+                    if (ids.length === 0) {
+                        throw `no code came from ${args.start}`;
+                    }
+                    ids.forEach(id => postMessage({ type: "breakpointAdded", id, label: args.label }));
+                    break;
+                }
+
+                const start = resolveAddress(args.start);
+                const end = args.end.trim() ? resolveAddress(args.end) : start;
+                const id = nes.add_breakpoint(args.kind, start, end, args.condition);
+                postMessage({ type: "breakpointAdded", id, label: args.label });
+            } catch (message) {
+                postMessage({ type: "debugError", message });
+            }
+            break;
+        case "removeBreakpoint": nes.remove_breakpoint(args.id); break;
+        case "profilerStart": nes.profiler_start(); profiling = true; break;
+        case "profilerStop": nes.profiler_stop(); profiling = false; break;
+        case "profilerReset": nes.profiler_reset(); break;
+        case "profileCumulative": profileCumulative = args.cumulative; postProfile(); break;
+        case "profileExport": postMessage({ type: "profileExported", text: nes.profile_collapsed(profileCumulative) }); break;
+        case "addCheat":
+            try {
+                nes.add_cheat(args.code);
+            } catch (message) {
+                postMessage({ type: "debugError", message });
+            }
+            break;
+        case "enableCheat": nes.enable_cheat(args.id, args.enabled); break;
+        case "removeCheat": nes.remove_cheat(args.id); break;
+        case "searchStart":
+            nes.search_start(MemoryDomain[args.domain], SearchWidth[args.width], SearchFormat[args.format]);
+            searching = true;
+            postSearch();
+            break;
+        case "searchFilter":
+            try {
+                // a blank value compares with the last snapshot
+                const value = args.value.trim() ? BigInt(args.value.trim()) : undefined;
+                nes.search_filter(SearchCompare[args.compare], value);
+            } catch {
+                postMessage({ type: "debugError", message: `\`${args.value}\` is not a number` });
+            }
+            postSearch();
+            break;
+        case "searchUndo": nes.search_undo(); postSearch(); break;
+        case "searchSnapshot": nes.search_snapshot(); postSearch(); break;
+        case "cdlStart": nes.cdl_start(); break;
+        case "cdlStop": nes.cdl_stop(); break;
+        case "cdlSave": postMessage({ type: "cdlSaved", bytes: nes.cdl_save() }); break;
+        case "cdlLoad":
+            try {
+                nes.cdl_load(args.bytes);
+            } catch (message) {
+                postMessage({ type: "debugError", message });
+            }
+            break;
+        case "addSource":
+            if (!nes.add_source_text(args.name, args.text)) {
+                postMessage({ type: "debugError", message: `the debug info doesn't mention ${args.name}` });
+            }
+            break;
+        case "loadSymbols":
+            try {
+                const format = SYMBOL_FORMATS[args.name.split(".").pop().toLowerCase()];
+                if (format === undefined) {
+                    throw `can't tell what kind of symbol file ${args.name} is`;
+                }
+                const count = nes.load_symbols(format, args.text);
+                postMessage({ type: "consoleLog", msg: `loaded ${count} symbols from ${args.name}` });
+            } catch (message) {
+                postMessage({ type: "debugError", message });
+            }
+            break;
     }
 
-    runBatch();
+    postTraceLines();
+    postMemory();
+    postDebugState();
 }